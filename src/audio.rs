@@ -1,10 +1,37 @@
-use std::collections::BTreeMap;
-use std::fs::File;
+use std::collections::{
+  BTreeMap,
+  HashMap,
+  VecDeque
+};
+use std::fs::{
+  self,
+  File
+};
+use std::io::{
+  BufWriter,
+  Write
+};
 use std::path::{
   Path,
   PathBuf
 };
 use std::sync::Arc;
+use std::sync::atomic::{
+  AtomicBool,
+  AtomicUsize,
+  Ordering
+};
+use std::sync::mpsc::{
+  self,
+  Receiver,
+  Sender,
+  SyncSender,
+  TryRecvError
+};
+use std::time::{
+  Duration,
+  Instant
+};
 
 use anyhow::{
   Context,
@@ -12,9 +39,16 @@ use anyhow::{
   bail
 };
 use rodio::buffer::SamplesBuffer;
+use rodio::cpal::traits::HostTrait;
+use rodio::source::{
+  Done,
+  Stoppable
+};
 use rodio::{
+  DeviceTrait,
   OutputStream,
-  OutputStreamBuilder
+  OutputStreamBuilder,
+  Source
 };
 use rustysynth::{
   SoundFont,
@@ -24,83 +58,372 @@ use rustysynth::{
 use tracing::{
   debug,
   info,
+  trace,
   warn
 };
 
 use crate::config::{
+  AudioBackend,
   AudioConfig,
   InstrumentProfile,
-  SoundFontProfile
+  MetronomeNoteOff,
+  SoundFontProfile,
+  VoiceOverflowPolicy
+};
+use crate::songs::{
+  Hand,
+  SongFile,
+  is_note_off_velocity
 };
-use crate::songs::SongFile;
-
-const COMMON_SOUNDFONT_PATHS: [&str;
-  5] = [
-  "/usr/share/sounds/sf2/FluidR3_GM.\
-   sf2",
-  "/usr/share/sounds/sf2/TimGM6mb.sf2",
-  "/usr/share/sounds/sf2/default-GM.\
-   sf2",
-  "/usr/share/soundfonts/FluidR3_GM.\
-   sf2",
-  "/usr/share/sf2/FluidR3_GM.sf2"
-];
+
+const STREAM_RECONNECT_INTERVAL:
+  Duration = Duration::from_secs(2);
+const USER_VOICE_POLL_INTERVAL:
+  Duration = Duration::from_millis(5);
+const METRONOME_CLICK_FADE_MS: u64 = 4;
+const METRONOME_CLICK_GAP_MS: u64 = 8;
+const LIVE_SYNTH_BLOCK_FRAMES: usize =
+  64;
+const SUSTAIN_BUFFER_DURATION_FACTOR:
+  u64 = 4;
+const LIVE_RECORDING_QUEUE_BLOCKS:
+  usize = 2048;
 
 pub struct AudioEngine {
-  stream:              OutputStream,
+  stream: Option<OutputStream>,
+  stream_lost: Arc<AtomicBool>,
+  device_name:                String,
+  sample_rate_hz:             u32,
+  last_reconnect_attempt:
+    Option<Instant>,
   profiles: BTreeMap<
     String,
     LoadedSoundFontProfile
   >,
-  active_profile_name: String,
-  default_volume:      f32,
-  default_duration_ms: u64,
-  release_duration_ms: u64
+  profile_configs:
+    BTreeMap<String, InstrumentProfile>,
+  soundfont_search_paths: Vec<String>,
+  soundfont_cache:
+    HashMap<PathBuf, Arc<SoundFont>>,
+  tuning_cents:               f32,
+  fallback_to_default_preset: bool,
+  active_profile_name:        String,
+  default_volume:             f32,
+  default_duration_ms:        u64,
+  release_duration_ms:        u64,
+  warmup_ms:                  u64,
+  reference_pitch_hz:         f32,
+  max_user_voices:            usize,
+  user_voice_overflow:
+    VoiceOverflowPolicy,
+  user_voices: VecDeque<UserVoice>,
+  live_synth_enabled:         bool,
+  live_synth: Option<LiveSynth>,
+  sustain_down:               bool,
+  recording: Option<Recording>,
+  recording_active: Arc<AtomicBool>
 }
 
-struct LoadedSoundFontProfile {
-  soundfont: Arc<SoundFont>,
-  profile:   SoundFontProfile
+struct Recording {
+  started_at:  Instant,
+  sample_rate: u32,
+  samples:     Vec<f32>
 }
 
-impl AudioEngine {
-  pub fn new(
-    config: &AudioConfig
-  ) -> Result<Self> {
-    let mut builder =
-      OutputStreamBuilder::from_default_device().context("no audio output device available")?;
+impl Recording {
+  fn mix(
+    &mut self,
+    sample_rate: u32,
+    samples: &[f32]
+  ) {
+    let offset_frames = (self
+      .started_at
+      .elapsed()
+      .as_secs_f64()
+      * f64::from(sample_rate))
+      as usize;
+    self.mix_at(
+      sample_rate,
+      offset_frames,
+      samples
+    );
+  }
+
+  fn mix_at(
+    &mut self,
+    sample_rate: u32,
+    offset_frames: usize,
+    samples: &[f32]
+  ) {
+    if sample_rate != self.sample_rate {
+      return;
+    }
 
-    builder = builder.with_sample_rate(
-      config.sample_rate_hz
+    mix_into(
+      &mut self.samples,
+      offset_frames * 2,
+      samples
     );
+  }
+}
 
-    let mut stream = builder
-      .open_stream_or_fallback()
-      .context(
-        "failed to open audio stream"
-      )?;
+struct LiveSynth {
+  profile_name:   String,
+  commands:       Sender<LiveCommand>,
+  stop:           Arc<AtomicBool>,
+  sounding:       VecDeque<(u8, u8)>,
+  started_at:     Instant,
+  sample_rate:    u32,
+  recorded: Receiver<RecordedBlock>,
+  dropped_blocks: Arc<AtomicUsize>
+}
+
+struct RecordedBlock {
+  frame:   usize,
+  samples:
+    [f32; LIVE_SYNTH_BLOCK_FRAMES * 2]
+}
+
+impl LiveSynth {
+  fn send(
+    &self,
+    command: LiveCommand
+  ) {
+    let _ = self.commands.send(command);
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum LiveCommand {
+  NoteOn {
+    key:      i32,
+    velocity: i32
+  },
+  NoteOff {
+    key: i32
+  },
+  AllNotesOff,
+  Sustain(bool),
+  SetGain(f32)
+}
+
+struct LiveSynthSource {
+  synth:            Synthesizer,
+  channel:          i32,
+  gain:             f32,
+  sample_rate:      u32,
+  commands: Receiver<LiveCommand>,
+  stop:             Arc<AtomicBool>,
+  left:             Vec<f32>,
+  right:            Vec<f32>,
+  position:         usize,
+  frames_rendered:  usize,
+  recording_active: Arc<AtomicBool>,
+  recorded: SyncSender<RecordedBlock>,
+  dropped_blocks:   Arc<AtomicUsize>
+}
+
+impl LiveSynthSource {
+  fn render_block(&mut self) -> bool {
+    if self.stop.load(Ordering::Relaxed)
+    {
+      return false;
+    }
+
+    loop {
+      match self.commands.try_recv() {
+        | Ok(LiveCommand::NoteOn {
+          key,
+          velocity
+        }) => {
+          self.synth.note_on(
+            self.channel,
+            key,
+            velocity
+          )
+        }
+        | Ok(
+          LiveCommand::NoteOff {
+            key
+          }
+        ) => {
+          self
+            .synth
+            .note_off(self.channel, key)
+        }
+        | Ok(
+          LiveCommand::AllNotesOff
+        ) => {
+          self.synth.note_off_all(false)
+        }
+        | Ok(LiveCommand::Sustain(
+          down
+        )) => {
+          self
+            .synth
+            .process_midi_message(
+              self.channel,
+              0xb0,
+              0x40,
+              if down {
+                127
+              } else {
+                0
+              }
+            )
+        }
+        | Ok(LiveCommand::SetGain(
+          gain
+        )) => self.gain = gain,
+        | Err(TryRecvError::Empty) => {
+          break
+        }
+        | Err(
+          TryRecvError::Disconnected
+        ) => return false
+      }
+    }
 
-    stream.log_on_drop(false);
+    self.synth.render(
+      &mut self.left,
+      &mut self.right
+    );
+    self.position = 0;
 
-    let mut profiles = BTreeMap::<
-      String,
-      LoadedSoundFontProfile
-    >::new();
-    for (profile_name, profile) in
-      &config.instrument_profiles
+    if self
+      .recording_active
+      .load(Ordering::Relaxed)
     {
-      let loaded =
-        load_soundfont_profile(
-          profile_name,
-          profile
-        )?;
-      profiles.insert(
-        profile_name.clone(),
-        loaded
-      );
+      let mut block = RecordedBlock {
+        frame:   self.frames_rendered,
+        samples: [0.0;
+          LIVE_SYNTH_BLOCK_FRAMES * 2]
+      };
+      for (frame, (left, right)) in self
+        .left
+        .iter()
+        .zip(&self.right)
+        .enumerate()
+      {
+        block.samples[frame * 2] =
+          (left * self.gain)
+            .clamp(-1.0, 1.0);
+        block.samples[frame * 2 + 1] =
+          (right * self.gain)
+            .clamp(-1.0, 1.0);
+      }
+      if self
+        .recorded
+        .try_send(block)
+        .is_err()
+      {
+        self.dropped_blocks.fetch_add(
+          1,
+          Ordering::Relaxed
+        );
+      }
+    }
+    self.frames_rendered +=
+      LIVE_SYNTH_BLOCK_FRAMES;
+    true
+  }
+}
+
+impl Iterator for LiveSynthSource {
+  type Item = f32;
+
+  fn next(&mut self) -> Option<f32> {
+    if self.position
+      >= self.left.len() * 2
+      && !self.render_block()
+    {
+      return None;
     }
 
-    if !profiles
+    let frame = self.position / 2;
+    let sample = if self
+      .position
+      .is_multiple_of(2)
+    {
+      self.left[frame]
+    } else {
+      self.right[frame]
+    };
+    self.position += 1;
+    Some(
+      (sample * self.gain)
+        .clamp(-1.0, 1.0)
+    )
+  }
+}
+
+impl Source for LiveSynthSource {
+  fn current_span_len(
+    &self
+  ) -> Option<usize> {
+    None
+  }
+
+  fn channels(&self) -> u16 {
+    2
+  }
+
+  fn sample_rate(&self) -> u32 {
+    self.sample_rate
+  }
+
+  fn total_duration(
+    &self
+  ) -> Option<Duration> {
+    None
+  }
+}
+
+struct UserVoice {
+  stop:      Arc<AtomicBool>,
+  remaining: Arc<AtomicUsize>,
+  sustained: bool
+}
+
+impl UserVoice {
+  fn is_finished(&self) -> bool {
+    self
+      .remaining
+      .load(Ordering::Relaxed)
+      == 0
+  }
+}
+
+struct LoadedSoundFontProfile {
+  soundfont:      Arc<SoundFont>,
+  profile:        SoundFontProfile,
+  tuning_cents:   f32,
+  preset_missing: bool
+}
+
+impl AudioEngine {
+  pub fn new(
+    config: &AudioConfig
+  ) -> Result<Self> {
+    let stream_lost =
+      Arc::new(AtomicBool::new(false));
+    let (stream, device_name) =
+      match config.backend {
+        | AudioBackend::Device => {
+          let (stream, device_name) =
+            open_output_stream(
+              config.sample_rate_hz,
+              &stream_lost
+            )?;
+          (Some(stream), device_name)
+        }
+        | AudioBackend::Null => {
+          (None, "null".to_string())
+        }
+      };
+
+    if !config
+      .instrument_profiles
       .contains_key(&config.instrument)
     {
       bail!(
@@ -111,9 +434,78 @@ impl AudioEngine {
       );
     }
 
+    let mut engine = Self {
+      stream,
+      stream_lost,
+      device_name,
+      sample_rate_hz: config
+        .sample_rate_hz,
+      last_reconnect_attempt: None,
+      profiles: BTreeMap::new(),
+      profile_configs: config
+        .instrument_profiles
+        .clone(),
+      soundfont_search_paths: config
+        .soundfont_search_paths
+        .clone(),
+      soundfont_cache: HashMap::new(),
+      tuning_cents:
+        reference_pitch_cents(
+          config.reference_pitch_hz
+        ),
+      fallback_to_default_preset:
+        config
+          .fallback_to_default_preset,
+      active_profile_name: config
+        .instrument
+        .clone(),
+      default_volume: config
+        .master_volume,
+      default_duration_ms: config
+        .note_duration_ms,
+      release_duration_ms: config
+        .release_duration_ms,
+      warmup_ms: config.warmup_ms,
+      reference_pitch_hz: config
+        .reference_pitch_hz,
+      max_user_voices: config
+        .max_user_voices,
+      user_voice_overflow: config
+        .user_voice_overflow,
+      user_voices: VecDeque::new(),
+      live_synth_enabled: config
+        .live_synth,
+      live_synth: None,
+      sustain_down: false,
+      recording: None,
+      recording_active: Arc::new(
+        AtomicBool::new(false)
+      )
+    };
+
+    if config.lazy_load_profiles {
+      engine.ensure_profile_loaded(
+        &config.instrument
+      )?;
+    } else {
+      for profile_name in config
+        .instrument_profiles
+        .keys()
+      {
+        engine.ensure_profile_loaded(
+          profile_name
+        )?;
+      }
+    }
+    engine.warm_up_output();
+    let device_name =
+      &engine.device_name;
+    let profiles = &engine.profiles;
+
     info!(
-      sample_rate = stream.config().sample_rate(),
-      channels = stream.config().channel_count(),
+      device = %device_name,
+      backend = ?config.backend,
+      sample_rate = engine.output_sample_rate().unwrap_or(config.sample_rate_hz),
       profile_name =
         %config.instrument,
       profiles_loaded = profiles.len(),
@@ -121,28 +513,120 @@ impl AudioEngine {
       master_volume = config.master_volume,
       default_note_duration_ms = config.note_duration_ms,
       release_duration_ms = config.release_duration_ms,
+      reference_pitch_hz = config.reference_pitch_hz,
       "audio engine initialized",
     );
 
-    Ok(Self {
-      stream,
-      profiles,
-      active_profile_name: config
-        .instrument
-        .clone(),
-      default_volume: config
-        .master_volume,
-      default_duration_ms: config
-        .note_duration_ms,
-      release_duration_ms: config
-        .release_duration_ms
-    })
+    Ok(engine)
+  }
+
+  fn ensure_profile_loaded(
+    &mut self,
+    profile_name: &str
+  ) -> Result<()> {
+    if self
+      .profiles
+      .contains_key(profile_name)
+    {
+      return Ok(());
+    }
+
+    let Some(profile) = self
+      .profile_configs
+      .get(profile_name)
+    else {
+      bail!(
+        "unknown audio profile \
+         '{profile_name}'"
+      );
+    };
+
+    let mut loaded =
+      load_soundfont_profile(
+        profile_name,
+        profile,
+        &self.soundfont_search_paths,
+        &mut self.soundfont_cache
+      )?;
+    loaded.tuning_cents =
+      self.tuning_cents;
+    check_profile_preset(
+      profile_name,
+      &mut loaded,
+      self.fallback_to_default_preset
+    );
+    self.profiles.insert(
+      profile_name.to_string(),
+      loaded
+    );
+    Ok(())
   }
 
   pub fn master_volume(&self) -> f32 {
     self.default_volume
   }
 
+  pub fn recover_lost_stream(
+    &mut self
+  ) -> Option<Result<String>> {
+    if self.stream.is_none()
+      || !self
+        .stream_lost
+        .load(Ordering::Relaxed)
+    {
+      return None;
+    }
+
+    let now = Instant::now();
+    if self
+      .last_reconnect_attempt
+      .is_some_and(|last| {
+        now.duration_since(last)
+          < STREAM_RECONNECT_INTERVAL
+      })
+    {
+      return None;
+    }
+    let first_attempt = self
+      .last_reconnect_attempt
+      .is_none();
+    self.last_reconnect_attempt =
+      Some(now);
+
+    if first_attempt {
+      warn!(
+        device = %self.device_name,
+        "audio stream lost; attempting \
+         reconnect"
+      );
+    }
+
+    let stream_lost =
+      Arc::new(AtomicBool::new(false));
+    match open_output_stream(
+      self.sample_rate_hz,
+      &stream_lost
+    ) {
+      | Ok((stream, device_name)) => {
+        self.flush_live_synth();
+        self.stream = Some(stream);
+        self.stream_lost = stream_lost;
+        self.device_name =
+          device_name.clone();
+        self.last_reconnect_attempt =
+          None;
+        self.warm_up_output();
+        info!(device = %device_name, "audio stream reconnected");
+        Some(Ok(device_name))
+      }
+      | Err(error) => {
+        warn!(%error, "audio stream reconnect failed");
+        first_attempt
+          .then_some(Err(error))
+      }
+    }
+  }
+
   pub fn active_profile_name(
     &self
   ) -> &str {
@@ -153,7 +637,7 @@ impl AudioEngine {
     &self
   ) -> Vec<String> {
     self
-      .profiles
+      .profile_configs
       .keys()
       .cloned()
       .collect::<Vec<_>>()
@@ -165,9 +649,16 @@ impl AudioEngine {
     if let Some(profile) =
       self.current_profile()
     {
+      let fallback =
+        if profile.preset_missing {
+          " (preset not found, using \
+           default)"
+        } else {
+          ""
+        };
       format!(
         "{} (soundfont bank={} \
-         preset={} channel={})",
+         preset={} channel={}){fallback}",
         self.active_profile_name,
         profile.profile.bank,
         profile.profile.preset,
@@ -185,16 +676,15 @@ impl AudioEngine {
     &mut self,
     profile_name: &str
   ) -> Result<()> {
-    if !self
-      .profiles
-      .contains_key(profile_name)
+    self.ensure_profile_loaded(
+      profile_name
+    )?;
+
+    if self.active_profile_name
+      != profile_name
     {
-      bail!(
-        "unknown audio profile \
-         '{profile_name}'"
-      );
+      self.flush_live_synth();
     }
-
     self.active_profile_name =
       profile_name.to_string();
     info!(
@@ -211,84 +701,850 @@ impl AudioEngine {
     let clamped =
       volume.clamp(0.0, 2.5);
     self.default_volume = clamped;
+    if let Some(gain) = self
+      .current_profile()
+      .map(|profile| {
+        live_synth_gain(
+          profile, clamped
+        )
+      })
+      && let Some(live) =
+        &self.live_synth
+    {
+      live.send(LiveCommand::SetGain(
+        gain
+      ));
+    }
     info!(
       master_volume = clamped,
       "master volume updated"
     );
   }
 
-  pub fn play_metronome_tick(
+  pub fn start_note(
     &mut self,
-    accent: bool
+    midi_note: u8,
+    velocity: u8
   ) {
-    let midi_note = if accent {
-      94
-    } else {
-      86
+    if !self.live_synth_enabled
+      || self.stream.is_none()
+    {
+      self.play_note_with_velocity(
+        midi_note, velocity
+      );
+      return;
+    }
+
+    if is_note_off_velocity(velocity) {
+      self.stop_note(midi_note);
+      return;
+    }
+
+    if self.is_stream_lost() {
+      return;
+    }
+
+    let Some(profile) =
+      self.current_profile()
+    else {
+      return;
     };
-    let velocity = if accent {
-      124
-    } else {
-      92
+    let Some(key) = profile
+      .profile
+      .fit_note_to_range(midi_note)
+    else {
+      debug!(
+        midi_note,
+        profile = %self.active_profile_name,
+        "note outside profile range; dropped",
+      );
+      return;
     };
-    let duration_ms = if accent {
-      115
-    } else {
-      90
+    let shaped_velocity = profile
+      .profile
+      .shape_velocity(i32::from(
+        velocity.clamp(1, 127)
+      ));
+
+    if let Err(error) =
+      self.ensure_live_synth()
+    {
+      warn!(%error, "failed starting live synthesizer; using note buffers");
+      self.live_synth_enabled = false;
+      self.play_note_with_velocity(
+        midi_note, velocity
+      );
+      return;
+    }
+
+    let max_user_voices =
+      self.max_user_voices;
+    let overflow =
+      self.user_voice_overflow;
+    let Some(live) =
+      self.live_synth.as_mut()
+    else {
+      return;
     };
 
-    self
-      .play_note_with_velocity_duration(
+    let (admitted, stolen) =
+      admit_live_note(
+        &mut live.sounding,
         midi_note,
-        velocity,
-        duration_ms
+        key,
+        max_user_voices,
+        overflow
       );
+    if let Some((_, stolen)) = stolen
+      && !key_is_sounding(
+        &live.sounding,
+        stolen
+      )
+    {
+      live.send(LiveCommand::NoteOff {
+        key: i32::from(stolen)
+      });
+      trace!(
+        stolen,
+        "stole oldest held note"
+      );
+    }
+    if !admitted {
+      debug!(
+        midi_note = key,
+        max_user_voices,
+        "held note limit reached; \
+         note dropped"
+      );
+      return;
+    }
+
+    live.send(LiveCommand::NoteOn {
+      key:      i32::from(key),
+      velocity: shaped_velocity
+    });
   }
 
-  pub fn play_note(
+  pub fn stop_note(
     &mut self,
     midi_note: u8
   ) {
-    self
-      .play_note_with_velocity_duration(
-        midi_note,
-        112,
-        self.default_duration_ms
-      );
-  }
-
+    let Some(live) =
+      self.live_synth.as_mut()
+    else {
+      return;
+    };
+    let Some(index) = live
+      .sounding
+      .iter()
+      .position(|(input, _)| {
+        *input == midi_note
+      })
+    else {
+      return;
+    };
+    let Some((_, key)) =
+      live.sounding.remove(index)
+    else {
+      return;
+    };
+
+    // Clamped notes can share a key;
+    // keep it until its last input
+    // lets go.
+    if !key_is_sounding(
+      &live.sounding,
+      key
+    ) {
+      live.send(LiveCommand::NoteOff {
+        key: i32::from(key)
+      });
+    }
+  }
+
+  /// Lifting the pedal stops every
+  /// buffered voice started while it
+  /// was down, even for keys still
+  /// held: buffered notes never see a
+  /// key-up, so this is their release.
+  pub fn set_sustain(
+    &mut self,
+    down: bool
+  ) {
+    if self.sustain_down == down {
+      return;
+    }
+    self.sustain_down = down;
+
+    if let Some(live) = &self.live_synth
+    {
+      live.send(LiveCommand::Sustain(
+        down
+      ));
+    }
+
+    if !down {
+      for voice in &self.user_voices {
+        if voice.sustained {
+          voice.stop.store(
+            true,
+            Ordering::Relaxed
+          );
+        }
+      }
+    }
+    debug!(down, "sustain pedal");
+  }
+
+  pub fn stop_all_notes(&mut self) {
+    if let Some(live) =
+      self.live_synth.as_mut()
+    {
+      live.sounding.clear();
+      live
+        .send(LiveCommand::AllNotesOff);
+    }
+
+    for voice in
+      self.user_voices.drain(..)
+    {
+      voice
+        .stop
+        .store(true, Ordering::Relaxed);
+    }
+  }
+
+  fn ensure_live_synth(
+    &mut self
+  ) -> Result<()> {
+    if self
+      .live_synth
+      .as_ref()
+      .is_some_and(|live| {
+        live.profile_name
+          == self.active_profile_name
+          && !live
+            .stop
+            .load(Ordering::Relaxed)
+      })
+    {
+      return Ok(());
+    }
+
+    self.flush_live_synth();
+    let Some(sample_rate) =
+      self.output_sample_rate()
+    else {
+      bail!("no audio output stream");
+    };
+    let source = self
+      .start_live_synth(sample_rate)?;
+    if let Some(stream) = &self.stream {
+      stream.mixer().add(source);
+    }
+    Ok(())
+  }
+
+  /// Builds the live synthesizer state
+  /// and returns its source for the
+  /// caller to mix; a pedal already
+  /// down is replayed into it.
+  fn start_live_synth(
+    &mut self,
+    sample_rate: u32
+  ) -> Result<LiveSynthSource> {
+    let Some(profile) =
+      self.current_profile()
+    else {
+      bail!(
+        "active profile {} is not \
+         loaded",
+        self.active_profile_name
+      );
+    };
+
+    let synth = build_synthesizer(
+      profile,
+      sample_rate
+    )?;
+    let (commands, receiver) =
+      mpsc::channel();
+    let (recorded_sender, recorded) =
+      mpsc::sync_channel(
+        LIVE_RECORDING_QUEUE_BLOCKS
+      );
+    let dropped_blocks =
+      Arc::new(AtomicUsize::new(0));
+    let stop =
+      Arc::new(AtomicBool::new(false));
+    let source = LiveSynthSource {
+      synth,
+      channel: i32::from(
+        profile.profile.channel
+      ),
+      gain: live_synth_gain(
+        profile,
+        self.default_volume
+      ),
+      sample_rate,
+      commands: receiver,
+      stop: Arc::clone(&stop),
+      left: vec![
+        0.0;
+        LIVE_SYNTH_BLOCK_FRAMES
+      ],
+      right: vec![
+        0.0;
+        LIVE_SYNTH_BLOCK_FRAMES
+      ],
+      position: LIVE_SYNTH_BLOCK_FRAMES
+        * 2,
+      frames_rendered: 0,
+      recording_active: Arc::clone(
+        &self.recording_active
+      ),
+      recorded: recorded_sender,
+      dropped_blocks: Arc::clone(
+        &dropped_blocks
+      )
+    };
+
+    info!(
+      profile = %self.active_profile_name,
+      sample_rate,
+      "live synthesizer started"
+    );
+    if self.sustain_down {
+      let _ = commands.send(
+        LiveCommand::Sustain(true)
+      );
+    }
+    self.live_synth = Some(LiveSynth {
+      profile_name: self
+        .active_profile_name
+        .clone(),
+      commands,
+      stop,
+      sounding: VecDeque::new(),
+      started_at: Instant::now(),
+      sample_rate,
+      recorded,
+      dropped_blocks
+    });
+    Ok(source)
+  }
+
+  fn flush_live_synth(&mut self) {
+    self.collect_live_recording();
+    if let Some(live) =
+      self.live_synth.take()
+    {
+      live
+        .send(LiveCommand::AllNotesOff);
+      live
+        .stop
+        .store(true, Ordering::Relaxed);
+      debug!(
+        profile = %live.profile_name,
+        "live synthesizer flushed"
+      );
+    }
+  }
+
+  pub fn play_metronome_tick(
+    &mut self,
+    accent: bool,
+    note_off: MetronomeNoteOff,
+    beat_ms: u64
+  ) {
+    let midi_note = if accent {
+      94
+    } else {
+      86
+    };
+    let velocity = if accent {
+      124
+    } else {
+      92
+    };
+    let duration_ms = if accent {
+      115
+    } else {
+      90
+    };
+
+    if note_off
+      == MetronomeNoteOff::Release
+    {
+      self
+        .play_note_with_velocity_duration(
+          midi_note,
+          velocity,
+          duration_ms
+        );
+      return;
+    }
+
+    let Some(sample_rate) =
+      self.output_sample_rate()
+    else {
+      return;
+    };
+    let Some(profile) =
+      self.current_profile()
+    else {
+      return;
+    };
+    let Some(midi_note) = profile
+      .profile
+      .fit_note_to_range(midi_note)
+    else {
+      return;
+    };
+
+    match render_soundfont_click_samples(
+      profile,
+      midi_note,
+      velocity,
+      metronome_click_ms(
+        duration_ms,
+        beat_ms
+      ),
+      sample_rate,
+      self.default_volume
+    ) {
+      | Ok(samples) => {
+        self.queue_samples(
+          sample_rate,
+          samples
+        )
+      }
+      | Err(error) => {
+        warn!(%error, midi_note, "failed rendering metronome click")
+      }
+    }
+  }
+
+  pub fn play_note(
+    &mut self,
+    midi_note: u8
+  ) {
+    self.play_note_with_velocity(
+      midi_note, 112
+    );
+  }
+
+  pub fn play_note_with_velocity(
+    &mut self,
+    midi_note: u8,
+    velocity: u8
+  ) {
+    let Some(sample_rate) =
+      self.output_sample_rate()
+    else {
+      return;
+    };
+    let duration_ms =
+      if self.sustain_down {
+        self.default_duration_ms
+        * SUSTAIN_BUFFER_DURATION_FACTOR
+      } else {
+        self.default_duration_ms
+      };
+    let Some(samples) = self
+      .render_note(
+        midi_note,
+        velocity,
+        duration_ms,
+        sample_rate,
+        &self.active_profile_name,
+        1.0
+      )
+    else {
+      return;
+    };
+
+    if self.is_stream_lost() {
+      return;
+    }
+
+    if !self.reserve_user_voice() {
+      debug!(
+        midi_note,
+        max_user_voices =
+          self.max_user_voices,
+        "user voice limit reached; \
+         note dropped"
+      );
+      return;
+    }
+
+    self.record_samples(
+      sample_rate,
+      &samples
+    );
+    let stop =
+      Arc::new(AtomicBool::new(false));
+    let remaining =
+      Arc::new(AtomicUsize::new(1));
+    let stop_flag = Arc::clone(&stop);
+    let source = Done::new(
+      SamplesBuffer::new(
+        2,
+        sample_rate,
+        samples
+      )
+      .stoppable()
+      .periodic_access(
+        USER_VOICE_POLL_INTERVAL,
+        move |voice: &mut Stoppable<
+          SamplesBuffer
+        >| {
+          if stop_flag
+            .load(Ordering::Relaxed)
+          {
+            voice.stop();
+          }
+        }
+      ),
+      Arc::clone(&remaining)
+    );
+
+    if let Some(stream) = &self.stream {
+      stream.mixer().add(source);
+    }
+    self.user_voices.push_back(
+      UserVoice {
+        stop,
+        remaining,
+        sustained: self.sustain_down
+      }
+    );
+  }
+
   pub fn play_note_with_velocity_duration(
     &mut self,
     midi_note: u8,
     velocity: u8,
     duration_ms: u64
   ) {
-    let sample_rate = self
-      .stream
-      .config()
-      .sample_rate();
-    let frequency_hz =
-      midi_to_frequency_hz(midi_note);
-
-    debug!(
+    let profile_name =
+      self.active_profile_name.clone();
+    self.play_note_on_profile(
+      &profile_name,
       midi_note,
       velocity,
-      duration_ms,
-      frequency_hz,
-      profile = %self.active_profile_name,
-      "rendering soundfont note",
+      duration_ms
     );
+  }
 
-    let Some(active_profile) =
-      self.current_profile()
+  pub fn play_note_on_profile(
+    &mut self,
+    profile_name: &str,
+    midi_note: u8,
+    velocity: u8,
+    duration_ms: u64
+  ) {
+    self
+      .play_note_on_profile_with_gain(
+        profile_name,
+        midi_note,
+        velocity,
+        duration_ms,
+        1.0
+      );
+  }
+
+  pub fn play_note_on_profile_with_gain(
+    &mut self,
+    profile_name: &str,
+    midi_note: u8,
+    velocity: u8,
+    duration_ms: u64,
+    gain: f32
+  ) {
+    if let Err(error) = self
+      .ensure_profile_loaded(
+        profile_name
+      )
+    {
+      warn!(%error, profile = %profile_name, "failed loading profile for note");
+      return;
+    }
+
+    let Some(sample_rate) =
+      self.output_sample_rate()
+    else {
+      return;
+    };
+
+    if let Some(samples) = self
+      .render_note(
+        midi_note,
+        velocity,
+        duration_ms,
+        sample_rate,
+        profile_name,
+        gain
+      )
+    {
+      self.record_samples(
+        sample_rate,
+        &samples
+      );
+      self.queue_samples(
+        sample_rate,
+        samples
+      );
+    }
+  }
+
+  pub fn is_recording(&self) -> bool {
+    self.recording.is_some()
+  }
+
+  pub fn start_recording(
+    &mut self
+  ) -> Result<()> {
+    let Some(sample_rate) =
+      self.output_sample_rate()
     else {
+      bail!(
+        "recording needs an audio \
+         output device"
+      );
+    };
+
+    self.recording = Some(Recording {
+      started_at: Instant::now(),
+      sample_rate,
+      samples: Vec::new()
+    });
+    self
+      .recording_active
+      .store(true, Ordering::Relaxed);
+    info!(
+      sample_rate,
+      "recording started"
+    );
+    Ok(())
+  }
+
+  pub fn stop_recording(
+    &mut self,
+    path: &Path
+  ) -> Result<Option<f32>> {
+    self.collect_live_recording();
+    self
+      .recording_active
+      .store(false, Ordering::Relaxed);
+    let Some(recording) =
+      self.recording.take()
+    else {
+      bail!("not recording");
+    };
+
+    let frames =
+      recording.samples.len() / 2;
+    if frames == 0 {
+      return Ok(None);
+    }
+
+    if let Some(parent) = path.parent()
+    {
+      fs::create_dir_all(parent)
+        .with_context(|| {
+          format!(
+            "failed creating \
+             recording directory {}",
+            parent.display()
+          )
+        })?;
+    }
+    write_wav(
+      path,
+      recording.sample_rate,
+      &recording.samples
+    )?;
+    info!(path = %path.display(), frames, "recording saved");
+    Ok(Some(
+      frames as f32
+        / recording.sample_rate as f32
+    ))
+  }
+
+  /// Moves live-synth blocks queued
+  /// by the audio thread into the
+  /// recording, placing each block by
+  /// the synth's own frame counter.
+  pub fn collect_live_recording(
+    &mut self
+  ) {
+    let Some(live) = &self.live_synth
+    else {
+      return;
+    };
+
+    let dropped = live
+      .dropped_blocks
+      .swap(0, Ordering::Relaxed);
+    if dropped > 0 {
       warn!(
-        profile = %self.active_profile_name,
-        "active profile missing while rendering note"
+        dropped,
+        "live recording queue \
+         overflowed; blocks were lost"
       );
+    }
+
+    let Some(recording) =
+      self.recording.as_mut()
+    else {
+      while live
+        .recorded
+        .try_recv()
+        .is_ok()
+      {}
       return;
     };
 
+    let rate =
+      f64::from(live.sample_rate);
+    let lead_frames = (recording
+      .started_at
+      .saturating_duration_since(
+        live.started_at
+      )
+      .as_secs_f64()
+      * rate)
+      as usize;
+    let lag_frames = (live
+      .started_at
+      .saturating_duration_since(
+        recording.started_at
+      )
+      .as_secs_f64()
+      * rate)
+      as usize;
+    while let Ok(block) =
+      live.recorded.try_recv()
+    {
+      let Some(frame) = (block.frame
+        + lag_frames)
+        .checked_sub(lead_frames)
+      else {
+        continue;
+      };
+      recording.mix_at(
+        live.sample_rate,
+        frame,
+        &block.samples
+      );
+    }
+  }
+
+  fn record_samples(
+    &mut self,
+    sample_rate: u32,
+    samples: &[f32]
+  ) {
+    if let Some(recording) =
+      self.recording.as_mut()
+    {
+      recording
+        .mix(sample_rate, samples);
+    }
+  }
+
+  fn reserve_user_voice(
+    &mut self
+  ) -> bool {
+    self.user_voices.retain(|voice| {
+      !voice.is_finished()
+    });
+
+    if self.user_voices.len()
+      < self.max_user_voices
+    {
+      return true;
+    }
+
+    match self.user_voice_overflow {
+      | VoiceOverflowPolicy::DropNew => {
+        false
+      }
+      | VoiceOverflowPolicy::StealOldest => {
+        if let Some(oldest) =
+          self.user_voices.pop_front()
+        {
+          oldest
+            .stop
+            .store(true, Ordering::Relaxed);
+          trace!(
+            active_user_voices =
+              self.user_voices.len(),
+            "stole oldest user voice"
+          );
+        }
+        true
+      }
+    }
+  }
+
+  fn render_note(
+    &self,
+    midi_note: u8,
+    velocity: u8,
+    duration_ms: u64,
+    sample_rate: u32,
+    profile_name: &str,
+    gain: f32
+  ) -> Option<Vec<f32>> {
+    if is_note_off_velocity(velocity) {
+      trace!(
+        midi_note,
+        "velocity 0 is a note-off; \
+         nothing to render"
+      );
+      return None;
+    }
+
+    let Some(active_profile) =
+      self.profiles.get(profile_name)
+    else {
+      warn!(
+        profile = %profile_name,
+        "profile missing while rendering note"
+      );
+      return None;
+    };
+
+    let Some(midi_note) =
+      active_profile
+        .profile
+        .fit_note_to_range(midi_note)
+    else {
+      debug!(
+        midi_note,
+        profile = %profile_name,
+        "note outside profile range; dropped",
+      );
+      return None;
+    };
+    let frequency_hz =
+      midi_to_frequency_hz(
+        midi_note,
+        self.reference_pitch_hz
+      );
+
+    debug!(
+      midi_note,
+      velocity,
+      duration_ms,
+      frequency_hz,
+      profile = %profile_name,
+      "rendering soundfont note",
+    );
+
     match render_soundfont_note_samples(
       active_profile,
       midi_note,
@@ -296,19 +1552,12 @@ impl AudioEngine {
       duration_ms,
       self.release_duration_ms,
       sample_rate,
-      self.default_volume
+      self.default_volume * gain
     ) {
-      | Ok(samples) => {
-        self.stream.mixer().add(
-          SamplesBuffer::new(
-            2,
-            sample_rate,
-            samples
-          )
-        );
-      }
+      | Ok(samples) => Some(samples),
       | Err(error) => {
         warn!(%error, midi_note, velocity, duration_ms, "failed rendering note");
+        None
       }
     }
   }
@@ -318,10 +1567,11 @@ impl AudioEngine {
     &mut self,
     song: &SongFile
   ) {
-    let sample_rate = self
-      .stream
-      .config()
-      .sample_rate();
+    let Some(sample_rate) =
+      self.output_sample_rate()
+    else {
+      return;
+    };
 
     info!(
       song_id = %song.meta.id,
@@ -346,7 +1596,8 @@ impl AudioEngine {
       active_profile,
       song,
       sample_rate,
-      self.default_volume,
+      self.default_volume
+        * song.meta.gain(),
       self.default_duration_ms,
       self.release_duration_ms
     ) {
@@ -368,12 +1619,9 @@ impl AudioEngine {
           "song preview rendered",
         );
 
-        self.stream.mixer().add(
-          SamplesBuffer::new(
-            2,
-            sample_rate,
-            samples
-          )
+        self.queue_samples(
+          sample_rate,
+          samples
         );
       }
       | Err(error) => {
@@ -382,6 +1630,196 @@ impl AudioEngine {
     }
   }
 
+  pub fn render_song_stems(
+    &self,
+    song: &SongFile,
+    out_dir: &Path
+  ) -> Result<Vec<PathBuf>> {
+    let Some(active_profile) =
+      self.current_profile()
+    else {
+      bail!(
+        "active profile {} is not \
+         loaded",
+        self.active_profile_name
+      );
+    };
+
+    fs::create_dir_all(out_dir)
+      .with_context(|| {
+        format!(
+          "failed creating stem \
+           directory {}",
+          out_dir.display()
+        )
+      })?;
+
+    let mut written = Vec::new();
+    for (label, stem) in
+      song_stems(song)
+    {
+      let samples =
+        render_soundfont_song_samples(
+          active_profile,
+          &stem,
+          self.sample_rate_hz,
+          self.default_volume
+            * song.meta.gain(),
+          self.default_duration_ms,
+          self.release_duration_ms
+        )?;
+      if samples.is_empty() {
+        continue;
+      }
+
+      let path = out_dir.join(format!(
+        "{}_{label}.wav",
+        song.meta.id
+      ));
+      write_wav(
+        &path,
+        self.sample_rate_hz,
+        &samples
+      )?;
+      info!(
+        song_id = %song.meta.id,
+        stem = %label,
+        path = %path.display(),
+        "song stem rendered"
+      );
+      written.push(path);
+    }
+
+    Ok(written)
+  }
+
+  pub fn play_sequence(
+    &mut self,
+    notes: &[(u8, u8, u64, u64)]
+  ) {
+    let Some(sample_rate) =
+      self.output_sample_rate()
+    else {
+      return;
+    };
+
+    let Some(active_profile) =
+      self.current_profile()
+    else {
+      warn!(
+        profile = %self.active_profile_name,
+        "active profile missing while rendering sequence"
+      );
+      return;
+    };
+
+    let (actions, max_frame) =
+      sequence_actions(
+        notes,
+        sample_rate
+      );
+    if actions.is_empty() {
+      return;
+    }
+
+    let total_frames = max_frame
+      .saturating_add(ms_to_frames(
+        self
+          .release_duration_ms
+          .max(160),
+        sample_rate
+      ));
+    match render_scheduled_actions(
+      active_profile,
+      sample_rate,
+      total_frames,
+      actions,
+      self.default_volume
+    ) {
+      | Ok(samples) => {
+        debug!(
+          notes = notes.len(),
+          frames = samples.len() / 2,
+          "sequence rendered"
+        );
+        self.queue_samples(
+          sample_rate,
+          samples
+        );
+      }
+      | Err(error) => {
+        warn!(%error, notes = notes.len(), "failed rendering sequence");
+      }
+    }
+  }
+
+  fn is_stream_lost(&self) -> bool {
+    let lost = self
+      .stream_lost
+      .load(Ordering::Relaxed);
+    if lost {
+      warn!(
+        device = %self.device_name,
+        "audio stream lost; dropping \
+         rendered samples"
+      );
+    }
+    lost
+  }
+
+  fn warm_up_output(&self) {
+    let Some(sample_rate) =
+      self.output_sample_rate()
+    else {
+      return;
+    };
+    let samples = silent_warmup_samples(
+      self.warmup_ms,
+      sample_rate
+    );
+    if samples.is_empty() {
+      return;
+    }
+
+    debug!(
+      warmup_ms = self.warmup_ms,
+      frames = samples.len() / 2,
+      "priming audio output"
+    );
+    self.queue_samples(
+      sample_rate,
+      samples
+    );
+  }
+
+  fn output_sample_rate(
+    &self
+  ) -> Option<u32> {
+    self.stream.as_ref().map(|stream| {
+      stream.config().sample_rate()
+    })
+  }
+
+  fn queue_samples(
+    &self,
+    sample_rate: u32,
+    samples: Vec<f32>
+  ) {
+    if self.is_stream_lost() {
+      return;
+    }
+
+    if let Some(stream) = &self.stream {
+      stream.mixer().add(
+        SamplesBuffer::new(
+          2,
+          sample_rate,
+          samples
+        )
+      );
+    }
+  }
+
   fn current_profile(
     &self
   ) -> Option<&LoadedSoundFontProfile>
@@ -392,79 +1830,169 @@ impl AudioEngine {
   }
 }
 
+fn open_output_stream(
+  sample_rate_hz: u32,
+  stream_lost: &Arc<AtomicBool>
+) -> Result<(OutputStream, String)> {
+  let device =
+    rodio::cpal::default_host()
+      .default_output_device()
+      .context(
+        "no audio output device \
+         available"
+      )?;
+  let device_name =
+    device.name().unwrap_or_else(
+      |_| "default output".to_string()
+    );
+
+  let lost_flag =
+    Arc::clone(stream_lost);
+  let builder =
+    OutputStreamBuilder::from_device(
+      device
+    )
+    .with_context(|| {
+      format!(
+        "audio output device \
+         '{device_name}' has no usable \
+         config"
+      )
+    })?
+    .with_sample_rate(sample_rate_hz)
+    .with_error_callback(move |error| {
+      warn!(%error, "audio stream error");
+      lost_flag
+        .store(true, Ordering::Relaxed);
+    });
+
+  let mut stream = builder
+    .open_stream_or_fallback()
+    .context(
+      "failed to open audio stream"
+    )?;
+
+  stream.log_on_drop(false);
+  Ok((stream, device_name))
+}
+
 pub fn midi_to_frequency_hz(
-  midi_note: u8
+  midi_note: u8,
+  reference_pitch_hz: f32
 ) -> f32 {
   let n = f32::from(midi_note);
-  440.0
+  reference_pitch_hz
     * 2.0_f32.powf((n - 69.0) / 12.0)
 }
 
+fn reference_pitch_cents(
+  reference_pitch_hz: f32
+) -> f32 {
+  1200.0
+    * (reference_pitch_hz / 440.0)
+      .log2()
+}
+
 fn load_soundfont_profile(
   profile_name: &str,
-  profile: &InstrumentProfile
+  profile: &InstrumentProfile,
+  search_paths: &[String],
+  cache: &mut HashMap<
+    PathBuf,
+    Arc<SoundFont>
+  >
 ) -> Result<LoadedSoundFontProfile> {
   match profile {
     | InstrumentProfile::Soundfont(
       sf2
     ) => {
-      load_soundfont(profile_name, sf2)
+      load_soundfont(
+        profile_name,
+        sf2,
+        search_paths,
+        cache
+      )
     }
   }
 }
 
 fn load_soundfont(
   profile_name: &str,
-  profile: &SoundFontProfile
+  profile: &SoundFontProfile,
+  search_paths: &[String],
+  cache: &mut HashMap<
+    PathBuf,
+    Arc<SoundFont>
+  >
 ) -> Result<LoadedSoundFontProfile> {
-  let (soundfont_path, used_fallback) =
-    resolve_soundfont_path(
-      &profile.soundfont_path
+  let (
+    soundfont_path,
+    matched_search_path
+  ) = resolve_soundfont_path(
+    &profile.soundfont_path,
+    search_paths
+  )
+  .with_context(|| {
+    let common_paths =
+      search_paths.join(", ");
+    format!(
+      "missing SoundFont for profile \
+       '{profile_name}'. looked for \
+       '{}' and fallback paths: \
+       {common_paths}. place an SF2 \
+       at the configured path or \
+       update [audio.\
+       instrument_profiles.\
+       {profile_name}]",
+      profile.soundfont_path
     )
-    .with_context(|| {
-      let common_paths =
-        COMMON_SOUNDFONT_PATHS
-          .join(", ");
-      format!(
-        "missing SoundFont for \
-         profile '{profile_name}'. \
-         looked for '{}' and fallback \
-         paths: {common_paths}. place \
-         an SF2 at the configured \
-         path or update \
-         [audio.instrument_profiles.\
-         {profile_name}]",
-        profile.soundfont_path
-      )
-    })?;
+  })?;
 
-  if used_fallback {
+  if let Some(search_path) =
+    matched_search_path
+  {
     warn!(
       profile_name,
       configured_path = %profile.soundfont_path,
+      search_path = %search_path,
       fallback_path = %soundfont_path.display(),
-      "configured SoundFont path missing; using fallback",
+      "configured SoundFont path missing; using search path match",
     );
   }
 
-  let mut file =
-    File::open(&soundfont_path)
-      .with_context(|| {
-        format!(
-          "failed opening SoundFont {}",
-          soundfont_path.display()
-        )
-      })?;
-
-  let soundfont =
-    SoundFont::new(&mut file)
-      .with_context(|| {
-        format!(
-          "failed parsing SoundFont {}",
-          soundfont_path.display()
-        )
-      })?;
-
+  let soundfont = if let Some(cached) =
+    cache.get(&soundfont_path)
+  {
+    debug!(profile_name, path = %soundfont_path.display(), "reusing loaded SoundFont");
+    Arc::clone(cached)
+  } else {
+    let mut file =
+      File::open(&soundfont_path)
+        .with_context(|| {
+          format!(
+            "failed opening SoundFont \
+             {}",
+            soundfont_path.display()
+          )
+        })?;
+
+    let soundfont = Arc::new(
+      SoundFont::new(&mut file)
+        .with_context(|| {
+          format!(
+            "failed parsing SoundFont \
+             {}",
+            soundfont_path.display()
+          )
+        })?
+    );
+    cache.insert(
+      soundfont_path.clone(),
+      Arc::clone(&soundfont)
+    );
+    soundfont
+  };
+
   let soundfont_info =
     soundfont.get_info();
   info!(
@@ -482,34 +2010,115 @@ fn load_soundfont(
   );
 
   Ok(LoadedSoundFontProfile {
-    soundfont: Arc::new(soundfont),
-    profile:   profile.clone()
+    soundfont,
+    profile: profile.clone(),
+    tuning_cents: 0.0,
+    preset_missing: false
   })
 }
 
+fn check_profile_preset(
+  profile_name: &str,
+  loaded: &mut LoadedSoundFontProfile,
+  fallback_to_default: bool
+) {
+  let presets =
+    loaded.soundfont.get_presets();
+  let has_preset =
+    |bank: u8, preset: u8| {
+      presets.iter().any(|entry| {
+        entry.get_bank_number()
+          == i32::from(bank)
+          && entry.get_patch_number()
+            == i32::from(preset)
+      })
+    };
+
+  let bank = loaded.profile.bank;
+  let preset = loaded.profile.preset;
+  if has_preset(bank, preset) {
+    return;
+  }
+
+  warn!(
+    profile_name,
+    bank,
+    preset,
+    available_presets = presets.len(),
+    fallback_to_default,
+    "configured bank/preset not found \
+     in SoundFont",
+  );
+  if !fallback_to_default {
+    return;
+  }
+
+  let fallback = if has_preset(0, 0) {
+    Some((0, 0))
+  } else {
+    presets
+      .iter()
+      .map(|entry| {
+        (
+          entry.get_bank_number(),
+          entry.get_patch_number()
+        )
+      })
+      .min()
+      .and_then(|(bank, preset)| {
+        Some((
+          u8::try_from(bank).ok()?,
+          u8::try_from(preset).ok()?
+        ))
+      })
+  };
+
+  if let Some((bank, preset)) = fallback
+  {
+    loaded.profile.bank = bank;
+    loaded.profile.preset = preset;
+    loaded.preset_missing = true;
+  }
+}
+
 fn resolve_soundfont_path(
-  configured_path: &str
-) -> Option<(PathBuf, bool)> {
+  configured_path: &str,
+  search_paths: &[String]
+) -> Option<(PathBuf, Option<String>)> {
   let configured =
     Path::new(configured_path);
   if configured.exists() {
     return Some((
       configured.to_path_buf(),
-      false
+      None
     ));
   }
 
-  for candidate in
-    COMMON_SOUNDFONT_PATHS
-  {
+  let configured_file_name =
+    configured.file_name();
+  for search_path in search_paths {
     let candidate =
-      Path::new(candidate);
-    if candidate.exists() {
+      Path::new(search_path);
+    if candidate.is_file() {
       return Some((
         candidate.to_path_buf(),
-        true
+        Some(search_path.clone())
       ));
     }
+
+    if candidate.is_dir()
+      && let Some(file_name) =
+        configured_file_name
+    {
+      let nested =
+        candidate.join(file_name);
+      if nested.is_file() {
+        return Some((
+          nested,
+          Some(search_path.clone())
+        ));
+      }
+    }
   }
 
   None
@@ -562,7 +2171,266 @@ fn render_soundfont_note_samples(
   )
 }
 
-#[allow(dead_code)]
+fn live_synth_gain(
+  profile: &LoadedSoundFontProfile,
+  master_volume: f32
+) -> f32 {
+  (master_volume
+    * profile
+      .profile
+      .instrument_gain_multiplier)
+    .clamp(0.0, 2.5)
+}
+
+/// Tracks held notes as `(input,
+/// key)` pairs so a release stops the
+/// key its own input started.
+fn admit_live_note(
+  sounding: &mut VecDeque<(u8, u8)>,
+  input: u8,
+  key: u8,
+  max_voices: usize,
+  overflow: VoiceOverflowPolicy
+) -> (bool, Option<(u8, u8)>) {
+  sounding
+    .retain(|(held, _)| *held != input);
+  let mut stolen = None;
+  if sounding.len() >= max_voices {
+    match overflow {
+      | VoiceOverflowPolicy::DropNew => {
+        return (false, None);
+      }
+      | VoiceOverflowPolicy::StealOldest => {
+        stolen = sounding.pop_front();
+      }
+    }
+  }
+
+  sounding.push_back((input, key));
+  (true, stolen)
+}
+
+fn key_is_sounding(
+  sounding: &VecDeque<(u8, u8)>,
+  key: u8
+) -> bool {
+  sounding
+    .iter()
+    .any(|(_, held)| *held == key)
+}
+
+fn render_soundfont_click_samples(
+  profile: &LoadedSoundFontProfile,
+  midi_note: u8,
+  velocity: u8,
+  click_ms: u64,
+  sample_rate: u32,
+  master_volume: f32
+) -> Result<Vec<f32>> {
+  let actions = vec![ScheduledAction {
+    frame:  0,
+    action: MidiAction::NoteOn {
+      key:      i32::from(midi_note),
+      velocity: i32::from(
+        velocity.clamp(1, 127)
+      )
+    }
+  }];
+
+  let mut samples =
+    render_scheduled_actions(
+      profile,
+      sample_rate,
+      ms_to_frames(
+        click_ms,
+        sample_rate
+      ),
+      actions,
+      master_volume
+    )?;
+  fade_out_click(
+    &mut samples,
+    ms_to_frames(
+      METRONOME_CLICK_FADE_MS,
+      sample_rate
+    )
+  );
+  Ok(samples)
+}
+
+fn metronome_click_ms(
+  duration_ms: u64,
+  beat_ms: u64
+) -> u64 {
+  if beat_ms == 0 {
+    return duration_ms;
+  }
+
+  duration_ms
+    .min(beat_ms.saturating_sub(
+      METRONOME_CLICK_GAP_MS
+    ))
+    .max(METRONOME_CLICK_FADE_MS)
+}
+
+fn fade_out_click(
+  samples: &mut [f32],
+  fade_frames: usize
+) {
+  let frames = samples.len() / 2;
+  let fade_frames =
+    fade_frames.min(frames);
+  for offset in 0..fade_frames {
+    let frame =
+      frames - fade_frames + offset;
+    let gain = 1.0
+      - (offset + 1) as f32
+        / fade_frames as f32;
+    samples[frame * 2] *= gain;
+    samples[frame * 2 + 1] *= gain;
+  }
+}
+
+fn song_stems(
+  song: &SongFile
+) -> Vec<(String, SongFile)> {
+  let by_hand = song
+    .events
+    .iter()
+    .any(|event| event.hand.is_some());
+
+  let mut stems =
+    BTreeMap::<String, SongFile>::new();
+  for event in &song.events {
+    let label = if by_hand {
+      match event.hand {
+        | Some(Hand::Left) => "left",
+        | Some(Hand::Right) => "right",
+        | Some(Hand::Both) => "both",
+        | None => "unassigned"
+      }
+      .to_string()
+    } else {
+      event.channel.map_or_else(
+        || "unassigned".to_string(),
+        |channel| {
+          format!("ch{channel}")
+        }
+      )
+    };
+
+    stems
+      .entry(label)
+      .or_insert_with(|| {
+        SongFile {
+          meta: song.meta.clone(),
+          tempo_map: song
+            .tempo_map
+            .clone(),
+          ..SongFile::default()
+        }
+      })
+      .events
+      .push(event.clone());
+  }
+
+  stems.into_iter().collect()
+}
+
+fn mix_into(
+  buffer: &mut Vec<f32>,
+  offset: usize,
+  samples: &[f32]
+) {
+  let end = offset + samples.len();
+  if buffer.len() < end {
+    buffer.resize(end, 0.0);
+  }
+
+  for (target, sample) in buffer
+    [offset..end]
+    .iter_mut()
+    .zip(samples)
+  {
+    *target = (*target + sample)
+      .clamp(-1.0, 1.0);
+  }
+}
+
+fn write_wav(
+  path: &Path,
+  sample_rate: u32,
+  samples: &[f32]
+) -> Result<()> {
+  let channels = 2_u16;
+  let bytes_per_sample = 2_u16;
+  let data_len = u32::try_from(
+    samples.len()
+      * usize::from(bytes_per_sample)
+  )
+  .context("stem too long for WAV")?;
+  let block_align =
+    channels * bytes_per_sample;
+
+  let file = File::create(path)
+    .with_context(|| {
+      format!(
+        "failed creating {}",
+        path.display()
+      )
+    })?;
+  let mut writer = BufWriter::new(file);
+
+  writer.write_all(b"RIFF")?;
+  writer.write_all(
+    &(36 + data_len).to_le_bytes()
+  )?;
+  writer.write_all(b"WAVEfmt ")?;
+  writer
+    .write_all(&16_u32.to_le_bytes())?;
+  writer
+    .write_all(&1_u16.to_le_bytes())?;
+  writer.write_all(
+    &channels.to_le_bytes()
+  )?;
+  writer.write_all(
+    &sample_rate.to_le_bytes()
+  )?;
+  writer.write_all(
+    &(sample_rate
+      * u32::from(block_align))
+    .to_le_bytes()
+  )?;
+  writer.write_all(
+    &block_align.to_le_bytes()
+  )?;
+  writer.write_all(
+    &(bytes_per_sample * 8)
+      .to_le_bytes()
+  )?;
+  writer.write_all(b"data")?;
+  writer.write_all(
+    &data_len.to_le_bytes()
+  )?;
+
+  for sample in samples {
+    let value = (sample
+      .clamp(-1.0, 1.0)
+      * f32::from(i16::MAX))
+      as i16;
+    writer.write_all(
+      &value.to_le_bytes()
+    )?;
+  }
+
+  writer.flush().with_context(|| {
+    format!(
+      "failed writing {}",
+      path.display()
+    )
+  })
+}
+
 fn render_soundfont_song_samples(
   profile: &LoadedSoundFontProfile,
   song: &SongFile,
@@ -575,8 +2443,6 @@ fn render_soundfont_song_samples(
     return Ok(Vec::new());
   }
 
-  let beat_seconds =
-    60.0 / song.meta.tempo_bpm.max(1.0);
   let fallback_duration_frames =
     ms_to_frames(
       default_note_duration_ms.max(40),
@@ -595,9 +2461,10 @@ fn render_soundfont_song_samples(
       continue;
     }
 
-    let start_seconds =
-      (event.at_beats.max(0.0))
-        * beat_seconds;
+    let start_beats =
+      event.at_beats.max(0.0);
+    let start_seconds = song
+      .beats_to_seconds(start_beats);
     let start_frame = seconds_to_frames(
       start_seconds,
       sample_rate
@@ -605,10 +2472,12 @@ fn render_soundfont_song_samples(
 
     let event_duration_frames =
       if event.duration_beats > 0.0 {
-        let duration_seconds = (event
-          .duration_beats
-          * beat_seconds)
-          .max(0.04);
+        let duration_seconds =
+          (song.beats_to_seconds(
+            start_beats
+              + event.duration_beats
+          ) - start_seconds)
+            .max(0.04);
         seconds_to_frames(
           duration_seconds,
           sample_rate
@@ -674,6 +2543,61 @@ fn render_soundfont_song_samples(
   )
 }
 
+fn sequence_actions(
+  notes: &[(u8, u8, u64, u64)],
+  sample_rate: u32
+) -> (Vec<ScheduledAction>, usize) {
+  let mut actions = Vec::new();
+  let mut start_frame = 0usize;
+  let mut max_frame = 0usize;
+
+  for &(
+    midi_note,
+    velocity,
+    delay_ms,
+    duration_ms
+  ) in notes
+  {
+    if delay_ms > 0 {
+      start_frame = start_frame
+        .saturating_add(ms_to_frames(
+          delay_ms,
+          sample_rate
+        ));
+    }
+    if midi_note > 127
+      || is_note_off_velocity(velocity)
+    {
+      continue;
+    }
+
+    let note_off_frame = start_frame
+      .saturating_add(ms_to_frames(
+        duration_ms.max(40),
+        sample_rate
+      ));
+    actions.push(ScheduledAction {
+      frame:  start_frame,
+      action: MidiAction::NoteOn {
+        key:      i32::from(midi_note),
+        velocity: i32::from(
+          velocity.clamp(1, 127)
+        )
+      }
+    });
+    actions.push(ScheduledAction {
+      frame:  note_off_frame,
+      action: MidiAction::NoteOff {
+        key: i32::from(midi_note)
+      }
+    });
+    max_frame =
+      max_frame.max(note_off_frame);
+  }
+
+  (actions, max_frame)
+}
+
 fn render_scheduled_actions(
   profile: &LoadedSoundFontProfile,
   sample_rate: u32,
@@ -733,6 +2657,7 @@ fn render_scheduled_actions(
       apply_midi_action(
         &mut synth,
         channel,
+        &profile.profile,
         actions[action_index].action
       );
       action_index += 1;
@@ -827,13 +2752,66 @@ fn build_synthesizer(
   synth.process_midi_message(
     channel, 0xb0, 0x0b, 127
   );
+  apply_channel_tuning(
+    &mut synth,
+    channel,
+    profile.tuning_cents
+  );
 
   Ok(synth)
 }
 
+fn apply_channel_tuning(
+  synth: &mut Synthesizer,
+  channel: i32,
+  cents: f32
+) {
+  if cents.abs() < 0.01 {
+    return;
+  }
+
+  let coarse = (cents / 100.0).round();
+  let fine = cents - coarse * 100.0;
+  let fine_value =
+    (8192.0 + fine / 100.0 * 8192.0)
+      .round()
+      .clamp(0.0, 16383.0) as i32;
+
+  // RPN 1 = master fine tuning,
+  // RPN 2 = master coarse tuning.
+  for (rpn, msb, lsb) in [
+    (
+      1,
+      fine_value >> 7,
+      fine_value & 0x7f
+    ),
+    (2, 64 + coarse as i32, 0)
+  ] {
+    synth.process_midi_message(
+      channel, 0xb0, 0x65, 0
+    );
+    synth.process_midi_message(
+      channel, 0xb0, 0x64, rpn
+    );
+    synth.process_midi_message(
+      channel, 0xb0, 0x06, msb
+    );
+    synth.process_midi_message(
+      channel, 0xb0, 0x26, lsb
+    );
+  }
+  synth.process_midi_message(
+    channel, 0xb0, 0x65, 127
+  );
+  synth.process_midi_message(
+    channel, 0xb0, 0x64, 127
+  );
+}
+
 fn apply_midi_action(
   synth: &mut Synthesizer,
   channel: i32,
+  profile: &SoundFontProfile,
   action: MidiAction
 ) {
   match action {
@@ -841,8 +2819,12 @@ fn apply_midi_action(
       key,
       velocity
     } => {
-      synth
-        .note_on(channel, key, velocity)
+      synth.note_on(
+        channel,
+        key,
+        profile
+          .shape_velocity(velocity)
+      )
     }
     | MidiAction::NoteOff {
       key
@@ -850,6 +2832,23 @@ fn apply_midi_action(
   }
 }
 
+fn silent_warmup_samples(
+  warmup_ms: u64,
+  sample_rate: u32
+) -> Vec<f32> {
+  if warmup_ms == 0 {
+    return Vec::new();
+  }
+
+  vec![
+    0.0;
+    ms_to_frames(
+      warmup_ms,
+      sample_rate
+    ) * 2
+  ]
+}
+
 fn ms_to_frames(
   milliseconds: u64,
   sample_rate: u32
@@ -900,3 +2899,447 @@ struct ScheduledAction {
   frame:  usize,
   action: MidiAction
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::VelocityCurve;
+
+  #[test]
+  fn sequence_delays_accumulate_from_previous_note()
+   {
+    let (actions, max_frame) =
+      sequence_actions(
+        &[
+          (60, 100, 0, 500),
+          (64, 100, 0, 500),
+          (67, 100, 250, 1_000)
+        ],
+        1_000
+      );
+
+    let note_ons: Vec<(usize, i32)> =
+      actions
+        .iter()
+        .filter_map(|entry| {
+          match entry.action {
+            | MidiAction::NoteOn {
+              key,
+              ..
+            } => {
+              Some((entry.frame, key))
+            }
+            | MidiAction::NoteOff {
+              ..
+            } => None
+          }
+        })
+        .collect();
+    assert_eq!(note_ons, vec![
+      (0, 60),
+      (0, 64),
+      (250, 67)
+    ]);
+    assert_eq!(max_frame, 1_250);
+  }
+
+  #[test]
+  fn velocity_curve_reshapes_note_velocity()
+   {
+    let mut profile =
+      SoundFontProfile::default();
+    assert_eq!(
+      profile.shape_velocity(64),
+      64
+    );
+
+    profile.velocity_curve =
+      VelocityCurve::Exponential;
+    assert_eq!(
+      profile.shape_velocity(64),
+      32
+    );
+    assert_eq!(
+      profile.shape_velocity(127),
+      127
+    );
+
+    profile.velocity_curve =
+      VelocityCurve::Compressed;
+    assert_eq!(
+      profile.shape_velocity(32),
+      64
+    );
+
+    profile.velocity_curve =
+      VelocityCurve::Gamma;
+    profile.velocity_curve_gamma = 3.0;
+    assert_eq!(
+      profile.shape_velocity(1),
+      1
+    );
+  }
+
+  #[test]
+  fn splits_stems_by_hand_or_channel() {
+    use crate::songs::SongEvent;
+
+    let event = |hand, channel| {
+      SongEvent {
+        notes: vec![60],
+        hand,
+        channel,
+        ..SongEvent::default()
+      }
+    };
+    let mut song = SongFile {
+      events: vec![
+        event(
+          Some(Hand::Left),
+          Some(1)
+        ),
+        event(
+          Some(Hand::Right),
+          Some(1)
+        ),
+        event(
+          Some(Hand::Left),
+          Some(2)
+        ),
+      ],
+      tempo_map: vec![(4.0, 60.0)],
+      ..SongFile::default()
+    };
+
+    let stems = song_stems(&song);
+    let labels = stems
+      .iter()
+      .map(|(label, stem)| {
+        (
+          label.as_str(),
+          stem.events.len()
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(labels, vec![
+      ("left", 2),
+      ("right", 1)
+    ]);
+    assert!(stems.iter().all(
+      |(_, stem)| {
+        stem.tempo_map == song.tempo_map
+      }
+    ));
+
+    for event in &mut song.events {
+      event.hand = None;
+    }
+    let labels = song_stems(&song)
+      .into_iter()
+      .map(|(label, _)| label)
+      .collect::<Vec<_>>();
+    assert_eq!(labels, vec![
+      "ch1", "ch2"
+    ]);
+  }
+
+  #[test]
+  fn writes_stereo_pcm_wav_header() {
+    let path = std::env::temp_dir()
+      .join(format!(
+        "symfose-stem-{}.wav",
+        std::process::id()
+      ));
+    write_wav(&path, 48_000, &[
+      0.0, 0.5, -0.5, 1.0
+    ])
+    .expect("write wav");
+    let bytes = fs::read(&path)
+      .expect("read wav");
+    let _ = fs::remove_file(&path);
+
+    assert_eq!(bytes.len(), 44 + 8);
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(
+      &bytes[8..16],
+      b"WAVEfmt "
+    );
+    assert_eq!(
+      u16::from_le_bytes([
+        bytes[22], bytes[23]
+      ]),
+      2
+    );
+    assert_eq!(
+      u32::from_le_bytes([
+        bytes[40], bytes[41],
+        bytes[42], bytes[43]
+      ]),
+      8
+    );
+  }
+
+  #[test]
+  fn lifting_sustain_stops_only_sustained_voices()
+   {
+    let config = AudioConfig {
+      backend: AudioBackend::Null,
+      lazy_load_profiles: true,
+      ..AudioConfig::default()
+    };
+    let mut engine =
+      AudioEngine::new(&config)
+        .expect("null audio engine");
+    let voice = |sustained| {
+      UserVoice {
+        stop: Arc::new(
+          AtomicBool::new(false)
+        ),
+        remaining: Arc::new(
+          AtomicUsize::new(1)
+        ),
+        sustained
+      }
+    };
+
+    engine.set_sustain(true);
+    engine.user_voices.extend([
+      voice(true),
+      voice(false),
+      voice(true)
+    ]);
+    engine.set_sustain(false);
+
+    let stopped = engine
+      .user_voices
+      .iter()
+      .map(|voice| {
+        voice
+          .stop
+          .load(Ordering::Relaxed)
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(stopped, [
+      true, false, true
+    ]);
+  }
+
+  #[test]
+  fn new_live_synth_inherits_a_held_pedal()
+   {
+    let config = AudioConfig {
+      backend: AudioBackend::Null,
+      lazy_load_profiles: true,
+      ..AudioConfig::default()
+    };
+    let mut engine =
+      AudioEngine::new(&config)
+        .expect("null audio engine");
+
+    engine.set_sustain(true);
+    let source = engine
+      .start_live_synth(44_100)
+      .expect("live synth");
+
+    assert!(matches!(
+      source.commands.try_recv(),
+      Ok(LiveCommand::Sustain(true))
+    ));
+    assert!(
+      source
+        .commands
+        .try_recv()
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn null_backend_runs_without_an_output_device()
+   {
+    let config = AudioConfig {
+      backend: AudioBackend::Null,
+      lazy_load_profiles: true,
+      ..AudioConfig::default()
+    };
+    let mut engine =
+      AudioEngine::new(&config)
+        .expect("null audio engine");
+
+    engine.set_master_volume(0.25);
+    engine.play_note(60);
+    engine.start_note(62, 100);
+    engine.set_sustain(true);
+    engine.stop_note(62);
+    engine.set_sustain(false);
+    engine.stop_all_notes();
+    engine.play_metronome_tick(
+      true,
+      MetronomeNoteOff::Cut,
+      500
+    );
+
+    assert!(engine.stream.is_none());
+    assert!(
+      (engine.master_volume() - 0.25)
+        .abs()
+        < 1e-6
+    );
+    assert_eq!(
+      engine.active_profile_name(),
+      config.instrument
+    );
+    assert!(
+      engine
+        .recover_lost_stream()
+        .is_none()
+    );
+  }
+
+  #[test]
+  fn warmup_buffer_is_silent_stereo() {
+    assert!(
+      silent_warmup_samples(0, 48_000)
+        .is_empty()
+    );
+
+    let samples = silent_warmup_samples(
+      100, 48_000
+    );
+    assert_eq!(
+      samples.len(),
+      4_800 * 2
+    );
+    assert!(
+      samples
+        .iter()
+        .all(|sample| *sample == 0.0)
+    );
+  }
+
+  #[test]
+  fn metronome_clicks_end_before_the_next_beat()
+   {
+    let sixteenth_at_240_bpm = 62;
+    let click_ms = metronome_click_ms(
+      115,
+      sixteenth_at_240_bpm
+    );
+    assert!(
+      click_ms < sixteenth_at_240_bpm
+    );
+    assert_eq!(
+      metronome_click_ms(90, 500),
+      90
+    );
+
+    let mut samples = vec![1.0; 16];
+    fade_out_click(&mut samples, 4);
+    assert_eq!(samples[0], 1.0);
+    assert_eq!(samples[14], 0.0);
+    assert_eq!(samples[15], 0.0);
+    assert!(samples[10] > samples[12]);
+  }
+
+  #[test]
+  fn live_notes_respect_the_held_note_limit()
+   {
+    let mut sounding = VecDeque::new();
+    for key in [60, 62] {
+      assert_eq!(
+        admit_live_note(
+          &mut sounding,
+          key,
+          key,
+          2,
+          VoiceOverflowPolicy::DropNew
+        ),
+        (true, None)
+      );
+    }
+
+    assert_eq!(
+      admit_live_note(
+        &mut sounding,
+        64,
+        64,
+        2,
+        VoiceOverflowPolicy::DropNew
+      ),
+      (false, None)
+    );
+    assert_eq!(
+      admit_live_note(
+        &mut sounding,
+        60,
+        60,
+        2,
+        VoiceOverflowPolicy::DropNew
+      ),
+      (true, None)
+    );
+    assert_eq!(
+      admit_live_note(
+        &mut sounding,
+        64,
+        64,
+        2,
+        VoiceOverflowPolicy::StealOldest
+      ),
+      (true, Some((62, 62)))
+    );
+    assert_eq!(
+      sounding,
+      VecDeque::from([
+        (60, 60),
+        (64, 64)
+      ])
+    );
+  }
+
+  #[test]
+  fn clamped_inputs_sharing_a_key_are_tracked_apart()
+   {
+    let mut sounding = VecDeque::new();
+    for input in [110, 115] {
+      assert_eq!(
+        admit_live_note(
+          &mut sounding,
+          input,
+          108,
+          4,
+          VoiceOverflowPolicy::DropNew
+        ),
+        (true, None)
+      );
+    }
+
+    sounding.retain(|(input, _)| {
+      *input != 110
+    });
+    assert!(key_is_sounding(
+      &sounding, 108
+    ));
+    sounding.retain(|(input, _)| {
+      *input != 115
+    });
+    assert!(!key_is_sounding(
+      &sounding, 108
+    ));
+  }
+
+  #[test]
+  fn recording_mixes_overlapping_notes_at_their_offsets()
+   {
+    let mut buffer = Vec::new();
+    mix_into(&mut buffer, 2, &[
+      0.5, 0.5, 0.5, 0.5
+    ]);
+    mix_into(&mut buffer, 4, &[
+      0.75, 0.25
+    ]);
+
+    assert_eq!(buffer, vec![
+      0.0, 0.0, 0.5, 0.5, 1.0, 0.75
+    ]);
+  }
+}
@@ -5,6 +5,7 @@ use std::fmt::{
   Result as FmtResult
 };
 use std::fs;
+use std::net::SocketAddr;
 use std::path::Path;
 
 use anyhow::{
@@ -31,9 +32,13 @@ pub struct AppConfig {
   pub input:            InputConfig,
   pub keyboard:         KeyboardConfig,
   pub gameplay:         GameplayConfig,
+  pub metronome:        MetronomeConfig,
+  pub ui:               UiConfig,
   pub control_bindings: ControlBindings,
   pub keybindings: BTreeMap<String, u8>,
-  pub song_library: SongLibraryConfig
+  pub song_library: SongLibraryConfig,
+  pub sync:             SyncConfig,
+  pub midi_sync:        MidiSyncConfig
 }
 
 impl Default for AppConfig {
@@ -51,6 +56,10 @@ impl Default for AppConfig {
         KeyboardConfig::default(),
       gameplay:
         GameplayConfig::default(),
+      metronome:
+        MetronomeConfig::default(),
+      ui:
+        UiConfig::default(),
       control_bindings:
         ControlBindings::default(),
       keybindings:
@@ -58,23 +67,188 @@ impl Default for AppConfig {
           KeyboardLayout::default()
         ),
       song_library:
-        SongLibraryConfig::default()
+        SongLibraryConfig::default(),
+      sync:
+        SyncConfig::default(),
+      midi_sync:
+        MidiSyncConfig::default()
     }
   }
 }
 
+#[derive(
+  Debug, Clone, Serialize, Deserialize,
+)]
+#[serde(default)]
+pub struct MetronomeConfig {
+  pub enabled:        bool,
+  pub accent_pattern: Vec<u8>,
+  pub note_off:       MetronomeNoteOff
+}
+
+impl Default for MetronomeConfig {
+  fn default() -> Self {
+    Self {
+      enabled:        true,
+      accent_pattern: Vec::new(),
+      note_off:
+        MetronomeNoteOff::default()
+    }
+  }
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MetronomeNoteOff {
+  #[default]
+  Cut,
+  Release
+}
+
+impl MetronomeConfig {
+  pub fn pattern_fits_bar(
+    &self,
+    beats_per_bar: u8
+  ) -> bool {
+    !self.accent_pattern.is_empty()
+      && usize::from(beats_per_bar)
+        .is_multiple_of(
+          self.accent_pattern.len()
+        )
+  }
+
+  pub fn is_accent(
+    &self,
+    beat_index: u64,
+    beats_per_bar: u8
+  ) -> bool {
+    if self
+      .pattern_fits_bar(beats_per_bar)
+    {
+      let position = (beat_index
+        % self.accent_pattern.len()
+          as u64)
+        as usize;
+      return self.accent_pattern
+        [position]
+        != 0;
+    }
+
+    beat_index.is_multiple_of(
+      u64::from(beats_per_bar.max(1))
+    )
+  }
+}
+
+#[derive(
+  Debug, Clone, Serialize, Deserialize,
+)]
+#[serde(default)]
+pub struct UiConfig {
+  pub white_key_label_color: String,
+  pub black_key_label_color: String,
+  pub song_page_size:        usize,
+  pub lead_in_highlight:     bool,
+  pub lead_in_seconds:       f32,
+  pub flash_fade_ms:         u64,
+  pub octave_convention:
+    OctaveConvention
+}
+
+impl Default for UiConfig {
+  fn default() -> Self {
+    Self {
+      white_key_label_color: "#191919"
+        .to_string(),
+      black_key_label_color: "#f2f2f2"
+        .to_string(),
+      song_page_size:        50,
+      lead_in_highlight:     false,
+      lead_in_seconds:       0.8,
+      flash_fade_ms:         0,
+      octave_convention:
+        OctaveConvention::default()
+    }
+  }
+}
+
+impl UiConfig {
+  pub fn white_key_label_rgb(
+    &self
+  ) -> [u8; 3] {
+    parse_hex_color(
+      &self.white_key_label_color
+    )
+    .unwrap_or([25, 25, 25])
+  }
+
+  pub fn black_key_label_rgb(
+    &self
+  ) -> [u8; 3] {
+    parse_hex_color(
+      &self.black_key_label_color
+    )
+    .unwrap_or([242, 242, 242])
+  }
+}
+
+fn parse_hex_color(
+  value: &str
+) -> Option<[u8; 3]> {
+  let hex =
+    value.trim().strip_prefix('#')?;
+  if hex.len() != 6
+    || !hex
+      .chars()
+      .all(|ch| ch.is_ascii_hexdigit())
+  {
+    return None;
+  }
+
+  let channel = |range| {
+    u8::from_str_radix(&hex[range], 16)
+      .ok()
+  };
+  Some([
+    channel(0..2)?,
+    channel(2..4)?,
+    channel(4..6)?
+  ])
+}
+
 #[derive(
   Debug, Clone, Serialize, Deserialize,
 )]
 #[serde(default)]
 pub struct AppSection {
-  pub print_unmapped_keys: bool
+  pub print_unmapped_keys:        bool,
+  pub unmapped_key_log_window_ms: u64,
+  pub mute_modifier_only_keys:    bool,
+  pub auto_save_interval_seconds: u64,
+  pub require_confirm_quit:       bool,
+  pub confirm_quit_window_ms:     u64,
+  pub tick_rate_ms:               u64
 }
 
 impl Default for AppSection {
   fn default() -> Self {
     Self {
-      print_unmapped_keys: false
+      print_unmapped_keys:        false,
+      unmapped_key_log_window_ms: 2_000,
+      mute_modifier_only_keys:    true,
+      auto_save_interval_seconds: 0,
+      require_confirm_quit:       false,
+      confirm_quit_window_ms:     2_000,
+      tick_rate_ms:               16
     }
   }
 }
@@ -104,14 +278,22 @@ impl Default for LoggingConfig {
 #[serde(default)]
 pub struct InputConfig {
   pub allow_key_repeat:           bool,
-  pub ignore_shift_for_char_keys: bool
+  pub ignore_shift_for_char_keys: bool,
+  pub ignore_shift_for_note_keys: bool,
+  pub note_velocity:              u8,
+  pub velocity_map:
+    BTreeMap<String, u8>
 }
 
 impl Default for InputConfig {
   fn default() -> Self {
     Self {
       allow_key_repeat:           false,
-      ignore_shift_for_char_keys: true
+      ignore_shift_for_char_keys: true,
+      ignore_shift_for_note_keys: true,
+      note_velocity:              112,
+      velocity_map:
+        BTreeMap::new()
     }
   }
 }
@@ -122,18 +304,82 @@ impl Default for InputConfig {
 #[serde(default)]
 pub struct KeyboardConfig {
   pub layout: KeyboardLayout,
-  pub use_layout_default_bindings: bool
+  pub use_layout_default_bindings: bool,
+  pub primary_label_order:
+    PrimaryLabelOrder,
+  pub keybindings_on_conflict:
+    KeybindingConflictPolicy,
+  pub keymap_export_path: String
 }
 
 impl Default for KeyboardConfig {
   fn default() -> Self {
     Self {
       layout: KeyboardLayout::default(),
-      use_layout_default_bindings: true
+      use_layout_default_bindings: true,
+      primary_label_order:
+        PrimaryLabelOrder::default(),
+      keybindings_on_conflict:
+        KeybindingConflictPolicy::default(),
+      keymap_export_path:
+        "symfose-keymap.md".to_string()
     }
   }
 }
 
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum OctaveConvention {
+  #[default]
+  Scientific,
+  Yamaha,
+  Helmholtz
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PrimaryLabelOrder {
+  #[default]
+  Alphabetical,
+  LayoutPriority
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum KeybindingConflictPolicy {
+  #[default]
+  Error,
+  WarnLastWins,
+  WarnFirstWins
+}
+
 #[derive(
   Debug,
   Clone,
@@ -177,13 +423,47 @@ impl Display for KeyboardLayout {
 pub struct GameplayConfig {
   pub transpose_song_to_fit_bindings:
     bool,
+  pub transpose_strategy:
+    TransposeStrategy,
+  pub out_of_range_transposed_notes:
+    NoteRangePolicy,
+  pub practice_hand: PracticeHand,
+  pub shared_hand_notes:
+    SharedHandNotes,
   pub warn_on_missing_song_notes: bool,
+  pub check_instrument_range: bool,
   pub optimize_bindings_for_song: bool,
   pub auto_jump_pressed_key_into_view:
     bool,
   pub auto_scroll_song_lane_follow_playback:
     bool,
   pub piano_visible_white_keys: u16,
+  pub snap_keyboard_to_octaves: bool,
+  pub song_keys_only: bool,
+  pub auto_advance: bool,
+  pub auto_advance_delay_ms: u64,
+  pub trim_leading_silence: bool,
+  pub cut_overlapping_same_pitch: bool,
+  pub show_original_note_names: bool,
+  pub distinguish_autoplay_flashes:
+    bool,
+  pub session_minutes: u32,
+  pub strict_perfect: bool,
+  pub ignore_octave_errors: bool,
+  pub duration_weighted_scoring: bool,
+  pub timer_ghost_volume: f32,
+  pub start_paused: bool,
+  pub tutorial_wrong_note_grace_ms: u64,
+  pub tutorial_only_advance_on_correct_note:
+    bool,
+  pub tutorial_play_bad_notes_out_loud:
+    bool,
+  pub left_hand_volume: f32,
+  pub right_hand_volume: f32,
+  pub playback_speed: f32,
+  pub global_transpose_semitones: i8,
+  pub show_beat_grid: bool,
+  pub timeline_drag_scrub: bool,
   pub song_lane_units_per_line: u16,
   pub song_lane_unit_width_px: f32,
   pub song_lane_tile_height_px: f32
@@ -194,8 +474,18 @@ impl Default for GameplayConfig {
     Self {
       transpose_song_to_fit_bindings:
         true,
+      transpose_strategy:
+        TransposeStrategy::default(),
+      out_of_range_transposed_notes:
+        NoteRangePolicy::default(),
+      practice_hand:
+        PracticeHand::default(),
+      shared_hand_notes:
+        SharedHandNotes::default(),
       warn_on_missing_song_notes:
         true,
+      check_instrument_range:
+        true,
       optimize_bindings_for_song:
         false,
       auto_jump_pressed_key_into_view:
@@ -204,6 +494,32 @@ impl Default for GameplayConfig {
         true,
       piano_visible_white_keys:
         18,
+      snap_keyboard_to_octaves:
+        false,
+      song_keys_only: false,
+      auto_advance: false,
+      auto_advance_delay_ms: 2_000,
+      trim_leading_silence: false,
+      cut_overlapping_same_pitch: true,
+      show_original_note_names: true,
+      distinguish_autoplay_flashes: true,
+      session_minutes: 0,
+      strict_perfect: false,
+      ignore_octave_errors: false,
+      duration_weighted_scoring: false,
+      timer_ghost_volume: 0.0,
+      start_paused: false,
+      tutorial_wrong_note_grace_ms: 0,
+      tutorial_only_advance_on_correct_note:
+        true,
+      tutorial_play_bad_notes_out_loud:
+        true,
+      left_hand_volume: 1.0,
+      right_hand_volume: 1.0,
+      playback_speed: 1.0,
+      global_transpose_semitones: 0,
+      show_beat_grid: true,
+      timeline_drag_scrub: true,
       song_lane_units_per_line:
         36,
       song_lane_unit_width_px:
@@ -219,30 +535,102 @@ impl Default for GameplayConfig {
 )]
 #[serde(default)]
 pub struct AudioConfig {
-  pub instrument:          String,
-  pub master_volume:       f32,
-  pub note_duration_ms:    u64,
-  pub release_duration_ms: u64,
-  pub sample_rate_hz:      u32,
+  pub backend: AudioBackend,
+  pub instrument: String,
+  pub master_volume:              f32,
+  pub note_duration_ms:           u64,
+  pub release_duration_ms:        u64,
+  pub warmup_ms:                  u64,
+  pub visual_offset_ms:           i32,
+  pub input_offset_ms:            i32,
+  pub reference_pitch_hz:         f32,
+  pub sample_rate_hz:             u32,
+  pub max_user_voices:            usize,
+  pub user_voice_overflow:
+    VoiceOverflowPolicy,
+  pub fallback_to_default_preset: bool,
+  pub lazy_load_profiles:         bool,
+  pub channel_profile_map:
+    BTreeMap<String, String>,
+  pub soundfont_search_paths:
+    Vec<String>,
   pub instrument_profiles:
-    BTreeMap<String, InstrumentProfile>
+    BTreeMap<String, InstrumentProfile>,
+  pub stems_export_directory: String,
+  pub recordings_directory: String,
+  pub live_synth:                 bool
 }
 
 impl Default for AudioConfig {
   fn default() -> Self {
     Self {
-      instrument:          "piano"
-        .to_string(),
-      master_volume:       0.68,
-      note_duration_ms:    680,
-      release_duration_ms: 720,
-      sample_rate_hz:      48_000,
+      backend:
+        AudioBackend::default(),
+      instrument:
+        "piano".to_string(),
+      master_volume:              0.68,
+      note_duration_ms:           680,
+      release_duration_ms:        720,
+      warmup_ms:                  120,
+      visual_offset_ms:           0,
+      input_offset_ms:            0,
+      reference_pitch_hz:         440.0,
+      sample_rate_hz:
+        48_000,
+      max_user_voices:            24,
+      user_voice_overflow:
+        VoiceOverflowPolicy::default(),
+      fallback_to_default_preset: true,
+      lazy_load_profiles:         false,
+      channel_profile_map:
+        BTreeMap::new(),
+      soundfont_search_paths:
+        default_soundfont_search_paths(),
       instrument_profiles:
-        default_instrument_profiles()
+        default_instrument_profiles(),
+      stems_export_directory:
+        "exports/stems".to_string(),
+      recordings_directory:
+        "recordings".to_string(),
+      live_synth:                 true
     }
   }
 }
 
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioBackend {
+  #[default]
+  Device,
+  Null
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum VoiceOverflowPolicy {
+  #[default]
+  StealOldest,
+  DropNew
+}
+
 #[derive(
   Debug, Clone, Serialize, Deserialize,
 )]
@@ -273,7 +661,13 @@ pub struct SoundFontProfile {
   pub channel: u8,
   pub maximum_polyphony: usize,
   pub enable_reverb_and_chorus: bool,
-  pub instrument_gain_multiplier: f32
+  pub instrument_gain_multiplier: f32,
+  pub min_note: Option<u8>,
+  pub max_note: Option<u8>,
+  pub out_of_range_notes:
+    NoteRangePolicy,
+  pub velocity_curve: VelocityCurve,
+  pub velocity_curve_gamma: f32
 }
 
 impl Default for SoundFontProfile {
@@ -287,7 +681,206 @@ impl Default for SoundFontProfile {
       channel: 0,
       maximum_polyphony: 128,
       enable_reverb_and_chorus: true,
-      instrument_gain_multiplier: 1.0
+      instrument_gain_multiplier: 1.0,
+      min_note: None,
+      max_note: None,
+      out_of_range_notes:
+        NoteRangePolicy::default(),
+      velocity_curve:
+        VelocityCurve::default(),
+      velocity_curve_gamma: 1.0
+    }
+  }
+}
+
+impl SoundFontProfile {
+  pub fn shape_velocity(
+    &self,
+    velocity: i32
+  ) -> i32 {
+    let gamma = match self
+      .velocity_curve
+    {
+      | VelocityCurve::Linear => {
+        return velocity;
+      }
+      | VelocityCurve::Exponential => {
+        2.0
+      }
+      | VelocityCurve::Compressed => {
+        0.5
+      }
+      | VelocityCurve::Gamma => {
+        self.velocity_curve_gamma
+      }
+    };
+
+    let normalized =
+      velocity.clamp(1, 127) as f32
+        / 127.0;
+    ((normalized.powf(gamma) * 127.0)
+      .round() as i32)
+      .clamp(1, 127)
+  }
+
+  pub fn fit_note_to_range(
+    &self,
+    midi_note: u8
+  ) -> Option<u8> {
+    self.out_of_range_notes.fit(
+      i16::from(midi_note),
+      self.min_note.unwrap_or(0),
+      self.max_note.unwrap_or(127)
+    )
+  }
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PracticeHand {
+  #[default]
+  Both,
+  Left,
+  Right
+}
+
+impl PracticeHand {
+  pub const ALL: [PracticeHand; 3] = [
+    PracticeHand::Both,
+    PracticeHand::Left,
+    PracticeHand::Right
+  ];
+}
+
+impl Display for PracticeHand {
+  fn fmt(
+    &self,
+    f: &mut Formatter<'_>
+  ) -> FmtResult {
+    let label = match self {
+      | Self::Both => "Both hands",
+      | Self::Left => "Left hand",
+      | Self::Right => "Right hand"
+    };
+
+    write!(f, "{label}")
+  }
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum SharedHandNotes {
+  #[default]
+  IncludeInBoth,
+  MelodyHandOnly,
+  Skip
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TransposeStrategy {
+  #[default]
+  MaxCoverage,
+  CenterRange
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteRangePolicy {
+  #[default]
+  Drop,
+  Clamp,
+  OctaveFold
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum VelocityCurve {
+  #[default]
+  Linear,
+  Exponential,
+  Compressed,
+  Gamma
+}
+
+impl NoteRangePolicy {
+  /// Maps a note into
+  /// `min_note..=max_note`, or `None`
+  /// when the policy drops it.
+  pub fn fit(
+    self,
+    note: i16,
+    min_note: u8,
+    max_note: u8
+  ) -> Option<u8> {
+    let min = i16::from(min_note);
+    let max = i16::from(max_note);
+    if (min..=max).contains(&note) {
+      return Some(note as u8);
+    }
+
+    match self {
+      | Self::Drop => None,
+      | Self::Clamp => {
+        Some(note.clamp(min, max) as u8)
+      }
+      | Self::OctaveFold => {
+        let mut folded = note;
+        while folded < min {
+          folded += 12;
+        }
+        while folded > max {
+          folded -= 12;
+        }
+
+        (folded >= min)
+          .then_some(folded as u8)
+      }
     }
   }
 }
@@ -365,27 +958,44 @@ impl AppConfig {
 )]
 #[serde(default)]
 pub struct ControlBindings {
-  pub quit:           Vec<String>,
-  pub list_songs:     Vec<String>,
+  pub quit: Vec<String>,
+  pub list_songs: Vec<String>,
   pub print_bindings: Vec<String>,
-  pub play_song:      Vec<String>
+  pub play_song: Vec<String>,
+  pub go_to_song: Vec<String>,
+  pub global_transpose_up: Vec<String>,
+  pub global_transpose_down:
+    Vec<String>,
+  pub sustain: Vec<String>
 }
 
 impl Default for ControlBindings {
   fn default() -> Self {
     Self {
-      quit:           vec![
+      quit:                  vec![
         "esc".to_string(),
         "ctrl+c".to_string(),
       ],
-      list_songs:     vec![
+      list_songs:            vec![
         "f1".to_string(),
       ],
-      print_bindings: vec![
+      print_bindings:        vec![
         "f2".to_string(),
       ],
-      play_song:      vec![
+      play_song:             vec![
         "f5".to_string(),
+      ],
+      go_to_song:            vec![
+        "f3".to_string(),
+      ],
+      global_transpose_up:   vec![
+        "f8".to_string(),
+      ],
+      global_transpose_down: vec![
+        "f7".to_string(),
+      ],
+      sustain:               vec![
+        "space".to_string(),
       ]
     }
   }
@@ -396,26 +1006,178 @@ impl Default for ControlBindings {
 )]
 #[serde(default)]
 pub struct SongLibraryConfig {
-  pub directory:       String,
-  pub midi_directory:  String,
-  pub schema_path:     String,
-  pub cache_directory: String
+  pub directory:             String,
+  pub midi_directory:        String,
+  pub schema_path:           String,
+  pub cache_directory:       String,
+  pub cache_enabled:         bool,
+  pub midi_default_tags: Vec<String>,
+  pub midi_default_artist:   String,
+  pub duplicate_sources:
+    DuplicateSourcePreference,
+  pub cache_key_salt:        String,
+  pub index_file:            String,
+  pub midi_export_directory: String,
+  pub enforce_schema:        bool,
+  pub midi_import: MidiImportConfig
 }
 
 impl Default for SongLibraryConfig {
   fn default() -> Self {
     Self {
-      directory:       "res/songs"
-        .to_string(),
-      midi_directory:  "res/assets/\
-                        midi"
+      directory:           "res/songs"
         .to_string(),
+      midi_directory:
+        "res/assets/midi".to_string(),
       schema_path:
         "res/songs/schema/song.schema.\
          json"
           .to_string(),
-      cache_directory: ".cache/songs"
-        .to_string()
+      cache_directory:
+        ".cache/songs".to_string(),
+      cache_enabled:       true,
+      midi_default_tags:   vec![
+        "midi".to_string(),
+        "imported".to_string(),
+      ],
+      midi_default_artist:
+        "MIDI Import".to_string(),
+      duplicate_sources:
+        DuplicateSourcePreference::default(
+        ),
+      cache_key_salt: String::new(),
+      index_file:
+        ".cache/songs/library.index.toml"
+          .to_string(),
+      midi_export_directory:
+        "exports/midi".to_string(),
+      enforce_schema: false,
+      midi_import:
+        MidiImportConfig::default()
+    }
+  }
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateSourcePreference {
+  #[default]
+  KeepBoth,
+  PreferToml,
+  PreferMidi
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ChordGrouping {
+  #[default]
+  ExactDuration,
+  OnsetLongest,
+  OnsetShortest
+}
+
+#[derive(
+  Debug, Clone, Serialize, Deserialize,
+)]
+#[serde(default)]
+pub struct MidiImportConfig {
+  pub merge_tied_notes: bool,
+  pub tie_gap_beats: f32,
+  pub convert_smpte_timing: bool,
+  pub smpte_fallback_ticks_per_beat:
+    u32,
+  pub chord_grouping: ChordGrouping
+}
+
+impl Default for MidiImportConfig {
+  fn default() -> Self {
+    Self {
+      merge_tied_notes:
+        false,
+      tie_gap_beats:
+        0.02,
+      convert_smpte_timing:
+        true,
+      smpte_fallback_ticks_per_beat:
+        480,
+      chord_grouping:
+        ChordGrouping::default()
+    }
+  }
+}
+
+impl MidiImportConfig {
+  pub fn signature(&self) -> String {
+    format!(
+      "merge_tied_notes={};\
+       tie_gap_beats={};\
+       convert_smpte_timing={};\
+       smpte_fallback_ticks_per_beat={};\
+       chord_grouping={:?}",
+      self.merge_tied_notes,
+      self.tie_gap_beats,
+      self.convert_smpte_timing,
+      self.smpte_fallback_ticks_per_beat,
+      self.chord_grouping
+    )
+  }
+}
+
+#[derive(
+  Debug, Clone, Serialize, Deserialize,
+)]
+#[serde(default)]
+pub struct SyncConfig {
+  pub enabled:        bool,
+  pub target_address: String,
+  pub listen_address: String
+}
+
+impl Default for SyncConfig {
+  fn default() -> Self {
+    Self {
+      enabled:        false,
+      target_address: "127.0.0.1:9000"
+        .to_string(),
+      listen_address: String::new()
+    }
+  }
+}
+
+#[derive(
+  Debug, Clone, Serialize, Deserialize,
+)]
+#[serde(default)]
+pub struct MidiSyncConfig {
+  pub enabled:          bool,
+  pub port_name:        String,
+  pub follow_transport: bool
+}
+
+impl Default for MidiSyncConfig {
+  fn default() -> Self {
+    Self {
+      enabled:          false,
+      port_name:        String::new(),
+      follow_transport: true
     }
   }
 }
@@ -485,6 +1247,26 @@ pub fn write_default(
 fn validate_config(
   config: &AppConfig
 ) -> Result<()> {
+  if !(250..=10_000).contains(
+    &config.app.confirm_quit_window_ms
+  ) {
+    bail!(
+      "app.confirm_quit_window_ms \
+       must be in range 250..=10000"
+    );
+  }
+
+  if config
+    .app
+    .auto_save_interval_seconds
+    > 3_600
+  {
+    bail!(
+      "app.auto_save_interval_seconds \
+       must be in range 0..=3600"
+    );
+  }
+
   if !(0.0..=2.5).contains(
     &config.audio.master_volume
   ) {
@@ -511,6 +1293,41 @@ fn validate_config(
     );
   }
 
+  if config.audio.warmup_ms > 2_000 {
+    bail!(
+      "audio.warmup_ms must be <= 2000"
+    );
+  }
+
+  for (field, value) in [
+    (
+      "visual_offset_ms",
+      config.audio.visual_offset_ms
+    ),
+    (
+      "input_offset_ms",
+      config.audio.input_offset_ms
+    )
+  ] {
+    if !(-1_000..=1_000)
+      .contains(&value)
+    {
+      bail!(
+        "audio.{field} must be in \
+         range -1000..=1000"
+      );
+    }
+  }
+
+  if !(400.0..=480.0).contains(
+    &config.audio.reference_pitch_hz
+  ) {
+    bail!(
+      "audio.reference_pitch_hz must \
+       be in range 400.0..=480.0"
+    );
+  }
+
   if config.audio.sample_rate_hz
     < 16_000
     || config.audio.sample_rate_hz
@@ -522,6 +1339,86 @@ fn validate_config(
     );
   }
 
+  if config
+    .metronome
+    .accent_pattern
+    .iter()
+    .any(|accent| *accent > 1)
+  {
+    bail!(
+      "metronome.accent_pattern \
+       entries must be 0 or 1"
+    );
+  }
+
+  if !config
+    .metronome
+    .accent_pattern
+    .is_empty()
+    && !config
+      .metronome
+      .accent_pattern
+      .contains(&1)
+  {
+    bail!(
+      "metronome.accent_pattern must \
+       accent at least one beat"
+    );
+  }
+
+  for (field, value) in [
+    (
+      "white_key_label_color",
+      &config.ui.white_key_label_color
+    ),
+    (
+      "black_key_label_color",
+      &config.ui.black_key_label_color
+    )
+  ] {
+    if parse_hex_color(value).is_none()
+    {
+      bail!(
+        "ui.{field} must be a #rrggbb \
+         hex color"
+      );
+    }
+  }
+
+  if !(5..=500)
+    .contains(&config.ui.song_page_size)
+  {
+    bail!(
+      "ui.song_page_size must be in \
+       range 5..=500"
+    );
+  }
+
+  if config.ui.flash_fade_ms > 2_000 {
+    bail!(
+      "ui.flash_fade_ms must be in \
+       range 0..=2000"
+    );
+  }
+
+  if !(0.1..=4.0).contains(
+    &config.ui.lead_in_seconds
+  ) {
+    bail!(
+      "ui.lead_in_seconds must be in \
+       range 0.1..=4.0"
+    );
+  }
+
+  if !(1..=256).contains(
+    &config.audio.max_user_voices
+  ) {
+    bail!(
+      "audio.max_user_voices must be \
+       in range 1..=256"
+    );
+  }
+
   if config
     .audio
     .instrument
@@ -560,6 +1457,35 @@ fn validate_config(
     );
   }
 
+  for (channel, profile_name) in
+    &config.audio.channel_profile_map
+  {
+    let valid_channel = channel
+      .parse::<u8>()
+      .is_ok_and(|channel| {
+        (1..=16).contains(&channel)
+      });
+    if !valid_channel {
+      bail!(
+        "audio.channel_profile_map \
+         key '{channel}' must be a \
+         MIDI channel in range 1..=16"
+      );
+    }
+    if !config
+      .audio
+      .instrument_profiles
+      .contains_key(profile_name)
+    {
+      bail!(
+        "audio.channel_profile_map.\
+         {channel}='{profile_name}' \
+         does not match any \
+         audio.instrument_profiles key"
+      );
+    }
+  }
+
   for (profile_name, profile) in
     &config.audio.instrument_profiles
   {
@@ -589,6 +1515,30 @@ fn validate_config(
     );
   }
 
+  if !(1..=127).contains(
+    &config.input.note_velocity
+  ) {
+    bail!(
+      "input.note_velocity must be in \
+       range 1..=127"
+    );
+  }
+
+  if let Some(entry) = config
+    .input
+    .velocity_map
+    .iter()
+    .find(|(_, velocity)| {
+      !(1..=127).contains(*velocity)
+    })
+  {
+    bail!(
+      "input.velocity_map.{} must be \
+       in range 1..=127",
+      entry.0
+    );
+  }
+
   if config
     .song_library
     .directory
@@ -637,6 +1587,106 @@ fn validate_config(
     );
   }
 
+  if !(0.0..=1.0).contains(
+    &config.gameplay.timer_ghost_volume
+  ) {
+    bail!(
+      "gameplay.timer_ghost_volume \
+       must be in range 0.0..=1.0"
+    );
+  }
+
+  if !(0.0..=1.5).contains(
+    &config.gameplay.left_hand_volume
+  ) || !(0.0..=1.5).contains(
+    &config.gameplay.right_hand_volume
+  ) {
+    bail!(
+      "gameplay.left_hand_volume and \
+       gameplay.right_hand_volume \
+       must be in range 0.0..=1.5"
+    );
+  }
+
+  if !(0.25..=2.0).contains(
+    &config.gameplay.playback_speed
+  ) {
+    bail!(
+      "gameplay.playback_speed must \
+       be in range 0.25..=2.0"
+    );
+  }
+
+  if config
+    .gameplay
+    .tutorial_wrong_note_grace_ms
+    > 2_000
+  {
+    bail!(
+      "gameplay.tutorial_wrong_note_\
+       grace_ms must be <= 2000"
+    );
+  }
+
+  if !(4..=250)
+    .contains(&config.app.tick_rate_ms)
+  {
+    bail!(
+      "app.tick_rate_ms must be in \
+       range 4..=250"
+    );
+  }
+
+  if config
+    .keyboard
+    .keymap_export_path
+    .trim()
+    .is_empty()
+  {
+    bail!(
+      "keyboard.keymap_export_path \
+       cannot be empty"
+    );
+  }
+
+  if config
+    .song_library
+    .midi_default_tags
+    .iter()
+    .any(|tag| tag.trim().is_empty())
+  {
+    bail!(
+      "song_library.midi_default_tags \
+       cannot contain empty tags"
+    );
+  }
+
+  if !(0.0..=1.0).contains(
+    &config
+      .song_library
+      .midi_import
+      .tie_gap_beats
+  ) {
+    bail!(
+      "song_library.midi_import.\
+       tie_gap_beats must be in range \
+       0.0..=1.0"
+    );
+  }
+
+  if !(24..=15_360).contains(
+    &config
+      .song_library
+      .midi_import
+      .smpte_fallback_ticks_per_beat
+  ) {
+    bail!(
+      "song_library.midi_import.\
+       smpte_fallback_ticks_per_beat \
+       must be in range 24..=15360"
+    );
+  }
+
   if !(8..=44).contains(
     &config
       .gameplay
@@ -648,6 +1698,27 @@ fn validate_config(
     );
   }
 
+  if config.gameplay.session_minutes
+    > 600
+  {
+    bail!(
+      "gameplay.session_minutes must \
+       be in range 0..=600"
+    );
+  }
+
+  if !(-24..=24).contains(
+    &config
+      .gameplay
+      .global_transpose_semitones
+  ) {
+    bail!(
+      "gameplay.global_transpose_\
+       semitones must be in range \
+       -24..=24"
+    );
+  }
+
   if !(8..=200).contains(
     &config
       .gameplay
@@ -683,6 +1754,35 @@ fn validate_config(
     );
   }
 
+  if config.sync.enabled
+    && config
+      .sync
+      .target_address
+      .parse::<SocketAddr>()
+      .is_err()
+  {
+    bail!(
+      "sync.target_address must be an \
+       ip:port address"
+    );
+  }
+
+  if !config
+    .sync
+    .listen_address
+    .is_empty()
+    && config
+      .sync
+      .listen_address
+      .parse::<SocketAddr>()
+      .is_err()
+  {
+    bail!(
+      "sync.listen_address must be \
+       empty or an ip:port address"
+    );
+  }
+
   Ok(())
 }
 
@@ -742,6 +1842,33 @@ fn validate_instrument_profile(
         );
       }
 
+      if sf2
+        .min_note
+        .into_iter()
+        .chain(sf2.max_note)
+        .any(|note| note > 127)
+      {
+        bail!(
+          "audio.instrument_profiles.\
+           {profile_name}.min_note \
+           and max_note must be <= 127"
+        );
+      }
+
+      if let (
+        Some(min_note),
+        Some(max_note)
+      ) =
+        (sf2.min_note, sf2.max_note)
+        && min_note > max_note
+      {
+        bail!(
+          "audio.instrument_profiles.\
+           {profile_name}.min_note \
+           must be <= max_note"
+        );
+      }
+
       if !(0.0..=2.5).contains(
         &sf2.instrument_gain_multiplier
       ) {
@@ -752,12 +1879,46 @@ fn validate_instrument_profile(
            must be between 0.0 and 2.5"
         );
       }
+
+      if !(0.1..=5.0).contains(
+        &sf2.velocity_curve_gamma
+      ) {
+        bail!(
+          "audio.instrument_profiles.\
+           {profile_name}.\
+           velocity_curve_gamma must \
+           be between 0.1 and 5.0"
+        );
+      }
     }
   }
 
   Ok(())
 }
 
+fn default_soundfont_search_paths()
+-> Vec<String> {
+  [
+    "/usr/share/sounds/sf2/FluidR3_GM.\
+     sf2",
+    "/usr/share/sounds/sf2/TimGM6mb.\
+     sf2",
+    "/usr/share/sounds/sf2/default-GM.\
+     sf2",
+    "/usr/share/soundfonts/FluidR3_GM.\
+     sf2",
+    "/usr/share/sf2/FluidR3_GM.sf2",
+    "/Library/Audio/Sounds/Banks",
+    "/opt/homebrew/share/soundfonts",
+    "/usr/local/share/soundfonts",
+    "C:/soundfonts",
+    "C:/ProgramData/soundfonts"
+  ]
+  .into_iter()
+  .map(str::to_string)
+  .collect()
+}
+
 fn default_instrument_profiles()
 -> BTreeMap<String, InstrumentProfile> {
   fn sf2_profile(
@@ -776,7 +1937,14 @@ fn default_instrument_profiles()
         maximum_polyphony: polyphony,
         enable_reverb_and_chorus: true,
         instrument_gain_multiplier:
-          gain
+          gain,
+        min_note: None,
+        max_note: None,
+        out_of_range_notes:
+          NoteRangePolicy::default(),
+        velocity_curve:
+          VelocityCurve::default(),
+        velocity_curve_gamma: 1.0
       }
     )
   }
@@ -882,3 +2050,66 @@ fn default_keybindings(
 
   map
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn note_range_policies_at_both_edges()
+  {
+    let mut profile =
+      SoundFontProfile {
+        min_note: Some(48),
+        max_note: Some(72),
+        ..SoundFontProfile::default()
+      };
+    let fit =
+      |profile: &SoundFontProfile| {
+        [0, 47, 48, 72, 73, 127].map(
+          |note| {
+            profile
+              .fit_note_to_range(note)
+          }
+        )
+      };
+
+    assert_eq!(fit(&profile), [
+      None,
+      None,
+      Some(48),
+      Some(72),
+      None,
+      None
+    ]);
+
+    profile.out_of_range_notes =
+      NoteRangePolicy::Clamp;
+    assert_eq!(fit(&profile), [
+      Some(48),
+      Some(48),
+      Some(48),
+      Some(72),
+      Some(72),
+      Some(72)
+    ]);
+
+    profile.out_of_range_notes =
+      NoteRangePolicy::OctaveFold;
+    assert_eq!(fit(&profile), [
+      Some(48),
+      Some(59),
+      Some(48),
+      Some(72),
+      Some(61),
+      Some(67)
+    ]);
+
+    profile.min_note = Some(60);
+    profile.max_note = Some(64);
+    assert_eq!(
+      profile.fit_note_to_range(70),
+      None
+    );
+  }
+}
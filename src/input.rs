@@ -8,6 +8,12 @@ use std::fmt::{
   Display,
   Formatter
 };
+use std::mem;
+use std::sync::{
+  Arc,
+  Mutex
+};
+use std::time::Instant;
 
 use anyhow::{
   Context,
@@ -19,6 +25,13 @@ use iced::keyboard::{
   self,
   Key
 };
+use midir::{
+  MidiInput,
+  MidiInputConnection
+};
+use tracing::warn;
+
+use crate::config::KeybindingConflictPolicy;
 
 #[derive(
   Debug,
@@ -81,6 +94,18 @@ impl KeyChord {
       modifiers: normalized_mods
     })
   }
+
+  pub fn without_shift(
+    &self
+  ) -> Option<Self> {
+    if !self.modifiers.shift {
+      return None;
+    }
+
+    let mut unshifted = self.clone();
+    unshifted.modifiers.shift = false;
+    Some(unshifted)
+  }
 }
 
 impl Display for KeyChord {
@@ -110,7 +135,8 @@ impl Display for KeyChord {
 }
 
 pub fn compile_note_bindings(
-  raw_bindings: &BTreeMap<String, u8>
+  raw_bindings: &BTreeMap<String, u8>,
+  on_conflict: KeybindingConflictPolicy
 ) -> Result<HashMap<KeyChord, u8>> {
   let mut compiled = HashMap::new();
 
@@ -132,21 +158,205 @@ pub fn compile_note_bindings(
         )
       })?;
 
-    if let Some(existing_note) =
-      compiled.insert(
-        chord.clone(),
-        *midi_note
-      )
-    {
+    let Some(&existing_note) =
+      compiled.get(&chord)
+    else {
+      compiled
+        .insert(chord, *midi_note);
+      continue;
+    };
+
+    match on_conflict {
+      | KeybindingConflictPolicy::Error => {
+        bail!(
+          "duplicate keybinding {chord} \
+           (MIDI {existing_note} and \
+           MIDI {midi_note})"
+        );
+      }
+      | KeybindingConflictPolicy::WarnLastWins => {
+        warn!(%chord, kept = midi_note, dropped = existing_note, spec = %chord_spec, "duplicate keybinding resolved, last wins");
+        compiled.insert(chord, *midi_note);
+      }
+      | KeybindingConflictPolicy::WarnFirstWins => {
+        warn!(%chord, kept = existing_note, dropped = midi_note, spec = %chord_spec, "duplicate keybinding resolved, first wins");
+      }
+    }
+  }
+
+  Ok(compiled)
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  PartialEq,
+  Eq,
+  Hash,
+)]
+pub enum KeyRow {
+  Number,
+  Top,
+  Home,
+  Bottom
+}
+
+impl KeyRow {
+  pub const ALL: [KeyRow; 4] = [
+    KeyRow::Number,
+    KeyRow::Top,
+    KeyRow::Home,
+    KeyRow::Bottom
+  ];
+
+  pub fn keys(
+    self
+  ) -> &'static [&'static str] {
+    match self {
+      | Self::Number => {
+        &[
+          "`", "1", "2", "3", "4", "5",
+          "6", "7", "8", "9", "0", "-",
+          "="
+        ]
+      }
+      | Self::Top => {
+        &[
+          "q", "w", "e", "r", "t", "y",
+          "u", "i", "o", "p", "[", "]",
+          "\\"
+        ]
+      }
+      | Self::Home => {
+        &[
+          "a", "s", "d", "f", "g", "h",
+          "j", "k", "l", ";", "'"
+        ]
+      }
+      | Self::Bottom => {
+        &[
+          "z", "x", "c", "v", "b", "n",
+          "m", ",", ".", "/"
+        ]
+      }
+    }
+  }
+
+  fn parse(
+    token: &str
+  ) -> Option<Self> {
+    match token {
+      | "number" => Some(Self::Number),
+      | "top" => Some(Self::Top),
+      | "home" => Some(Self::Home),
+      | "bottom" => Some(Self::Bottom),
+      | _ => None
+    }
+  }
+
+  pub fn of_key(
+    key: &str
+  ) -> Option<Self> {
+    match key {
+      | "`" | "1" | "2" | "3" | "4"
+      | "5" | "6" | "7" | "8"
+      | "9" | "0" | "-" | "=" => {
+        Some(Self::Number)
+      }
+      | "q" | "w" | "e" | "r" | "t"
+      | "y" | "u" | "i" | "o"
+      | "p" | "[" | "]" | "\\" => {
+        Some(Self::Top)
+      }
+      | "a" | "s" | "d" | "f" | "g"
+      | "h" | "j" | "k" | "l"
+      | ";" | "'" => Some(Self::Home),
+      | "z" | "x" | "c" | "v" | "b"
+      | "n" | "m" | "," | "."
+      | "/" => Some(Self::Bottom),
+      | _ => None
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct VelocityMap {
+  default:  u8,
+  by_chord: HashMap<KeyChord, u8>,
+  by_row:   HashMap<KeyRow, u8>
+}
+
+impl VelocityMap {
+  pub fn velocity_for(
+    &self,
+    chord: &KeyChord
+  ) -> u8 {
+    self
+      .by_chord
+      .get(chord)
+      .or_else(|| {
+        KeyRow::of_key(&chord.key)
+          .and_then(|row| {
+            self.by_row.get(&row)
+          })
+      })
+      .copied()
+      .unwrap_or(self.default)
+  }
+}
+
+pub fn compile_velocity_map(
+  default_velocity: u8,
+  raw_entries: &BTreeMap<String, u8>
+) -> Result<VelocityMap> {
+  let mut by_chord = HashMap::new();
+  let mut by_row = HashMap::new();
+
+  for (spec, velocity) in raw_entries {
+    if !(1..=127).contains(velocity) {
       bail!(
-        "duplicate keybinding {chord} \
-         (MIDI {existing_note} and \
-         MIDI {midi_note})"
+        "velocity map entry {spec} \
+         must be in range 1..=127 \
+         (velocity 0 is a note-off)"
       );
     }
+
+    if let Some(row_name) =
+      spec.strip_prefix("row:")
+    {
+      let row = KeyRow::parse(
+        &row_name
+          .trim()
+          .to_ascii_lowercase()
+      )
+      .with_context(|| {
+        format!(
+          "unknown keyboard row \
+           '{row_name}' in velocity \
+           map (expected number, top, \
+           home, bottom)"
+        )
+      })?;
+      by_row.insert(row, *velocity);
+      continue;
+    }
+
+    let chord = parse_chord(spec)
+      .with_context(|| {
+        format!(
+          "invalid velocity map key \
+           {spec}"
+        )
+      })?;
+    by_chord.insert(chord, *velocity);
   }
 
-  Ok(compiled)
+  Ok(VelocityMap {
+    default: default_velocity,
+    by_chord,
+    by_row
+  })
 }
 
 pub fn compile_chord_set(
@@ -341,6 +551,52 @@ fn parse_function_key_token(
   Ok(Some(format!("f{n}")))
 }
 
+pub fn modifier_key_token(
+  key: &Key
+) -> Option<&'static str> {
+  match key.as_ref() {
+    | Key::Named(Named::Shift) => {
+      Some("shift")
+    }
+    | Key::Named(Named::Control) => {
+      Some("ctrl")
+    }
+    | Key::Named(
+      Named::Alt | Named::AltGraph
+    ) => Some("alt"),
+    | Key::Named(
+      Named::Super | Named::Meta
+    ) => Some("super"),
+    | _ => None
+  }
+}
+
+pub fn chord_layout_rank(
+  spec: &str,
+  key_priority: &[&str]
+) -> (usize, usize) {
+  let Ok(chord) = parse_chord(spec)
+  else {
+    return (usize::MAX, usize::MAX);
+  };
+
+  let modifiers = [
+    chord.modifiers.ctrl,
+    chord.modifiers.alt,
+    chord.modifiers.logo,
+    chord.modifiers.shift
+  ]
+  .into_iter()
+  .filter(|active| *active)
+  .count();
+  let position = key_priority
+    .iter()
+    .position(|key| *key == chord.key)
+    .unwrap_or(key_priority.len());
+
+  (modifiers, position)
+}
+
 fn key_to_token_from_event(
   key: &Key
 ) -> Option<(String, bool)> {
@@ -466,10 +722,254 @@ fn named_function_key(
   }
 }
 
+pub const MIDI_CLOCK_PPQN: f32 = 24.0;
+
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq,
+)]
+pub enum ClockTransport {
+  Start,
+  Continue,
+  Stop
+}
+
+#[derive(Debug, Default)]
+pub struct MidiClockState {
+  running:         bool,
+  awaiting_origin: bool,
+  pulses:          u64,
+  last_pulse_at:   Option<Instant>,
+  pulse_seconds:   Option<f32>,
+  transport:       Vec<ClockTransport>
+}
+
+impl MidiClockState {
+  pub fn handle_message(
+    &mut self,
+    message: &[u8],
+    at: Instant
+  ) {
+    match message.first() {
+      | Some(0xf8) => {
+        if let Some(last) =
+          self.last_pulse_at
+        {
+          let interval = at
+            .duration_since(last)
+            .as_secs_f32();
+          if interval > 0.0
+            && interval < 1.0
+          {
+            self.pulse_seconds = Some(
+              self
+                .pulse_seconds
+                .map_or(
+                  interval,
+                  |smoothed| {
+                    smoothed * 0.9
+                      + interval * 0.1
+                  }
+                )
+            );
+          }
+        }
+        self.last_pulse_at = Some(at);
+
+        if !self.running {
+          return;
+        }
+        if self.awaiting_origin {
+          self.awaiting_origin = false;
+        } else {
+          self.pulses += 1;
+        }
+      }
+      | Some(0xfa) => {
+        self.running = true;
+        self.awaiting_origin = true;
+        self.pulses = 0;
+        self
+          .transport
+          .push(ClockTransport::Start);
+      }
+      | Some(0xfb) => {
+        self.running = true;
+        self.transport.push(
+          ClockTransport::Continue
+        );
+      }
+      | Some(0xfc) => {
+        self.running = false;
+        self
+          .transport
+          .push(ClockTransport::Stop);
+      }
+      | _ => {}
+    }
+  }
+
+  pub fn beats(
+    &self,
+    now: Instant
+  ) -> Option<f32> {
+    if !self.running {
+      return None;
+    }
+    self.last_pulse_at?;
+
+    let whole = self.pulses as f32
+      / MIDI_CLOCK_PPQN;
+    if self.awaiting_origin {
+      return Some(whole);
+    }
+
+    let fraction = match (
+      self.last_pulse_at,
+      self.pulse_seconds
+    ) {
+      | (Some(last), Some(pulse)) => {
+        (now
+          .saturating_duration_since(
+            last
+          )
+          .as_secs_f32()
+          / pulse)
+          .min(1.0)
+      }
+      | _ => 0.0
+    };
+
+    Some(
+      whole
+        + fraction / MIDI_CLOCK_PPQN
+    )
+  }
+
+  pub fn bpm(&self) -> Option<f32> {
+    self.pulse_seconds.map(|pulse| {
+      60.0 / (pulse * MIDI_CLOCK_PPQN)
+    })
+  }
+
+  pub fn take_transport(
+    &mut self
+  ) -> Vec<ClockTransport> {
+    mem::take(&mut self.transport)
+  }
+}
+
+pub struct MidiClockInput {
+  pub port_name: String,
+  pub state: Arc<Mutex<MidiClockState>>,
+  _connection:   MidiInputConnection<()>
+}
+
+pub fn open_midi_clock(
+  port_filter: &str
+) -> Result<MidiClockInput> {
+  let input =
+    MidiInput::new("symfose-clock")
+      .context(
+        "failed creating MIDI input \
+         client"
+      )?;
+
+  let Some((port, port_name)) = input
+    .ports()
+    .into_iter()
+    .filter_map(|port| {
+      let name =
+        input.port_name(&port).ok()?;
+      Some((port, name))
+    })
+    .find(|(_, name)| {
+      name.contains(port_filter)
+    })
+  else {
+    bail!(
+      "no MIDI input port matches \
+       '{port_filter}'"
+    );
+  };
+
+  let state = Arc::new(Mutex::new(
+    MidiClockState::default()
+  ));
+  let shared = Arc::clone(&state);
+  let connection = match input.connect(
+    &port,
+    "symfose-clock-in",
+    move |_, message, _| {
+      if let Ok(mut clock) =
+        shared.lock()
+      {
+        clock.handle_message(
+          message,
+          Instant::now()
+        );
+      }
+    },
+    ()
+  ) {
+    | Ok(connection) => connection,
+    | Err(error) => {
+      bail!(
+        "failed connecting MIDI clock \
+         input {port_name}: {error}"
+      );
+    }
+  };
+
+  Ok(MidiClockInput {
+    port_name,
+    state,
+    _connection: connection
+  })
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  #[test]
+  fn resolves_duplicate_keybindings_by_policy()
+   {
+    let raw = BTreeMap::from([
+      ("control+a".to_string(), 60),
+      ("ctrl+a".to_string(), 62)
+    ]);
+    let chord =
+      parse_chord("ctrl+a").unwrap();
+
+    assert!(
+      compile_note_bindings(
+        &raw,
+        KeybindingConflictPolicy::Error
+      )
+      .is_err()
+    );
+
+    let last = compile_note_bindings(
+      &raw,
+      KeybindingConflictPolicy::WarnLastWins
+    )
+    .unwrap();
+    assert_eq!(
+      last.get(&chord),
+      Some(&62)
+    );
+
+    let first = compile_note_bindings(
+      &raw,
+      KeybindingConflictPolicy::WarnFirstWins
+    )
+    .unwrap();
+    assert_eq!(
+      first.get(&chord),
+      Some(&60)
+    );
+  }
+
   #[test]
   fn parses_function_key() {
     let chord =
@@ -500,4 +1000,189 @@ mod tests {
     assert_eq!(chord.key, "a");
     assert!(!chord.modifiers.shift);
   }
+
+  #[test]
+  fn velocity_map_prefers_chord_over_row()
+   {
+    let mut raw = BTreeMap::new();
+    raw.insert(
+      "row:number".to_string(),
+      120
+    );
+    raw.insert("1".to_string(), 64);
+
+    let map =
+      compile_velocity_map(100, &raw)
+        .unwrap();
+
+    assert_eq!(
+      map.velocity_for(
+        &parse_chord("1").unwrap()
+      ),
+      64
+    );
+    assert_eq!(
+      map.velocity_for(
+        &parse_chord("2").unwrap()
+      ),
+      120
+    );
+    assert_eq!(
+      map.velocity_for(
+        &parse_chord("f").unwrap()
+      ),
+      100
+    );
+  }
+
+  #[test]
+  fn strips_shift_only_when_present() {
+    let shifted =
+      parse_chord("shift+f").unwrap();
+    let unshifted =
+      shifted.without_shift().expect(
+        "shift should be removed"
+      );
+
+    assert_eq!(
+      unshifted,
+      parse_chord("f").unwrap()
+    );
+    assert!(
+      unshifted
+        .without_shift()
+        .is_none()
+    );
+  }
+
+  #[test]
+  fn recognizes_modifier_only_keys() {
+    assert_eq!(
+      modifier_key_token(&Key::Named(
+        Named::Shift
+      )),
+      Some("shift")
+    );
+    assert_eq!(
+      modifier_key_token(
+        &Key::Character("a".into())
+      ),
+      None
+    );
+  }
+
+  #[test]
+  fn ranks_chords_by_layout_priority() {
+    let priority = ["f", "j", "a"];
+    assert!(
+      chord_layout_rank("j", &priority)
+        < chord_layout_rank(
+          "a", &priority
+        )
+    );
+    assert!(
+      chord_layout_rank("z", &priority)
+        < chord_layout_rank(
+          "shift+f", &priority
+        )
+    );
+  }
+
+  #[test]
+  fn row_keys_round_trip_through_of_key()
+   {
+    for row in KeyRow::ALL {
+      for key in row.keys() {
+        assert_eq!(
+          KeyRow::of_key(key),
+          Some(row)
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn derives_beats_and_tempo_from_midi_clock()
+   {
+    use std::time::Duration;
+
+    let origin = Instant::now();
+    let pulse = Duration::from_secs_f32(
+      0.5 / 24.0
+    );
+    let mut clock =
+      MidiClockState::default();
+
+    assert_eq!(
+      clock.beats(origin),
+      None
+    );
+
+    clock
+      .handle_message(&[0xfa], origin);
+    for index in 0..=48 {
+      clock.handle_message(
+        &[0xf8],
+        origin + pulse * index
+      );
+    }
+
+    let at = origin + pulse * 48;
+    let beats =
+      clock.beats(at).unwrap();
+    assert!((beats - 2.0).abs() < 1e-3);
+    let bpm = clock.bpm().unwrap();
+    assert!((bpm - 120.0).abs() < 0.5);
+    assert_eq!(
+      clock.take_transport(),
+      vec![ClockTransport::Start]
+    );
+
+    clock.handle_message(&[0xfc], at);
+    clock.handle_message(
+      &[0xf8],
+      at + pulse
+    );
+    assert_eq!(
+      clock.beats(at + pulse * 10),
+      None
+    );
+    assert_eq!(
+      clock.take_transport(),
+      vec![ClockTransport::Stop]
+    );
+  }
+
+  #[test]
+  fn clock_pulses_while_stopped_report_no_beats()
+   {
+    use std::time::Duration;
+
+    let origin = Instant::now();
+    let pulse = Duration::from_secs_f32(
+      0.5 / 24.0
+    );
+    let mut clock =
+      MidiClockState::default();
+    for index in 0..24 {
+      clock.handle_message(
+        &[0xf8],
+        origin + pulse * index
+      );
+    }
+
+    let at = origin + pulse * 23;
+    assert_eq!(clock.beats(at), None);
+    let bpm = clock.bpm().unwrap();
+    assert!((bpm - 120.0).abs() < 0.5);
+    assert!(
+      clock.take_transport().is_empty()
+    );
+
+    clock.handle_message(&[0xfb], at);
+    assert_eq!(
+      clock.beats(at),
+      Some(0.0)
+    );
+  }
 }
@@ -3,9 +3,11 @@ mod config;
 mod input;
 mod songs;
 
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::collections::{
   BTreeMap,
+  BTreeSet,
   HashMap,
   HashSet
 };
@@ -15,18 +17,22 @@ use std::fmt::{
   Formatter,
   Result as FmtResult
 };
+use std::net::UdpSocket;
 use std::path::{
   Path,
   PathBuf
 };
 use std::time::{
   Duration,
-  Instant
+  Instant,
+  SystemTime,
+  UNIX_EPOCH
 };
 
 use anyhow::{
   Context,
-  Result
+  Result,
+  bail
 };
 use iced::widget::{
   button,
@@ -58,7 +64,8 @@ use iced::{
 use tracing::{
   debug,
   info,
-  trace
+  trace,
+  warn
 };
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::layer::SubscriberExt;
@@ -71,94 +78,355 @@ use tracing_subscriber::{
 use crate::audio::AudioEngine;
 use crate::config::{
   AppConfig,
+  AudioBackend,
   DEFAULT_CONFIG_PATH,
-  KeyboardLayout
+  GameplayConfig,
+  InstrumentProfile,
+  KeyboardLayout,
+  MetronomeNoteOff,
+  MidiSyncConfig,
+  NoteRangePolicy,
+  OctaveConvention,
+  PracticeHand,
+  PrimaryLabelOrder,
+  SharedHandNotes,
+  SongLibraryConfig,
+  SoundFontProfile,
+  SyncConfig,
+  TransposeStrategy,
+  keyboard_layout_key_priority
 };
 use crate::input::{
+  ClockTransport,
   KeyChord,
+  KeyModifiers,
+  KeyRow,
+  MidiClockInput,
+  VelocityMap,
+  chord_layout_rank,
   compile_chord_set,
-  compile_note_bindings
+  compile_note_bindings,
+  compile_velocity_map,
+  modifier_key_token,
+  open_midi_clock
 };
 use crate::songs::{
+  Hand,
+  LibraryLoadStats,
   LoadedSong,
+  OUTLINE_COLUMNS,
+  SongEvent,
   SongFile,
+  ensure_song_parsed,
+  export_midi,
   load_song_library
 };
 
+const MIN_PLAYBACK_SPEED: f32 = 0.25;
+const MAX_PLAYBACK_SPEED: f32 = 2.0;
 const FLASH_DURATION: Duration =
   Duration::from_millis(170);
-const TICK_RATE: Duration =
-  Duration::from_millis(16);
 const TIMER_WINDOW_SECONDS: f32 = 0.18;
 const TIMER_PERFECT_SECONDS: f32 = 0.07;
+const DRILL_CONTEXT_BARS: usize = 1;
+const DRILL_AUTOPLAY_SPEED: f32 = 0.75;
+const CLI_USAGE: &str =
+  "usage: symfose [--config <path>] \
+   [--no-audio] [--normalize-song \
+   <song.toml>]...";
 
 const WHITE_KEY_WIDTH: f32 = 72.0;
 const WHITE_KEY_HEIGHT: f32 = 250.0;
 const BLACK_KEY_WIDTH: f32 = 44.0;
 const BLACK_KEY_HEIGHT: f32 = 152.0;
+const THUMBNAIL_WIDTH: f32 = 144.0;
+const THUMBNAIL_HEIGHT: f32 = 24.0;
+const PITCH_CLASS_NAMES: [&str; 12] = [
+  "C", "C#", "D", "D#", "E", "F", "F#",
+  "G", "G#", "A", "A#", "B"
+];
+const STACKED_CHIP_MIN_NOTES: usize = 4;
+const TIMING_HISTOGRAM_BUCKETS: usize =
+  9;
+const TIMING_HISTOGRAM_HEIGHT: f32 =
+  48.0;
+const STACKED_CHIP_LINE_HEIGHT: f32 =
+  14.0;
+const CALIBRATION_CLICKS: u32 = 8;
+const CALIBRATION_CLICK_INTERVAL_MS:
+  u64 = 600;
 
 #[derive(Debug)]
 struct RuntimeBindings {
-  note_bindings:  HashMap<KeyChord, u8>,
+  note_bindings: HashMap<KeyChord, u8>,
   note_to_chords:
     BTreeMap<u8, Vec<String>>,
-  quit:           HashSet<KeyChord>,
-  list_songs:     HashSet<KeyChord>,
+  quit: HashSet<KeyChord>,
+  list_songs: HashSet<KeyChord>,
   print_bindings: HashSet<KeyChord>,
-  play_song:      HashSet<KeyChord>
+  play_song: HashSet<KeyChord>,
+  go_to_song: HashSet<KeyChord>,
+  global_transpose_up:
+    HashSet<KeyChord>,
+  global_transpose_down:
+    HashSet<KeyChord>,
+  sustain: HashSet<KeyChord>,
+  note_velocity:         VelocityMap
+}
+
+impl RuntimeBindings {
+  fn control_chords(
+    &self
+  ) -> impl Iterator<Item = &KeyChord>
+  {
+    [
+      &self.quit,
+      &self.list_songs,
+      &self.print_bindings,
+      &self.play_song,
+      &self.go_to_song,
+      &self.global_transpose_up,
+      &self.global_transpose_down,
+      &self.sustain
+    ]
+    .into_iter()
+    .flatten()
+  }
 }
 
 struct PianoApp {
   config: AppConfig,
+  config_path: PathBuf,
+  settings_dirty: bool,
+  last_auto_save: Instant,
   bindings: RuntimeBindings,
   songs: Vec<LoadedSong>,
+  song_thumbnails:
+    HashMap<String, SongThumbnail>,
   audio: AudioEngine,
   selected_song: Option<usize>,
   prepared_song: Option<PreparedSong>,
-  held_notes: HashSet<u8>,
-  flashed_notes: HashMap<u8, Instant>,
+  held_notes: HashMap<u8, Option<u8>>,
+  held_keys: HashMap<String, u8>,
+  clicked_note: Option<u8>,
+  flashed_notes: HashMap<
+    u8,
+    (Instant, Instant, FlashSource)
+  >,
   activity: Vec<String>,
   startup_notice: String,
   song_search_query: String,
+  song_jump: Option<SongJump>,
   instrument_options: Vec<String>,
   selected_instrument: String,
   transpose_song_to_fit_bindings: bool,
   warn_on_missing_song_notes: bool,
   optimize_bindings_for_song: bool,
+  binding_rebuild_pending: bool,
   auto_jump_pressed_key_into_view: bool,
   auto_scroll_song_lane_follow_playback:
     bool,
+  song_keys_only: bool,
+  song_keys: BTreeSet<u8>,
+  practice_hand: PracticeHand,
   keyboard_focus_note: Option<u8>,
   prepared_transpose_semitones: i8,
+  global_transpose: i8,
   missing_song_notes: Vec<u8>,
+  out_of_range_song_notes: Vec<u8>,
+  supplemental_bindings:
+    BTreeMap<String, u8>,
+  show_keymap_reference: bool,
+  sustain_down: bool,
+  song_page: usize,
+  scale_root: ScaleRoot,
+  scale_kind: ScaleKind,
+  unmapped_key_log:
+    HashMap<String, Instant>,
   play_mode: PlayMode,
   tutorial_options: TutorialOptions,
   playback: Option<PlaybackState>,
+  auto_advance_at: Option<Instant>,
+  pending_quit_at: Option<Instant>,
+  practice_session:
+    Option<PracticeSession>,
+  latency_calibration:
+    Option<LatencyCalibration>,
   last_timer_score: Option<TimerScore>,
-  volume: f32
+  last_performance: Vec<(f32, u8, u8)>,
+  timeline_scrubbing: bool,
+  practice_start_index: Option<usize>,
+  sync_socket: Option<UdpSocket>,
+  sync_input: Option<UdpSocket>,
+  midi_clock: Option<MidiClockInput>,
+  worst_section: Option<PracticeWindow>,
+  volume: f32,
+  left_hand_volume: f32,
+  right_hand_volume: f32,
+  playback_speed: f32,
+  drill_restore_speed: Option<f32>
+}
+
+#[derive(Debug)]
+struct PracticeSession {
+  ends_at:          Instant,
+  runs_completed:   usize,
+  timer_accuracies: Vec<f32>
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PracticeWindow {
+  start_seconds: f32,
+  end_seconds:   f32,
+  first_bar:     usize,
+  last_bar:      usize,
+  missed_notes:  usize
+}
+
+#[derive(Debug, Clone)]
+struct SongThumbnail {
+  rows: Vec<Vec<(u16, bool)>>
+}
+
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq,
+)]
+enum FlashSource {
+  User,
+  Autoplay
+}
+
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq,
+)]
+enum KeymapStatus {
+  Note(u8),
+  Control,
+  Unbound
+}
+
+#[derive(Debug, Clone)]
+struct SongJump {
+  previous_query:     String,
+  previous_selection: Option<usize>
 }
 
 #[derive(Debug, Clone)]
 struct PreparedSong {
-  events:           Vec<PreparedEvent>,
-  expected_notes:   Vec<ExpectedNote>,
-  duration_seconds: f32,
-  beat_seconds:     f32
+  events: Vec<PreparedEvent>,
+  expected_notes: Vec<ExpectedNote>,
+  duration_seconds:    f32,
+  beat_seconds:        f32,
+  beat_times:          Vec<f32>,
+  trim_offset_seconds: f32
+}
+
+impl PreparedSong {
+  /// Fractional beat count at a
+  /// playback time, following the
+  /// tempo map and section tempos.
+  fn beat_at_seconds(
+    &self,
+    seconds: f32
+  ) -> f32 {
+    let beat_seconds =
+      self.edge_beat_seconds();
+    let Some(first) =
+      self.beat_times.first()
+    else {
+      return (seconds
+        + self.trim_offset_seconds)
+        / beat_seconds;
+    };
+    if seconds < *first {
+      return (seconds - first)
+        / beat_seconds;
+    }
+
+    let index =
+      self.beat_times.partition_point(
+        |time| *time <= seconds
+      ) - 1;
+    let span = self
+      .beat_times
+      .get(index + 1)
+      .map_or(beat_seconds, |next| {
+        next - self.beat_times[index]
+      })
+      .max(1e-3);
+    index as f32
+      + (seconds
+        - self.beat_times[index])
+        / span
+  }
+
+  /// Playback time of a fractional
+  /// beat count; the inverse of
+  /// `beat_at_seconds`.
+  fn seconds_at_beat(
+    &self,
+    beat: f32
+  ) -> f32 {
+    let beat_seconds =
+      self.edge_beat_seconds();
+    let Some(last) =
+      self.beat_times.last()
+    else {
+      return beat * beat_seconds
+        - self.trim_offset_seconds;
+    };
+    let beat = beat.max(0.0);
+    let index = beat.floor() as usize;
+    match (
+      self.beat_times.get(index),
+      self.beat_times.get(index + 1)
+    ) {
+      | (Some(start), Some(end)) => {
+        start
+          + (end - start)
+            * (beat - index as f32)
+      }
+      | _ => {
+        last
+          + (beat
+            - (self.beat_times.len()
+              - 1) as f32)
+            * beat_seconds
+      }
+    }
+  }
+
+  fn edge_beat_seconds(&self) -> f32 {
+    match self.beat_times.as_slice() {
+      | [.., previous, last] => {
+        last - previous
+      }
+      | _ => self.beat_seconds
+    }
+    .max(1e-3)
+  }
 }
 
 #[derive(Debug, Clone)]
 struct PreparedEvent {
   at_seconds:       f32,
+  at_beats:         f32,
+  duration_beats:   f32,
   duration_seconds: f32,
   duration_ms:      u64,
+  note_duration_ms: Vec<u64>,
   velocity:         u8,
-  notes:            Vec<u8>
+  hand:             Option<Hand>,
+  channel:          Option<u8>,
+  notes:            Vec<u8>,
+  fingering:        Vec<u8>
 }
 
 #[derive(Debug, Clone)]
 struct ExpectedNote {
-  at_seconds: f32,
-  midi_note:  u8
+  at_seconds:     f32,
+  midi_note:      u8,
+  duration_beats: f32
 }
 
 #[derive(
@@ -197,10 +465,137 @@ impl Display for PlayMode {
   }
 }
 
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq,
+)]
+struct ScaleRoot(u8);
+
+impl ScaleRoot {
+  const ALL: [ScaleRoot; 12] = [
+    ScaleRoot(0),
+    ScaleRoot(1),
+    ScaleRoot(2),
+    ScaleRoot(3),
+    ScaleRoot(4),
+    ScaleRoot(5),
+    ScaleRoot(6),
+    ScaleRoot(7),
+    ScaleRoot(8),
+    ScaleRoot(9),
+    ScaleRoot(10),
+    ScaleRoot(11)
+  ];
+}
+
+impl Display for ScaleRoot {
+  fn fmt(
+    &self,
+    f: &mut Formatter<'_>
+  ) -> FmtResult {
+    write!(
+      f,
+      "{}",
+      PITCH_CLASS_NAMES
+        [usize::from(self.0 % 12)]
+    )
+  }
+}
+
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq,
+)]
+enum ScaleKind {
+  Off,
+  Major,
+  NaturalMinor,
+  HarmonicMinor,
+  MajorPentatonic,
+  MinorPentatonic,
+  Blues,
+  Dorian,
+  Mixolydian
+}
+
+impl ScaleKind {
+  const ALL: [ScaleKind; 9] = [
+    ScaleKind::Off,
+    ScaleKind::Major,
+    ScaleKind::NaturalMinor,
+    ScaleKind::HarmonicMinor,
+    ScaleKind::MajorPentatonic,
+    ScaleKind::MinorPentatonic,
+    ScaleKind::Blues,
+    ScaleKind::Dorian,
+    ScaleKind::Mixolydian
+  ];
+
+  fn intervals(self) -> &'static [u8] {
+    match self {
+      | ScaleKind::Off => &[],
+      | ScaleKind::Major => {
+        &[0, 2, 4, 5, 7, 9, 11]
+      }
+      | ScaleKind::NaturalMinor => {
+        &[0, 2, 3, 5, 7, 8, 10]
+      }
+      | ScaleKind::HarmonicMinor => {
+        &[0, 2, 3, 5, 7, 8, 11]
+      }
+      | ScaleKind::MajorPentatonic => {
+        &[0, 2, 4, 7, 9]
+      }
+      | ScaleKind::MinorPentatonic => {
+        &[0, 3, 5, 7, 10]
+      }
+      | ScaleKind::Blues => {
+        &[0, 3, 5, 6, 7, 10]
+      }
+      | ScaleKind::Dorian => {
+        &[0, 2, 3, 5, 7, 9, 10]
+      }
+      | ScaleKind::Mixolydian => {
+        &[0, 2, 4, 5, 7, 9, 10]
+      }
+    }
+  }
+}
+
+impl Display for ScaleKind {
+  fn fmt(
+    &self,
+    f: &mut Formatter<'_>
+  ) -> FmtResult {
+    let label = match self {
+      | ScaleKind::Off => "Off",
+      | ScaleKind::Major => "Major",
+      | ScaleKind::NaturalMinor => {
+        "Natural minor"
+      }
+      | ScaleKind::HarmonicMinor => {
+        "Harmonic minor"
+      }
+      | ScaleKind::MajorPentatonic => {
+        "Major pentatonic"
+      }
+      | ScaleKind::MinorPentatonic => {
+        "Minor pentatonic"
+      }
+      | ScaleKind::Blues => "Blues",
+      | ScaleKind::Dorian => "Dorian",
+      | ScaleKind::Mixolydian => {
+        "Mixolydian"
+      }
+    };
+
+    write!(f, "{label}")
+  }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct TutorialOptions {
   only_advance_on_correct_note: bool,
-  play_bad_notes_out_loud:      bool
+  play_bad_notes_out_loud:      bool,
+  wrong_note_grace: Duration
 }
 
 impl Default for TutorialOptions {
@@ -209,65 +604,119 @@ impl Default for TutorialOptions {
       only_advance_on_correct_note:
         true,
       play_bad_notes_out_loud:
-        true
+        true,
+      wrong_note_grace:
+        Duration::ZERO
     }
   }
 }
 
 #[derive(Debug, Clone)]
 struct TimerScore {
-  expected_notes: usize,
-  hit_notes:      usize,
-  perfect_hits:   usize,
-  good_hits:      usize,
-  wrong_notes:    usize,
-  missed_notes:   usize
+  expected_notes:    usize,
+  hit_notes:         usize,
+  perfect_hits:      usize,
+  good_hits:         usize,
+  octave_hits:       usize,
+  wrong_notes:       usize,
+  missed_notes:      usize,
+  duration_weighted: bool,
+  expected_beats:    f32,
+  hit_beats:         f32,
+  hit_offsets:       Vec<f32>
 }
 
 impl TimerScore {
   fn new(
-    expected_notes: usize
+    expected: &[ExpectedNote],
+    duration_weighted: bool
   ) -> Self {
     Self {
-      expected_notes,
+      expected_notes: expected.len(),
       hit_notes: 0,
       perfect_hits: 0,
       good_hits: 0,
+      octave_hits: 0,
       wrong_notes: 0,
-      missed_notes: 0
+      missed_notes: 0,
+      duration_weighted,
+      expected_beats: expected
+        .iter()
+        .map(|note| note.duration_beats)
+        .sum(),
+      hit_beats: 0.0,
+      hit_offsets: Vec::new()
     }
   }
 
+  fn octave_summary(&self) -> String {
+    if self.octave_hits == 0 {
+      return String::new();
+    }
+
+    format!(
+      " octave-displaced {}",
+      self.octave_hits
+    )
+  }
+
   fn accuracy_percent(&self) -> f32 {
     if self.expected_notes == 0 {
       return 0.0;
     }
 
+    if self.duration_weighted
+      && self.expected_beats > 0.0
+    {
+      return (self.hit_beats
+        / self.expected_beats)
+        * 100.0;
+    }
+
     (self.hit_notes as f32
       / self.expected_notes as f32)
       * 100.0
   }
 }
 
+#[derive(Debug)]
+struct LatencyCalibration {
+  started_at:     Instant,
+  clicks_played:  u32,
+  tap_offsets_ms: Vec<i32>
+}
+
 #[derive(Debug)]
 struct PlaybackState {
-  mode:                  PlayMode,
-  started_at:            Instant,
-  cursor_seconds:        f32,
-  next_event_index:      usize,
-  tutorial_event_index:  usize,
-  tutorial_matched:      HashSet<u8>,
-  next_metronome_beat_s: f32,
-  next_metronome_index:  u64,
-  matched_note_indices:  HashSet<usize>,
-  score:                 TimerScore
+  mode:                     PlayMode,
+  started_at:               Instant,
+  cursor_seconds:           f32,
+  next_event_index:         usize,
+  tutorial_event_index:     usize,
+  tutorial_matched:         HashSet<u8>,
+  tutorial_last_correct_at:
+    Option<Instant>,
+  next_metronome_beat_s:    f32,
+  next_metronome_index:     u64,
+  matched_note_indices: HashSet<usize>,
+  strict_missed_indices: HashSet<usize>,
+  score:                    TimerScore,
+  loop_window: Option<PracticeWindow>,
+  loops_completed:          u32,
+  paused:                   bool
 }
 
 impl PlaybackState {
   fn new(
     mode: PlayMode,
-    prepared: &PreparedSong
+    prepared: &PreparedSong,
+    duration_weighted: bool
   ) -> Self {
+    let (
+      metronome_beat_s,
+      metronome_index
+    ) = first_metronome_beat(prepared);
+
     Self {
       mode,
       started_at: Instant::now(),
@@ -275,13 +724,22 @@ impl PlaybackState {
       next_event_index: 0,
       tutorial_event_index: 0,
       tutorial_matched: HashSet::new(),
-      next_metronome_beat_s: 0.0,
-      next_metronome_index: 0,
+      tutorial_last_correct_at: None,
+      next_metronome_beat_s:
+        metronome_beat_s,
+      next_metronome_index:
+        metronome_index,
       matched_note_indices:
         HashSet::new(),
+      strict_missed_indices:
+        HashSet::new(),
       score: TimerScore::new(
-        prepared.expected_notes.len()
-      )
+        &prepared.expected_notes,
+        duration_weighted
+      ),
+      loop_window: None,
+      paused: false,
+      loops_completed: 0
     }
   }
 }
@@ -295,8 +753,22 @@ enum Message {
   SelectSong(usize),
   StartPlayback,
   RestartPlayback,
+  DrillWorstSection,
+  ReplayPerformance,
+  TimelineScrubStarted(f32),
+  TimelineScrubMoved(f32),
+  TimelineScrubEnded,
+  SetPracticeStart(usize),
+  ClearPracticeStart,
+  PausePlayback,
+  ResumePlayback,
+  ToggleRecording,
+  StartLatencyCalibration,
   StopPlayback,
   VolumeChanged(f32),
+  LeftHandVolumeChanged(f32),
+  RightHandVolumeChanged(f32),
+  PlaybackSpeedChanged(f32),
   PlayModeSelected(PlayMode),
   TutorialAdvanceOnlyCorrectChanged(
     bool
@@ -310,19 +782,43 @@ enum Message {
   AutoJumpPressedKeyIntoViewChanged(
     bool
   ),
+  SongKeysOnlyChanged(bool),
+  PracticeHandSelected(PracticeHand),
   AutoScrollSongLaneFollowPlaybackChanged(
     bool
   ),
   PlayNoteFromClick(u8),
+  ReleaseClickedNote(u8),
   SongSearchChanged(String),
   ApplySongTagFilter(String),
   InstrumentSelected(String),
+  AuditionInstrument(String),
+  ResetBindingsAndGameplay,
+  MapMissingNotes,
+  ExportSongStems,
+  ExportSongMidi,
+  ShowKeymapReferenceChanged(bool),
+  ExportKeymap,
+  SongPageChanged(usize),
+  ReloadSongLibrary,
+  ScaleRootSelected(ScaleRoot),
+  ScaleKindSelected(ScaleKind),
+  SetGlobalTranspose(i8),
   Tick(Instant)
 }
 
 fn main() -> Result<()> {
+  let cli = parse_cli_args(
+    env::args().skip(1)
+  )?;
+  if !cli.normalize_songs.is_empty() {
+    return run_normalize_songs(
+      &cli.normalize_songs
+    );
+  }
+
   let config_path =
-    configured_config_path();
+    configured_config_path(&cli);
 
   let config = config::load_or_create(
     &config_path
@@ -347,90 +843,33 @@ fn main() -> Result<()> {
     "bindings compiled"
   );
 
-  let songs = load_song_library(
-    &config.song_library
-  )
-  .with_context(|| {
-    format!(
-      "failed loading songs from {}",
-      config.song_library.directory
+  let (songs, library_stats) =
+    load_song_library(
+      &config.song_library
     )
-  })?;
+    .with_context(|| {
+      format!(
+        "failed loading songs from {}",
+        config.song_library.directory
+      )
+    })?;
 
+  let mut audio_config =
+    config.audio.clone();
+  if cli.no_audio {
+    audio_config.backend =
+      AudioBackend::Null;
+  }
   let audio =
-    AudioEngine::new(&config.audio)?;
-  let instrument_options =
-    audio.available_profiles();
-  let selected_instrument = audio
-    .active_profile_name()
-    .to_string();
-
-  let selected_song =
-    if songs.is_empty() {
-      None
-    } else {
-      Some(0)
-    };
-
-  let mut initial_state = PianoApp {
-    startup_notice: format!(
-      "Loaded {} song(s) from \
-       sources: {}, {} (cache: {})",
-      songs.len(),
-      config.song_library.directory,
-      config
-        .song_library
-        .midi_directory,
-      config
-        .song_library
-        .cache_directory
-    ),
-    selected_song,
-    prepared_song: None,
-    volume: audio.master_volume(),
-    song_search_query: String::new(),
-    instrument_options,
-    selected_instrument,
-    transpose_song_to_fit_bindings:
-      config
-        .gameplay
-        .transpose_song_to_fit_bindings,
-    warn_on_missing_song_notes: config
-      .gameplay
-      .warn_on_missing_song_notes,
-    optimize_bindings_for_song: config
-      .gameplay
-      .optimize_bindings_for_song,
-    auto_jump_pressed_key_into_view:
-      config
-        .gameplay
-        .auto_jump_pressed_key_into_view,
-    auto_scroll_song_lane_follow_playback:
-      config
-        .gameplay
-        .auto_scroll_song_lane_follow_playback,
-    keyboard_focus_note: None,
-    prepared_transpose_semitones: 0,
-    missing_song_notes: Vec::new(),
+    AudioEngine::new(&audio_config)?;
+  let initial_state = PianoApp::new(
     config,
+    config_path,
     bindings,
     songs,
-    audio,
-    held_notes: HashSet::new(),
-    flashed_notes: HashMap::new(),
-    activity: vec![
-      "Press mapped keys to play. \
-       Choose a song mode and press \
-       Start."
-        .to_string(),
-    ],
-    play_mode: PlayMode::Timer,
-    tutorial_options:
-      TutorialOptions::default(),
-    playback: None,
-    last_timer_score: None
-  };
-  initial_state.rebuild_song_context();
+    library_stats,
+    audio
+  );
 
   let state_slot =
     RefCell::new(Some(initial_state));
@@ -466,6 +905,27 @@ fn update(
   app: &mut PianoApp,
   message: Message
 ) -> Task<Message> {
+  if matches!(
+    message,
+    Message::VolumeChanged(_)
+      | Message::InstrumentSelected(_)
+      | Message::TransposeSongToFitBindingsChanged(_)
+      | Message::WarnOnMissingSongNotesChanged(_)
+      | Message::OptimizeBindingsForSongChanged(_)
+      | Message::AutoJumpPressedKeyIntoViewChanged(_)
+      | Message::AutoScrollSongLaneFollowPlaybackChanged(_)
+      | Message::SongKeysOnlyChanged(_)
+      | Message::PracticeHandSelected(_)
+      | Message::TutorialAdvanceOnlyCorrectChanged(_)
+      | Message::TutorialPlayBadNotesChanged(_)
+      | Message::LeftHandVolumeChanged(_)
+      | Message::RightHandVolumeChanged(_)
+      | Message::PlaybackSpeedChanged(_)
+      | Message::ResetBindingsAndGameplay
+  ) {
+    app.settings_dirty = true;
+  }
+
   match message {
     | Message::RuntimeEvent(
       event,
@@ -488,12 +948,81 @@ fn update(
     | Message::RestartPlayback => {
       app.start_playback();
     }
+    | Message::DrillWorstSection => {
+      app.start_worst_section_drill();
+    }
+    | Message::ReplayPerformance => {
+      app.replay_last_performance();
+    }
+    | Message::TimelineScrubStarted(
+      seconds
+    ) => {
+      app.timeline_scrubbing = true;
+      app.seek_to(seconds);
+    }
+    | Message::TimelineScrubMoved(
+      seconds
+    ) => {
+      if app.timeline_scrubbing {
+        app.seek_to(seconds);
+      }
+    }
+    | Message::TimelineScrubEnded => {
+      app.timeline_scrubbing = false;
+    }
+    | Message::SetPracticeStart(index) => {
+      app.practice_start_index =
+        Some(index);
+      app.push_activity(format!(
+        "Practice starts at event {}.",
+        index + 1
+      ));
+    }
+    | Message::ClearPracticeStart => {
+      app.practice_start_index = None;
+      app.push_activity(
+        "Practice starts from the top."
+          .to_string()
+      );
+    }
+    | Message::PausePlayback => {
+      app.set_playback_paused(true);
+    }
+    | Message::ToggleRecording => {
+      app.toggle_recording();
+    }
+    | Message::StartLatencyCalibration => {
+      app.start_latency_calibration();
+    }
+    | Message::ResumePlayback => {
+      app.set_playback_paused(false);
+    }
     | Message::StopPlayback => {
       app.stop_playback();
     }
     | Message::VolumeChanged(volume) => {
       app.set_volume(volume);
     }
+    | Message::LeftHandVolumeChanged(
+      volume
+    ) => {
+      app.left_hand_volume =
+        volume.clamp(0.0, 1.5);
+      debug!(volume = app.left_hand_volume, "left hand volume updated");
+    }
+    | Message::RightHandVolumeChanged(
+      volume
+    ) => {
+      app.right_hand_volume =
+        volume.clamp(0.0, 1.5);
+      debug!(volume = app.right_hand_volume, "right hand volume updated");
+    }
+    | Message::PlaybackSpeedChanged(
+      speed
+    ) => {
+      app.drill_restore_speed = None;
+      app.set_playback_speed(speed);
+    }
     | Message::PlayModeSelected(mode) => {
       app.play_mode = mode;
       app.push_activity(format!(
@@ -539,8 +1068,19 @@ fn update(
     ) => {
       app.optimize_bindings_for_song =
         value;
-      app.rebuild_song_context();
-      info!(value, "optimize_bindings_for_song updated");
+      if app.playback.is_some() {
+        app.binding_rebuild_pending =
+          true;
+        app.push_activity(
+          "Binding optimization change \
+           will apply when playback \
+           stops."
+            .to_string()
+        );
+      } else {
+        app.rebuild_song_context();
+      }
+      info!(value, deferred = app.binding_rebuild_pending, "optimize_bindings_for_song updated");
     }
     | Message::AutoJumpPressedKeyIntoViewChanged(
       value
@@ -552,6 +1092,15 @@ fn update(
       }
       info!(value, "auto_jump_pressed_key_into_view updated");
     }
+    | Message::SongKeysOnlyChanged(value) => {
+      app.song_keys_only = value;
+      info!(value, "song_keys_only updated");
+    }
+    | Message::PracticeHandSelected(hand) => {
+      app.practice_hand = hand;
+      app.rebuild_song_context();
+      info!(%hand, "practice_hand updated");
+    }
     | Message::AutoScrollSongLaneFollowPlaybackChanged(
       value
     ) => {
@@ -562,34 +1111,71 @@ fn update(
     | Message::PlayNoteFromClick(
       midi_note
     ) => {
-      app.flash_note(midi_note);
+      app.release_clicked_note();
+      app.clicked_note = Some(midi_note);
+      app.flash_note(
+        midi_note,
+        FlashSource::User
+      );
       app.set_focus_note(midi_note);
+      app.record_performance_note(
+        midi_note,
+        app.config.input.note_velocity
+      );
       let play_out_loud = app
-        .process_note_input(midi_note);
-      if play_out_loud {
-        app.audio.play_note(
+        .process_note_input(
+          midi_note,
+          Instant::now()
+        );
+      let sounding = play_out_loud.then(
+        || {
           app.manual_playback_note(
             midi_note
           )
+        }
+      );
+      if let Some(sounding) = sounding {
+        app.audio.start_note(
+          sounding,
+          app.config.input.note_velocity
         );
       }
+      app
+        .held_notes
+        .insert(midi_note, sounding);
 
       let line = format!(
         "click -> {} ({midi_note})",
-        midi_note_name(midi_note)
+        midi_note_name(midi_note, app.config.ui.octave_convention)
       );
       app.push_activity(line);
-      info!(midi_note, note = %midi_note_name(midi_note), "piano key clicked");
+      info!(midi_note, note = %midi_note_name(midi_note, app.config.ui.octave_convention), "piano key clicked");
+    }
+    | Message::ReleaseClickedNote(
+      midi_note
+    ) => {
+      if app.clicked_note == Some(midi_note)
+      {
+        app.release_clicked_note();
+      }
     }
     | Message::SongSearchChanged(
       query
     ) => {
       app.song_search_query = query;
+      app.song_page = 0;
     }
     | Message::ApplySongTagFilter(
       tag
     ) => {
       app.song_search_query = tag;
+      app.song_page = 0;
+    }
+    | Message::SongPageChanged(page) => {
+      app.song_page = page;
+    }
+    | Message::ReloadSongLibrary => {
+      app.reload_song_library();
     }
     | Message::InstrumentSelected(
       instrument
@@ -607,6 +1193,7 @@ fn update(
             "Instrument switched to \
              {instrument}"
           ));
+          app.refresh_instrument_range_check();
         }
         | Err(error) => {
           app.push_activity(format!(
@@ -616,8 +1203,58 @@ fn update(
         }
       }
     }
+    | Message::AuditionInstrument(
+      instrument
+    ) => {
+      app.audition_instrument(&instrument);
+    }
+    | Message::ResetBindingsAndGameplay => {
+      app.reset_bindings_and_gameplay();
+    }
+    | Message::ShowKeymapReferenceChanged(
+      enabled
+    ) => {
+      app.show_keymap_reference = enabled;
+    }
+    | Message::ExportKeymap => {
+      app.export_keymap();
+    }
+    | Message::MapMissingNotes => {
+      app.map_missing_notes();
+    }
+    | Message::ExportSongStems => {
+      app.export_song_stems();
+    }
+    | Message::ExportSongMidi => {
+      app.export_song_midi();
+    }
+    | Message::ScaleRootSelected(root) => {
+      app.scale_root = root;
+      debug!(root = %root, "scale assist root selected");
+    }
+    | Message::ScaleKindSelected(kind) => {
+      app.scale_kind = kind;
+      debug!(kind = %kind, "scale assist scale selected");
+    }
+    | Message::SetGlobalTranspose(
+      semitones
+    ) => {
+      app.set_global_transpose(semitones);
+    }
     | Message::Tick(now) => {
       app.handle_tick(now);
+      return Task::batch(
+        app
+          .poll_sync_input()
+          .into_iter()
+          .map(|semitones| {
+            Task::done(
+              Message::SetGlobalTranspose(
+                semitones
+              )
+            )
+          })
+      );
     }
   }
 
@@ -650,6 +1287,11 @@ fn handle_runtime_event(
         return None;
       }
 
+      if app.song_jump.is_some() {
+        app.handle_song_jump_key(&key);
+        return None;
+      }
+
       if repeat
         && !app
           .config
@@ -673,6 +1315,17 @@ fn handle_runtime_event(
             .ignore_shift_for_char_keys
         )
       else {
+        if let Some(token) =
+          modifier_key_token(&key)
+          && !app
+            .config
+            .app
+            .mute_modifier_only_keys
+        {
+          app.log_unmapped_key(
+            token.to_string()
+          );
+        }
         return None;
       };
 
@@ -684,6 +1337,9 @@ fn handle_runtime_event(
         .contains(&chord)
       {
         info!(%chord, "quit chord received");
+        if !app.confirm_quit() {
+          return None;
+        }
         app.push_activity(
           "Quit requested from \
            keyboard chord."
@@ -719,55 +1375,105 @@ fn handle_runtime_event(
 
       if app
         .bindings
-        .play_song
+        .go_to_song
         .contains(&chord)
       {
-        return Some(Task::done(
-          Message::StartPlayback
-        ));
+        app.begin_song_jump();
+        return None;
       }
 
-      if let Some(midi_note) = app
+      if app
         .bindings
-        .note_bindings
-        .get(&chord)
-        .copied()
+        .global_transpose_up
+        .contains(&chord)
       {
-        app
-          .held_notes
-          .insert(midi_note);
-        app.flash_note(midi_note);
-        app.set_focus_note(midi_note);
+        app.shift_global_transpose(1);
+        return None;
+      }
 
-        let play_out_loud = app
-          .process_note_input(
-            midi_note
-          );
-        if play_out_loud {
-          app.audio.play_note(
-            app.manual_playback_note(
-              midi_note
-            )
-          );
-        }
+      if app
+        .bindings
+        .global_transpose_down
+        .contains(&chord)
+      {
+        app.shift_global_transpose(-1);
+        return None;
+      }
 
-        let label = format!(
+      if app
+        .bindings
+        .sustain
+        .contains(&chord)
+      {
+        app.set_sustain(true);
+        return None;
+      }
+
+      if app
+        .bindings
+        .play_song
+        .contains(&chord)
+      {
+        return Some(Task::done(
+          Message::StartPlayback
+        ));
+      }
+
+      if let Some(midi_note) =
+        app.note_for_chord(&chord)
+      {
+        app.flash_note(
+          midi_note,
+          FlashSource::User
+        );
+        app.set_focus_note(midi_note);
+        app.record_performance_note(
+          midi_note,
+          app
+            .bindings
+            .note_velocity
+            .velocity_for(&chord)
+        );
+
+        let play_out_loud = app
+          .process_note_input(
+            midi_note,
+            Instant::now()
+          );
+        let sounding = play_out_loud
+          .then(|| {
+            app.manual_playback_note(
+              midi_note
+            )
+          });
+        if let Some(sounding) = sounding
+        {
+          let velocity = app
+            .bindings
+            .note_velocity
+            .velocity_for(&chord);
+          app
+            .audio
+            .start_note(sounding, velocity);
+        }
+        app
+          .held_notes
+          .insert(midi_note, sounding);
+        app
+          .held_keys
+          .insert(chord.key.clone(), midi_note);
+
+        let label = format!(
           "{chord} -> {} ({midi_note})",
-          midi_note_name(midi_note)
+          midi_note_name(midi_note, app.config.ui.octave_convention)
         );
         app.push_activity(label);
 
-        info!(%chord, midi_note, note = %midi_note_name(midi_note), "mapped key pressed");
-      } else if app
-        .config
-        .app
-        .print_unmapped_keys
-      {
-        let line = format!(
-          "Unmapped chord: {chord}"
+        info!(%chord, midi_note, note = %midi_note_name(midi_note, app.config.ui.octave_convention), "mapped key pressed");
+      } else {
+        app.log_unmapped_key(
+          chord.to_string()
         );
-        app.push_activity(line.clone());
-        debug!(%chord, "unmapped key chord");
       }
     }
     | iced::Event::Keyboard(
@@ -790,17 +1496,35 @@ fn handle_runtime_event(
         return None;
       };
 
-      if let Some(midi_note) = app
+      if app
         .bindings
-        .note_bindings
-        .get(&chord)
-        .copied()
+        .sustain
+        .contains(&chord)
       {
-        app
-          .held_notes
-          .remove(&midi_note);
+        app.set_sustain(false);
+        return None;
+      }
+
+      if let Some(midi_note) = app
+        .held_keys
+        .remove(&chord.key)
+        .or_else(|| {
+          app.note_for_chord(&chord)
+        })
+        && let Some(Some(sounding)) =
+          app.held_notes.remove(&midi_note)
+      {
+        app.audio.stop_note(sounding);
       }
     }
+    | iced::Event::Mouse(
+      iced::mouse::Event::ButtonReleased(
+        iced::mouse::Button::Left
+      )
+    ) => {
+      app.timeline_scrubbing = false;
+      app.release_clicked_note();
+    }
     | iced::Event::Window(
       iced::window::Event::Resized(
         size
@@ -821,24 +1545,54 @@ fn handle_runtime_event(
 fn view(
   app: &PianoApp
 ) -> Element<'_, Message> {
-  let header = container(
-    column![
-      text("Symfose Virtual Piano")
-        .size(34),
-      text(
-        "Virtual piano workflow with \
-         timer scoring, tutorial \
-         guidance, and auto play."
+  let mut header_column = column![
+    text("Symfose Virtual Piano")
+      .size(34),
+    text(
+      "Virtual piano workflow with \
+       timer scoring, tutorial \
+       guidance, and auto play."
+    )
+    .size(16),
+    text(&app.startup_notice).size(14),
+  ]
+  .spacing(6);
+  if let Some(session) =
+    &app.practice_session
+  {
+    let remaining = session
+      .ends_at
+      .saturating_duration_since(
+        Instant::now()
       )
-      .size(16),
-      text(&app.startup_notice)
-        .size(14),
-    ]
-    .spacing(6)
-  )
-  .padding(16)
-  .width(Length::Fill)
-  .style(container::primary);
+      .as_secs();
+    header_column = header_column.push(
+      text(format!(
+        "Practice session: \
+         {:02}:{:02} left ({} run(s) \
+         completed)",
+        remaining / 60,
+        remaining % 60,
+        session.runs_completed
+      ))
+      .size(14)
+    );
+  }
+
+  if app.global_transpose != 0 {
+    header_column = header_column.push(
+      text(format!(
+        "Global transpose: {:+} st",
+        app.global_transpose
+      ))
+      .size(14)
+    );
+  }
+
+  let header = container(header_column)
+    .padding(16)
+    .width(Length::Fill)
+    .style(container::primary);
 
   let main_content = row![
     controls_panel(app),
@@ -874,7 +1628,13 @@ fn controls_panel(
       binding_rows.push(text(format!(
         "{:>3} {:<4} <- {chord_list}",
         note,
-        midi_note_name(*note)
+        midi_note_name(
+          *note,
+          app
+            .config
+            .ui
+            .octave_convention
+        )
       )));
   }
 
@@ -917,6 +1677,37 @@ fn controls_panel(
         .play_song
         .join(" or ")
     )),
+    text(format!(
+      "Go To Song: {} (type, Enter \
+       selects, Esc cancels)",
+      app
+        .config
+        .control_bindings
+        .go_to_song
+        .join(" or ")
+    )),
+    text(format!(
+      "Global Transpose: {} up, {} \
+       down",
+      app
+        .config
+        .control_bindings
+        .global_transpose_up
+        .join(" or "),
+      app
+        .config
+        .control_bindings
+        .global_transpose_down
+        .join(" or ")
+    )),
+    text(format!(
+      "Sustain Pedal: {} (hold)",
+      app
+        .config
+        .control_bindings
+        .sustain
+        .join(" or ")
+    )),
     text(format!(
       "Print Bindings Hint: {}",
       app
@@ -936,7 +1727,7 @@ fn controls_panel(
   .placeholder("Mode")
   .width(Length::Fill);
 
-  let playback_controls = row![
+  let mut playback_controls = row![
     button(text("Start"))
       .on_press(Message::StartPlayback),
     button(text("Restart")).on_press(
@@ -946,6 +1737,72 @@ fn controls_panel(
       .on_press(Message::StopPlayback),
   ]
   .spacing(6);
+  match app.playback.as_ref() {
+    | Some(playback)
+      if playback.paused =>
+    {
+      playback_controls =
+        playback_controls.push(
+          button(text("Resume"))
+            .on_press(
+              Message::ResumePlayback
+            )
+        );
+    }
+    | Some(playback)
+      if playback.mode
+        != PlayMode::Tutorial =>
+    {
+      playback_controls =
+        playback_controls.push(
+          button(text("Pause"))
+            .on_press(
+              Message::PausePlayback
+            )
+        );
+    }
+    | _ => {}
+  }
+  if let Some(index) =
+    app.practice_start_index
+  {
+    playback_controls =
+      playback_controls.push(
+        button(text(format!(
+          "From event {} (clear)",
+          index + 1
+        )))
+        .on_press(
+          Message::ClearPracticeStart
+        )
+      );
+  }
+
+  let record_button = button(text(
+    if app.audio.is_recording() {
+      "Stop recording"
+    } else {
+      "Record"
+    }
+  ))
+  .on_press(Message::ToggleRecording);
+
+  let calibrate_button = button(text(
+    if app.latency_calibration.is_some()
+    {
+      "Calibrating: tap on each click"
+    } else {
+      "Calibrate latency"
+    }
+  ))
+  .on_press_maybe(
+    app
+      .latency_calibration
+      .is_none()
+      .then_some(
+      Message::StartLatencyCalibration
+    )
+  );
 
   let mut more_options = column![
     text("More Options").size(22)
@@ -953,6 +1810,32 @@ fn controls_panel(
   .spacing(6)
   .push(mode_picker)
   .push(playback_controls)
+  .push(text(format!(
+    "Playback speed: {:.2}x",
+    app.playback_speed
+  )))
+  .push(
+    slider(
+      MIN_PLAYBACK_SPEED
+        ..=MAX_PLAYBACK_SPEED,
+      app.playback_speed,
+      Message::PlaybackSpeedChanged
+    )
+    .step(0.05_f32)
+  )
+  .push(
+    row![
+      record_button,
+      calibrate_button,
+      text(format!(
+        "Input offset {} ms, visual \
+         offset {} ms",
+        app.config.audio.input_offset_ms,
+        app.config.audio.visual_offset_ms
+      )),
+    ]
+    .spacing(8)
+  )
   .push(
     toggler(
       app
@@ -1016,6 +1899,34 @@ fn controls_panel(
     .on_toggle(
       Message::AutoScrollSongLaneFollowPlaybackChanged
     )
+  )
+  .push(
+    toggler(app.song_keys_only)
+      .label(
+        "Show only the selected \
+         song's keys"
+      )
+      .on_toggle(
+        Message::SongKeysOnlyChanged
+      )
+  )
+  .push(
+    pick_list(
+      PracticeHand::ALL,
+      Some(app.practice_hand),
+      Message::PracticeHandSelected
+    )
+    .placeholder("Practice hand")
+    .width(Length::Fill)
+  )
+  .push(
+    button(text(
+      "Reset bindings + gameplay to \
+       defaults"
+    ))
+    .on_press(
+      Message::ResetBindingsAndGameplay
+    )
   );
 
   if app.play_mode == PlayMode::Tutorial
@@ -1050,11 +1961,123 @@ fn controls_panel(
       );
   }
 
+  if app.play_mode == PlayMode::Autoplay
+  {
+    more_options = more_options
+      .push(text(format!(
+        "Left hand volume: {:.2}",
+        app.left_hand_volume
+      )))
+      .push(
+        slider(
+          0.0..=1.5,
+          app.left_hand_volume,
+          Message::LeftHandVolumeChanged
+        )
+        .step(0.01_f32)
+      )
+      .push(text(format!(
+        "Right hand volume: {:.2}",
+        app.right_hand_volume
+      )))
+      .push(
+        slider(
+          0.0..=1.5,
+          app.right_hand_volume,
+          Message::RightHandVolumeChanged
+        )
+        .step(0.01_f32)
+      );
+  }
+
+  let mut keymap_reference = column![
+    text("Keymap Reference").size(22),
+    toggler(app.show_keymap_reference)
+      .label(
+        "Show which keys are bound"
+      )
+      .on_toggle(
+        Message::ShowKeymapReferenceChanged
+      ),
+    button(text("Export keymap"))
+      .on_press(Message::ExportKeymap),
+  ]
+  .spacing(4);
+  if app.show_keymap_reference {
+    for keys in app.keymap_reference() {
+      let mut key_row =
+        row!().spacing(2);
+      for (key, status) in keys {
+        let detail = match status {
+          | KeymapStatus::Note(
+            note
+          ) => {
+            midi_note_name(
+              note,
+              app
+                .config
+                .ui
+                .octave_convention
+            )
+          }
+          | KeymapStatus::Control => {
+            "ctl".to_string()
+          }
+          | KeymapStatus::Unbound => {
+            "-".to_string()
+          }
+        };
+        key_row = key_row.push(
+          container(
+            column![
+              text(key).size(12),
+              text(detail).size(9),
+            ]
+            .align_x(iced::Center)
+          )
+          .width(30)
+          .padding(2)
+          .style(move |_| {
+            keymap_cell_style(status)
+          })
+        );
+      }
+      keymap_reference =
+        keymap_reference.push(key_row);
+    }
+  }
+
+  let mut test_tone_rows = column![
+    text("Instrument Test Tones")
+      .size(22)
+  ]
+  .spacing(4);
+  for profile in &app.instrument_options
+  {
+    test_tone_rows = test_tone_rows
+      .push(
+        row![
+          text(profile.clone())
+            .width(Length::Fill),
+          button(text("Play C4"))
+            .on_press(
+            Message::AuditionInstrument(
+              profile.clone()
+            )
+          ),
+        ]
+        .spacing(6)
+        .align_y(iced::Center)
+      );
+  }
+
   container(
     scrollable(
       column![
         controls,
         more_options,
+        keymap_reference,
+        test_tone_rows,
         binding_rows,
         activity_rows
       ]
@@ -1073,28 +2096,35 @@ fn controls_panel(
 fn piano_panel(
   app: &PianoApp
 ) -> Element<'_, Message> {
-  let active_line = if app
-    .held_notes
-    .is_empty()
-  {
-    "(none)".to_string()
-  } else {
-    let mut active = app
-      .held_notes
-      .iter()
-      .copied()
-      .collect::<Vec<_>>();
-    active.sort_unstable();
+  let active_line =
+    if app.held_notes.is_empty() {
+      "(none)".to_string()
+    } else {
+      let mut active = app
+        .held_notes
+        .keys()
+        .copied()
+        .collect::<Vec<_>>();
+      active.sort_unstable();
 
-    active
-      .iter()
-      .map(|note| midi_note_name(*note))
-      .collect::<Vec<_>>()
-      .join(", ")
-  };
+      active
+        .iter()
+        .map(|note| {
+          midi_note_name(
+            *note,
+            app
+              .config
+              .ui
+              .octave_convention
+          )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+    };
 
   let playback_status =
     app.playback_status_line();
+  let now_next = app.now_next_readout();
 
   let header = row![
     column![
@@ -1109,7 +2139,11 @@ fn piano_panel(
         "Held notes: {active_line}"
       )),
       text(playback_status),
+      text(now_next).size(18),
     ]
+    .push(app.sustain_down.then(|| {
+      text("Sustain").size(14)
+    }))
     .spacing(4)
     .width(Length::FillPortion(4)),
     column![
@@ -1135,6 +2169,21 @@ fn piano_panel(
       )
       .step(0.01)
       .height(22),
+      text("Scale Assist (free play)"),
+      row![
+        pick_list(
+          ScaleRoot::ALL,
+          Some(app.scale_root),
+          Message::ScaleRootSelected
+        ),
+        pick_list(
+          ScaleKind::ALL,
+          Some(app.scale_kind),
+          Message::ScaleKindSelected
+        )
+        .width(Length::Fill),
+      ]
+      .spacing(6),
     ]
     .spacing(4)
     .width(Length::FillPortion(3)),
@@ -1208,6 +2257,17 @@ fn song_timeline_panel(
     .song_lane_tile_height_px
     .clamp(20.0, 140.0);
 
+  let beats_per_bar = f32::from(
+    app.selected_beats_per_bar()
+  );
+  let show_beat_grid =
+    app.config.gameplay.show_beat_grid;
+  let scrub_enabled = app
+    .config
+    .gameplay
+    .timeline_drag_scrub
+    && app.playback.is_some();
+
   let mut lines =
     Vec::<Vec<(usize, usize)>>::new();
   let mut current_line =
@@ -1362,16 +2422,67 @@ fn song_timeline_panel(
     {
       let event =
         &prepared.events[*event_index];
-      let notes = event
+
+      if show_beat_grid {
+        let previous_beat = event_index
+          .checked_sub(1)
+          .and_then(|index| {
+            prepared.events.get(index)
+          })
+          .map_or(-1.0, |previous| {
+            previous.at_beats.floor()
+          });
+        for beat in beats_crossed(
+          previous_beat,
+          event.at_beats.floor()
+        ) {
+          row_view = row_view.push(
+            beat_grid_marker(
+              beat % beats_per_bar
+                == 0.0,
+              tile_height
+            )
+          );
+        }
+      }
+
+      let labels = event
+        .notes
+        .iter()
+        .enumerate()
+        .map(|(index, note)| {
+          let label = app
+            .binding_label_for_song_note(
+              *note
+            );
+          match event
+            .fingering
+            .get(index)
+          {
+            | Some(finger) => {
+              format!("{label}({finger})")
+            }
+            | None => label
+          }
+        })
+        .collect::<Vec<_>>();
+      let names = event
         .notes
         .iter()
         .map(|note| {
-          app.binding_label_for_song_note(
-            *note
+          midi_note_name(
+            app
+              .song_input_note(*note)
+              .unwrap_or(*note),
+            app
+              .config
+              .ui
+              .octave_convention
           )
         })
-        .collect::<Vec<_>>()
-        .join(" ");
+        .collect::<Vec<_>>();
+      let stacked = labels.len()
+        >= STACKED_CHIP_MIN_NOTES;
 
       let is_current = app
         .playback
@@ -1397,35 +2508,99 @@ fn song_timeline_panel(
       let is_past = event.at_seconds
         + event.duration_seconds
         < cursor;
+      let is_practice_start =
+        app.playback.is_none()
+          && app.practice_start_index
+            == Some(*event_index);
       let tile_style =
         timeline_tile_style(
-          is_current, is_past
+          is_current
+            || is_practice_start,
+          is_past
         );
 
-      row_view = row_view.push(
-        container(text(notes).size(18))
-          .width(
-            unit_width
-              * (*event_units as f32)
+      let (chip, chip_height) =
+        if stacked {
+          let lines = labels
+            .iter()
+            .zip(&names)
+            .fold(
+              column!(),
+              |lines, (label, name)| {
+                lines.push(
+                  text(format!(
+                    "{label} {name}"
+                  ))
+                  .size(11)
+                )
+              }
+            );
+          let height = tile_height.max(
+            STACKED_CHIP_LINE_HEIGHT
+              * labels.len() as f32
+              + 8.0
+          );
+          (lines, height)
+        } else {
+          (
+            column![
+              text(labels.join(" "))
+                .size(16),
+              text(names.join(" "))
+                .size(10),
+            ],
+            tile_height
           )
-          .height(tile_height)
-          .padding([4, 6])
-          .center_y(tile_height)
-          .style(move |_| tile_style)
-      );
-    }
-
-    rows = rows.push(row_view);
-  }
-
-  let roll = scrollable(rows)
-    .height(220)
-    .width(Length::Fill);
+        };
 
-  container(
-    column![
-      text(
-        "Song Keys + Timing (wrapped \
+      let tile = container(chip)
+        .width(
+          unit_width
+            * (*event_units as f32)
+        )
+        .height(chip_height)
+        .padding([4, 6])
+        .center_y(chip_height)
+        .style(move |_| tile_style);
+      if scrub_enabled {
+        row_view = row_view.push(
+          mouse_area(tile)
+            .on_press(
+              Message::TimelineScrubStarted(
+                event.at_seconds
+              )
+            )
+            .on_enter(
+              Message::TimelineScrubMoved(
+                event.at_seconds
+              )
+            )
+            .on_release(
+              Message::TimelineScrubEnded
+            )
+        );
+      } else {
+        row_view = row_view.push(
+          mouse_area(tile).on_press(
+            Message::SetPracticeStart(
+              *event_index
+            )
+          )
+        );
+      }
+    }
+
+    rows = rows.push(row_view);
+  }
+
+  let roll = scrollable(rows)
+    .height(220)
+    .width(Length::Fill);
+
+  container(
+    column![
+      text(
+        "Song Keys + Timing (wrapped \
          lane)"
       )
       .size(16),
@@ -1516,7 +2691,28 @@ fn piano_keyboard(
   .height(WHITE_KEY_HEIGHT + 24.0)
   .width(Length::Fill);
 
-  container(scroller)
+  let transpose =
+    app.prepared_transpose_semitones;
+  let keyboard = if transpose == 0 {
+    column![scroller]
+  } else {
+    column![
+      container(
+        text(format!(
+          "Transposed {transpose:+} st"
+        ))
+        .size(12)
+      )
+      .padding([2, 8])
+      .style(|_| {
+        transpose_badge_style()
+      }),
+      scroller,
+    ]
+    .spacing(4)
+  };
+
+  container(keyboard)
     .padding(6)
     .style(container::bordered_box)
     .into()
@@ -1526,35 +2722,62 @@ fn white_key_widget<'a>(
   app: &PianoApp,
   note: u8
 ) -> Element<'a, Message> {
-  let active =
-    app.is_note_highlighted(note);
+  let active = app.note_highlight(note);
   let guided =
-    app.guided_notes().contains(&note);
+    app.guided_notes().contains(&note)
+      || app.in_scale_assist(note);
 
   let label =
     app.primary_binding_label(note);
+  let original = guided
+    .then(|| {
+      app.original_song_note_label(note)
+    })
+    .flatten();
+  let finger = app.guided_finger(note);
+
+  let style = white_key_style(
+    active,
+    guided,
+    app.lead_in_intensity(note),
+    app.is_dimmed_key(note),
+    app.config.ui.white_key_label_rgb()
+  );
 
-  let style =
-    white_key_style(active, guided);
+  let mut key_column = column![
+    space().height(Length::Fill),
+    text(label).size(18),
+    text(midi_note_name(
+      note,
+      app.config.ui.octave_convention
+    ))
+    .size(12),
+  ]
+  .spacing(4);
+  if let Some(original) = original {
+    key_column = key_column
+      .push(text(original).size(10));
+  }
+  if let Some(finger) = finger {
+    key_column = key_column.push(
+      text(format!("finger {finger}"))
+        .size(10)
+    );
+  }
 
   mouse_area(
-    container(
-      column![
-        space().height(Length::Fill),
-        text(label).size(18),
-        text(midi_note_name(note))
-          .size(12),
-      ]
-      .spacing(4)
-    )
-    .width(WHITE_KEY_WIDTH)
-    .height(WHITE_KEY_HEIGHT)
-    .padding([8, 6])
-    .style(move |_| style)
+    container(key_column)
+      .width(WHITE_KEY_WIDTH)
+      .height(WHITE_KEY_HEIGHT)
+      .padding([8, 6])
+      .style(move |_| style)
   )
   .on_press(Message::PlayNoteFromClick(
     note
   ))
+  .on_release(
+    Message::ReleaseClickedNote(note)
+  )
   .into()
 }
 
@@ -1562,36 +2785,417 @@ fn black_key_widget<'a>(
   app: &PianoApp,
   note: u8
 ) -> Element<'a, Message> {
-  let active =
-    app.is_note_highlighted(note);
+  let active = app.note_highlight(note);
   let guided =
-    app.guided_notes().contains(&note);
+    app.guided_notes().contains(&note)
+      || app.in_scale_assist(note);
 
   let label =
     app.primary_binding_label(note);
-  let style =
-    black_key_style(active, guided);
+  let original = guided
+    .then(|| {
+      app.original_song_note_label(note)
+    })
+    .flatten();
+  let finger = app.guided_finger(note);
+  let style = black_key_style(
+    active,
+    guided,
+    app.lead_in_intensity(note),
+    app.is_dimmed_key(note),
+    app.config.ui.black_key_label_rgb()
+  );
+
+  let mut key_column = column![
+    text(label).size(16),
+    text(midi_note_name(
+      note,
+      app.config.ui.octave_convention
+    ))
+    .size(11),
+  ]
+  .spacing(2);
+  if let Some(original) = original {
+    key_column = key_column
+      .push(text(original).size(10));
+  }
+  if let Some(finger) = finger {
+    key_column = key_column.push(
+      text(format!("f{finger}"))
+        .size(10)
+    );
+  }
 
   mouse_area(
-    container(
-      column![
-        text(label).size(16),
-        text(midi_note_name(note))
-          .size(11),
-      ]
-      .spacing(2)
-    )
-    .width(BLACK_KEY_WIDTH)
-    .height(BLACK_KEY_HEIGHT)
-    .padding([8, 4])
-    .style(move |_| style)
+    container(key_column)
+      .width(BLACK_KEY_WIDTH)
+      .height(BLACK_KEY_HEIGHT)
+      .padding([8, 4])
+      .style(move |_| style)
   )
   .on_press(Message::PlayNoteFromClick(
     note
   ))
+  .on_release(
+    Message::ReleaseClickedNote(note)
+  )
+  .into()
+}
+
+fn empty_library_hint(
+  app: &PianoApp
+) -> Element<'_, Message> {
+  let library =
+    &app.config.song_library;
+  column![
+    text("No songs found").size(16),
+    text(format!(
+      "Song files (.toml): {}",
+      display_library_dir(
+        &library.directory
+      )
+    ))
+    .size(12),
+    text(format!(
+      "MIDI files (.mid, .midi): {}",
+      display_library_dir(
+        &library.midi_directory
+      )
+    ))
+    .size(12),
+    text(
+      "Add song TOML or MIDI files to \
+       either directory, then reload \
+       the library."
+    )
+    .size(12),
+    button(text("Reload library"))
+      .on_press(
+        Message::ReloadSongLibrary
+      ),
+  ]
+  .spacing(6)
   .into()
 }
 
+fn suggested_profile_for_gm<'a>(
+  gm_name: &str,
+  profiles: &'a [String]
+) -> Option<&'a String> {
+  let gm_name = gm_name.to_lowercase();
+  profiles.iter().find(|profile| {
+    profile
+      .to_lowercase()
+      .split('_')
+      .all(|word| {
+        gm_name.contains(word)
+      })
+  })
+}
+
+fn closest_timer_match(
+  expected_notes: &[ExpectedNote],
+  cursor_seconds: f32,
+  is_candidate: impl Fn(
+    usize,
+    &ExpectedNote
+  ) -> bool
+) -> Option<(usize, f32)> {
+  let mut best_match: Option<(
+    usize,
+    f32
+  )> = None;
+
+  for (index, expected) in
+    expected_notes.iter().enumerate()
+  {
+    if !is_candidate(index, expected) {
+      continue;
+    }
+
+    let delta = (expected.at_seconds
+      - cursor_seconds)
+      .abs();
+    if delta > TIMER_WINDOW_SECONDS {
+      continue;
+    }
+
+    match best_match {
+      | Some((_, best_delta))
+        if delta >= best_delta => {}
+      | _ => {
+        best_match =
+          Some((index, delta));
+      }
+    }
+  }
+
+  best_match
+}
+
+fn keymap_cheat_sheet(
+  config: &AppConfig,
+  rows: &[Vec<(&str, KeymapStatus)>],
+  note_to_chords: &BTreeMap<
+    u8,
+    Vec<String>
+  >
+) -> String {
+  let mut sheet = format!(
+    "# Symfose keymap ({})\n",
+    config.keyboard.layout
+  );
+
+  for (row, keys) in
+    KeyRow::ALL.iter().zip(rows)
+  {
+    if keys.is_empty() {
+      continue;
+    }
+
+    let title = match row {
+      | KeyRow::Number => "Number row",
+      | KeyRow::Top => "Top row",
+      | KeyRow::Home => "Home row",
+      | KeyRow::Bottom => "Bottom row"
+    };
+    sheet.push_str(&format!(
+      "\n## {title}\n\n| Key | Note \
+       |\n| --- | --- |\n"
+    ));
+    for (key, status) in keys {
+      let detail = match status {
+        | KeymapStatus::Note(note) => {
+          format!(
+            "{} ({note})",
+            midi_note_name(
+              *note,
+              config
+                .ui
+                .octave_convention
+            )
+          )
+        }
+        | KeymapStatus::Control => {
+          "control".to_string()
+        }
+        | KeymapStatus::Unbound => {
+          "-".to_string()
+        }
+      };
+      sheet.push_str(&format!(
+        "| `{key}` | {detail} |\n"
+      ));
+    }
+  }
+
+  sheet.push_str(
+    "\n## All note bindings\n\n"
+  );
+  for (note, chords) in note_to_chords {
+    sheet.push_str(&format!(
+      "- {} ({note}): {}\n",
+      midi_note_name(
+        *note,
+        config.ui.octave_convention
+      ),
+      chords.join(", ")
+    ));
+  }
+
+  let controls =
+    &config.control_bindings;
+  sheet.push_str("\n## Controls\n\n");
+  for (label, chords) in [
+    ("Quit", &controls.quit),
+    ("Next song", &controls.list_songs),
+    (
+      "Print bindings",
+      &controls.print_bindings
+    ),
+    (
+      "Start song mode",
+      &controls.play_song
+    ),
+    (
+      "Go to song",
+      &controls.go_to_song
+    ),
+    (
+      "Global transpose up",
+      &controls.global_transpose_up
+    ),
+    (
+      "Global transpose down",
+      &controls.global_transpose_down
+    ),
+    (
+      "Sustain pedal",
+      &controls.sustain
+    )
+  ] {
+    sheet.push_str(&format!(
+      "- {label}: {}\n",
+      chords.join(" or ")
+    ));
+  }
+
+  sheet
+}
+
+fn open_sync_socket(
+  config: &SyncConfig
+) -> Option<UdpSocket> {
+  if !config.enabled {
+    return None;
+  }
+
+  let socket =
+    UdpSocket::bind("0.0.0.0:0")
+      .and_then(|socket| {
+        socket.set_nonblocking(true)?;
+        socket.connect(
+          config
+            .target_address
+            .as_str()
+        )?;
+        Ok(socket)
+      });
+
+  match socket {
+    | Ok(socket) => {
+      info!(
+        target = %config.target_address,
+        "sync output enabled"
+      );
+      Some(socket)
+    }
+    | Err(error) => {
+      warn!(
+        target = %config.target_address,
+        %error,
+        "sync output unavailable"
+      );
+      None
+    }
+  }
+}
+
+fn open_midi_clock_input(
+  config: &MidiSyncConfig
+) -> Option<MidiClockInput> {
+  if !config.enabled {
+    return None;
+  }
+
+  match open_midi_clock(
+    &config.port_name
+  ) {
+    | Ok(clock) => {
+      info!(
+        port = %clock.port_name,
+        "MIDI clock sync enabled"
+      );
+      Some(clock)
+    }
+    | Err(error) => {
+      warn!(
+        %error,
+        "MIDI clock sync unavailable"
+      );
+      None
+    }
+  }
+}
+
+fn open_sync_input(
+  config: &SyncConfig
+) -> Option<UdpSocket> {
+  if config.listen_address.is_empty() {
+    return None;
+  }
+
+  let socket = UdpSocket::bind(
+    config.listen_address.as_str()
+  )
+  .and_then(|socket| {
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+  });
+
+  match socket {
+    | Ok(socket) => {
+      info!(
+        address = %config.listen_address,
+        "sync input listening"
+      );
+      Some(socket)
+    }
+    | Err(error) => {
+      warn!(
+        address = %config.listen_address,
+        %error,
+        "sync input unavailable"
+      );
+      None
+    }
+  }
+}
+
+fn parse_sync_transpose(
+  message: &str
+) -> Option<i8> {
+  let message = message.trim();
+  let message = message
+    .strip_prefix("symfose ")
+    .unwrap_or(message);
+  let semitones = message
+    .strip_prefix("transpose=")?
+    .trim()
+    .parse::<i8>()
+    .ok()?;
+
+  (-24..=24)
+    .contains(&semitones)
+    .then_some(semitones)
+}
+
+fn sync_message(
+  cursor_seconds: f32,
+  beat: f32,
+  active_notes: &BTreeSet<u8>
+) -> String {
+  let beat =
+    beat.max(0.0).floor() as u64;
+  let notes = active_notes
+    .iter()
+    .map(u8::to_string)
+    .collect::<Vec<_>>()
+    .join(",");
+
+  format!(
+    "symfose cursor={cursor_seconds:.\
+     3} beat={beat} notes={notes}"
+  )
+}
+
+fn display_library_dir(
+  directory: &str
+) -> String {
+  let path = Path::new(directory);
+  std::fs::canonicalize(path)
+    .or_else(|_| {
+      env::current_dir()
+        .map(|cwd| cwd.join(path))
+    })
+    .map(|resolved| {
+      resolved.display().to_string()
+    })
+    .unwrap_or_else(|_| {
+      directory.to_string()
+    })
+}
+
 fn songs_panel(
   app: &PianoApp
 ) -> Element<'_, Message> {
@@ -1614,8 +3218,28 @@ fn songs_panel(
   ]
   .spacing(6);
 
+  let search_title =
+    if app.song_jump.is_some() {
+      "Song Search (go to song: Enter \
+       selects, Esc cancels)"
+    } else {
+      "Song Search"
+    };
+  let page_size =
+    app.config.ui.song_page_size.max(1);
+  let page_count = filtered_indices
+    .len()
+    .div_ceil(page_size)
+    .max(1);
+  let page =
+    app.song_page.min(page_count - 1);
+  let page_start = page * page_size;
+  let page_end = (page_start
+    + page_size)
+    .min(filtered_indices.len());
+
   let mut songs_column = column![
-    text("Song Search").size(18),
+    text(search_title).size(18),
     search_bar,
     text(format!(
       "Results: {} / {}",
@@ -1625,13 +3249,51 @@ fn songs_panel(
   ]
   .spacing(6);
 
-  if filtered_indices.is_empty() {
+  if page_count > 1 {
+    songs_column = songs_column.push(
+      row![
+        button(text("Prev"))
+          .on_press_maybe(
+            page.checked_sub(1).map(
+              Message::SongPageChanged
+            )
+          ),
+        text(format!(
+          "Page {} / {page_count} \
+           ({}-{})",
+          page + 1,
+          page_start + 1,
+          page_end
+        )),
+        button(text("Next"))
+          .on_press_maybe(
+          (page + 1 < page_count).then(
+            || {
+              Message::SongPageChanged(
+                page + 1
+              )
+            }
+          )
+        ),
+      ]
+      .spacing(6)
+      .align_y(iced::Center)
+    );
+  }
+
+  if app.songs.is_empty() {
+    songs_column = songs_column
+      .push(empty_library_hint(app));
+  } else if filtered_indices.is_empty()
+  {
     songs_column =
       songs_column.push(text(
         "No songs matched your search."
       ));
   } else {
-    for index in filtered_indices {
+    for &index in &filtered_indices
+      [page_start..page_end]
+    {
       let loaded = &app.songs[index];
       let selected = app.selected_song
         == Some(index);
@@ -1683,6 +3345,11 @@ fn songs_panel(
                 index
               )
             ),
+          song_thumbnail_view(
+            app.song_thumbnails.get(
+              &loaded.song.meta.id
+            )
+          ),
           container(tag_column)
             .align_y(iced::Center)
             .width(Length::Shrink),
@@ -1769,6 +3436,36 @@ fn selected_song_details(
       "Tempo: {:.0} BPM",
       loaded.song.meta.tempo_bpm
     )),
+    text(match loaded
+      .song
+      .meta
+      .gm_program_name()
+    {
+      | Some(name) => {
+        match suggested_profile_for_gm(
+          name,
+          &app.instrument_options
+        ) {
+          | Some(profile) => {
+            format!(
+              "Intended instrument: \
+               {name} (suggested \
+               profile: {profile})"
+            )
+          }
+          | None => {
+            format!(
+              "Intended instrument: \
+               {name}"
+            )
+          }
+        }
+      }
+      | None => {
+        "Intended instrument: -"
+          .to_string()
+      }
+    }),
     text(format!(
       "Events: {}",
       loaded.song.events.len()
@@ -1781,6 +3478,10 @@ fn selected_song_details(
       "Duration (beats): \
        {duration_beats:.2}"
     )),
+    text(format!(
+      "Duration (bars): {:.2}",
+      loaded.duration_bars()
+    )),
     text(format!(
       "File: {}",
       loaded.path.display()
@@ -1802,6 +3503,18 @@ fn selected_song_details(
   ]
   .spacing(4);
 
+  if let Some(prepared) = prepared
+    && prepared.trim_offset_seconds
+      > 0.0
+  {
+    info_column =
+      info_column.push(text(format!(
+        "Leading silence trimmed: \
+         {:.2}s",
+        prepared.trim_offset_seconds
+      )));
+  }
+
   if app.warn_on_missing_song_notes {
     if app.missing_song_notes.is_empty()
     {
@@ -1818,21 +3531,79 @@ fn selected_song_details(
         .map(|note| {
           format!(
             "{} ({})",
-            midi_note_name(*note),
+            midi_note_name(
+              *note,
+              app
+                .config
+                .ui
+                .octave_convention
+            ),
             note
           )
         })
         .collect::<Vec<_>>()
         .join(", ");
-      info_column = info_column.push(
-        text(format!(
+      info_column = info_column
+        .push(text(format!(
           "Missing key mappings: \
            {list}"
+        )))
+        .push(
+          button(text(
+            "Map missing notes"
+          ))
+          .on_press(
+            Message::MapMissingNotes
+          )
+        );
+    }
+
+    if !app
+      .out_of_range_song_notes
+      .is_empty()
+    {
+      let list = app
+        .out_of_range_song_notes
+        .iter()
+        .map(|note| {
+          format!(
+            "{} ({})",
+            midi_note_name(
+              *note,
+              app
+                .config
+                .ui
+                .octave_convention
+            ),
+            note
+          )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+      info_column = info_column.push(
+        text(format!(
+          "Outside {} range: {list}",
+          app.selected_instrument
         ))
       );
     }
   }
 
+  if app.play_mode == PlayMode::Timer
+    && app
+      .config
+      .gameplay
+      .strict_perfect
+  {
+    info_column = info_column.push(
+      text(
+        "Strict timing: only perfect \
+         hits count"
+      )
+      .size(14)
+    );
+  }
+
   if let Some(score) = app
     .playback
     .as_ref()
@@ -1858,25 +3629,93 @@ fn selected_song_details(
       info_column.push(text(format!(
         "Last timer result: {:.1}% \
          (perfect {} good {} wrong {} \
-         missed {})",
+         missed {}{})",
         score.accuracy_percent(),
         score.perfect_hits,
         score.good_hits,
         score.wrong_notes,
-        score.missed_notes
+        score.missed_notes,
+        score.octave_summary()
       )));
+    if !score.hit_offsets.is_empty() {
+      info_column = info_column.push(
+        timing_histogram(
+          &score.hit_offsets
+        )
+      );
+    }
   }
 
-  info_column.into()
-}
+  if let Some(window) =
+    &app.worst_section
+  {
+    info_column = info_column.push(
+      row![
+        text(format!(
+          "Worst section: bars {}-{} \
+           ({} missed)",
+          window.first_bar,
+          window.last_bar,
+          window.missed_notes
+        )),
+        button(text(
+          "Drill worst section"
+        ))
+        .on_press(
+          Message::DrillWorstSection
+        ),
+      ]
+      .spacing(8)
+      .align_y(iced::Center)
+    );
+  }
+
+  if app.playback.is_none()
+    && !app.last_performance.is_empty()
+  {
+    info_column = info_column.push(
+      button(text(format!(
+        "Replay my last take ({} \
+         notes)",
+        app.last_performance.len()
+      )))
+      .on_press(
+        Message::ReplayPerformance
+      )
+    );
+  }
+
+  info_column = info_column.push(
+    row![
+      button(text("Export stems"))
+        .on_press(
+          Message::ExportSongStems
+        ),
+      button(text("Export MIDI"))
+        .on_press_maybe(
+          app
+            .prepared_song
+            .is_some()
+            .then_some(
+              Message::ExportSongMidi
+            )
+        ),
+    ]
+    .spacing(6)
+  );
+
+  info_column.into()
+}
 
 fn subscription(
-  _app: &PianoApp
+  app: &PianoApp
 ) -> Subscription<Message> {
   Subscription::batch(vec![
     event::listen_with(map_event),
-    time::every(TICK_RATE)
-      .map(Message::Tick),
+    time::every(Duration::from_millis(
+      app.config.app.tick_rate_ms
+    ))
+    .map(Message::Tick),
   ])
 }
 
@@ -1903,6 +3742,164 @@ fn app_theme(
 }
 
 impl PianoApp {
+  fn new(
+    config: AppConfig,
+    config_path: PathBuf,
+    bindings: RuntimeBindings,
+    mut songs: Vec<LoadedSong>,
+    library_stats: LibraryLoadStats,
+    audio: AudioEngine
+  ) -> Self {
+    let instrument_options =
+      audio.available_profiles();
+    let selected_instrument = audio
+      .active_profile_name()
+      .to_string();
+
+    let song_thumbnails =
+      build_song_thumbnails(&songs);
+    let sync_socket =
+      open_sync_socket(&config.sync);
+    let sync_input =
+      open_sync_input(&config.sync);
+    let midi_clock =
+      open_midi_clock_input(
+        &config.midi_sync
+      );
+    let left_hand_volume =
+      config.gameplay.left_hand_volume;
+    let right_hand_volume =
+      config.gameplay.right_hand_volume;
+    let playback_speed =
+      config.gameplay.playback_speed;
+    let tutorial_options =
+      TutorialOptions {
+        wrong_note_grace:
+          Duration::from_millis(
+            config
+              .gameplay
+              .tutorial_wrong_note_grace_ms
+          ),
+        only_advance_on_correct_note:
+          config
+            .gameplay
+            .tutorial_only_advance_on_correct_note,
+        play_bad_notes_out_loud: config
+          .gameplay
+          .tutorial_play_bad_notes_out_loud
+      };
+
+    let selected_song =
+      parse_initial_song(
+        &mut songs,
+        &config.song_library
+      );
+
+    let mut app = Self {
+      startup_notice: format!(
+        "Loaded {} song(s) from \
+         sources: {}, {} (cache: {})",
+        songs.len(),
+        config.song_library.directory,
+        config
+          .song_library
+          .midi_directory,
+        config
+          .song_library
+          .cache_directory
+      ),
+      selected_song,
+      prepared_song: None,
+      volume: audio.master_volume(),
+      song_search_query: String::new(),
+      song_jump: None,
+      instrument_options,
+      selected_instrument,
+      transpose_song_to_fit_bindings:
+        config
+          .gameplay
+          .transpose_song_to_fit_bindings,
+      warn_on_missing_song_notes: config
+        .gameplay
+        .warn_on_missing_song_notes,
+      binding_rebuild_pending: false,
+      optimize_bindings_for_song: config
+        .gameplay
+        .optimize_bindings_for_song,
+      auto_jump_pressed_key_into_view:
+        config
+          .gameplay
+          .auto_jump_pressed_key_into_view,
+      auto_scroll_song_lane_follow_playback:
+        config
+          .gameplay
+          .auto_scroll_song_lane_follow_playback,
+      song_keys: BTreeSet::new(),
+      song_keys_only: config
+        .gameplay
+        .song_keys_only,
+      practice_hand: config
+        .gameplay
+        .practice_hand,
+      keyboard_focus_note: None,
+      prepared_transpose_semitones: 0,
+      global_transpose: config
+        .gameplay
+        .global_transpose_semitones,
+      missing_song_notes: Vec::new(),
+      out_of_range_song_notes: Vec::new(),
+      supplemental_bindings: BTreeMap::new(),
+      show_keymap_reference: false,
+      sustain_down: false,
+      song_page: 0,
+      scale_root: ScaleRoot(0),
+      scale_kind: ScaleKind::Off,
+      unmapped_key_log: HashMap::new(),
+      config,
+      config_path,
+      settings_dirty: false,
+      last_auto_save: Instant::now(),
+      bindings,
+      songs,
+      song_thumbnails,
+      audio,
+      held_notes: HashMap::new(),
+      held_keys: HashMap::new(),
+      clicked_note: None,
+      flashed_notes: HashMap::new(),
+      activity: vec![
+        "Press mapped keys to play. \
+         Choose a song mode and press \
+         Start."
+          .to_string(),
+      ],
+      play_mode: PlayMode::Timer,
+      tutorial_options,
+      playback: None,
+      auto_advance_at: None,
+      pending_quit_at: None,
+      practice_session: None,
+      latency_calibration: None,
+      last_timer_score: None,
+      last_performance: Vec::new(),
+      timeline_scrubbing: false,
+      practice_start_index: None,
+      sync_socket,
+      sync_input,
+      midi_clock,
+      worst_section: None,
+      left_hand_volume,
+      right_hand_volume,
+      playback_speed,
+      drill_restore_speed: None
+    };
+    app.report_library_stats(
+      library_stats
+    );
+    app.rebuild_song_context();
+    app
+  }
+
   fn filtered_song_indices(
     &self
   ) -> Vec<usize> {
@@ -1952,6 +3949,8 @@ impl PianoApp {
   }
 
   fn rebuild_song_context(&mut self) {
+    self.binding_rebuild_pending =
+      false;
     let mut bindings =
       match compile_runtime_bindings(
         &self.config
@@ -1966,39 +3965,45 @@ impl PianoApp {
         }
       };
 
+    let selected =
+      self.selected_song.and_then(
+        |index| self.songs.get(index)
+      );
     let mut forced_transpose = 0i8;
-    if let Some(index) =
-      self.selected_song
+    if let Some(song) = selected
+      && self
+        .transpose_song_to_fit_bindings
     {
-      if let Some(song) =
-        self.songs.get(index)
-      {
-        if self
-          .transpose_song_to_fit_bindings
-        {
-          let available_notes = bindings
-            .note_to_chords
-            .keys()
-            .copied()
-            .collect::<HashSet<_>>();
-          forced_transpose =
-            choose_transpose_for_fit(
-              &song.song,
-              &available_notes
-            );
-        }
+      let available_notes = bindings
+        .note_to_chords
+        .keys()
+        .copied()
+        .collect::<HashSet<_>>();
+      forced_transpose =
+        choose_transpose_for_fit(
+          &song.song,
+          &available_notes,
+          self
+            .config
+            .gameplay
+            .transpose_strategy
+        );
+    }
 
-        if self
-          .optimize_bindings_for_song
-        {
-          apply_song_ergonomic_bindings(
-            &mut bindings,
-            &song.song,
-            self.config.keyboard.layout,
-            forced_transpose
-          );
-        }
-      }
+    extend_runtime_bindings(
+      &mut bindings,
+      &self.supplemental_bindings
+    );
+
+    if let Some(song) = selected
+      && self.optimize_bindings_for_song
+    {
+      apply_song_ergonomic_bindings(
+        &mut bindings,
+        &song.song,
+        self.config.keyboard.layout,
+        forced_transpose
+      );
     }
 
     self.bindings = bindings;
@@ -2013,11 +4018,19 @@ impl PianoApp {
           (None, 0i8, Vec::new()),
           |loaded| {
             prepare_song_for_bindings(
-              &loaded.song,
+              &practice_hand_song(
+                &loaded.song,
+                self.practice_hand,
+                self
+                  .config
+                  .gameplay
+                  .shared_hand_notes
+              ),
               &self.bindings,
               self
                 .transpose_song_to_fit_bindings,
-              Some(forced_transpose)
+              Some(forced_transpose),
+              &self.config.gameplay
             )
           }
         );
@@ -2026,6 +4039,21 @@ impl PianoApp {
     self.prepared_transpose_semitones =
       transpose;
     self.missing_song_notes = missing;
+    self.song_keys = self
+      .prepared_song
+      .as_ref()
+      .map(|prepared| {
+        prepared
+          .expected_notes
+          .iter()
+          .filter_map(|expected| {
+            self.song_input_note(
+              expected.midi_note
+            )
+          })
+          .collect()
+      })
+      .unwrap_or_default();
 
     if self.warn_on_missing_song_notes
       && !self
@@ -2038,6 +4066,324 @@ impl PianoApp {
         self.missing_song_notes.len()
       ));
     }
+    self
+      .refresh_instrument_range_check();
+  }
+
+  fn refresh_instrument_range_check(
+    &mut self
+  ) {
+    let profile = self
+      .config
+      .audio
+      .instrument_profiles
+      .get(&self.selected_instrument);
+    let song =
+      self.selected_song.and_then(
+        |index| self.songs.get(index)
+      );
+
+    let outside = match (profile, song)
+    {
+      | (
+        Some(
+          InstrumentProfile::Soundfont(
+            sf2
+          )
+        ),
+        Some(song)
+      ) if self
+        .config
+        .gameplay
+        .check_instrument_range =>
+      {
+        notes_outside_profile_range(
+          &song.song,
+          sf2,
+          self.global_transpose
+        )
+      }
+      | _ => Vec::new()
+    };
+
+    if outside
+      == self.out_of_range_song_notes
+    {
+      return;
+    }
+    self.out_of_range_song_notes =
+      outside;
+    if self.warn_on_missing_song_notes
+      && !self
+        .out_of_range_song_notes
+        .is_empty()
+    {
+      self.push_activity(format!(
+        "Selected song has {} note(s) \
+         outside the {} range.",
+        self
+          .out_of_range_song_notes
+          .len(),
+        self.selected_instrument
+      ));
+    }
+  }
+
+  fn audition_instrument(
+    &mut self,
+    instrument: &str
+  ) {
+    let previous = self
+      .audio
+      .active_profile_name()
+      .to_string();
+
+    if let Err(error) = self
+      .audio
+      .set_active_profile(instrument)
+    {
+      self.push_activity(format!(
+        "Failed to audition \
+         {instrument}: {error}"
+      ));
+      return;
+    }
+
+    self.audio.play_note(60);
+
+    if let Err(error) = self
+      .audio
+      .set_active_profile(&previous)
+    {
+      self.push_activity(format!(
+        "Failed to restore instrument \
+         {previous}: {error}"
+      ));
+      return;
+    }
+
+    self.push_activity(format!(
+      "Test tone: {instrument} C4"
+    ));
+    info!(instrument, restored = %previous, "instrument test tone played");
+  }
+
+  fn reset_bindings_and_gameplay(
+    &mut self
+  ) {
+    let defaults =
+      &self.config.gameplay;
+    let mut reset = Vec::new();
+
+    if self
+      .transpose_song_to_fit_bindings
+      != defaults
+        .transpose_song_to_fit_bindings
+    {
+      self.transpose_song_to_fit_bindings =
+        defaults
+          .transpose_song_to_fit_bindings;
+      reset.push(
+        "transpose_song_to_fit_bindings"
+      );
+    }
+    if self.warn_on_missing_song_notes
+      != defaults
+        .warn_on_missing_song_notes
+    {
+      self.warn_on_missing_song_notes =
+        defaults
+          .warn_on_missing_song_notes;
+      reset.push(
+        "warn_on_missing_song_notes"
+      );
+    }
+    if self.optimize_bindings_for_song
+      != defaults
+        .optimize_bindings_for_song
+    {
+      self.optimize_bindings_for_song =
+        defaults
+          .optimize_bindings_for_song;
+      reset.push(
+        "optimize_bindings_for_song"
+      );
+    }
+    if self
+      .auto_jump_pressed_key_into_view
+      != defaults
+        .auto_jump_pressed_key_into_view
+    {
+      self.auto_jump_pressed_key_into_view =
+        defaults
+          .auto_jump_pressed_key_into_view;
+      reset.push(
+        "auto_jump_pressed_key_into_view"
+      );
+    }
+    if self
+      .auto_scroll_song_lane_follow_playback
+      != defaults
+        .auto_scroll_song_lane_follow_playback
+    {
+      self
+        .auto_scroll_song_lane_follow_playback =
+        defaults
+          .auto_scroll_song_lane_follow_playback;
+      reset.push(
+        "auto_scroll_song_lane_follow_playback"
+      );
+    }
+
+    if self.song_keys_only
+      != defaults.song_keys_only
+    {
+      self.song_keys_only =
+        defaults.song_keys_only;
+      reset.push("song_keys_only");
+    }
+    if self.practice_hand
+      != defaults.practice_hand
+    {
+      self.practice_hand =
+        defaults.practice_hand;
+      reset.push("practice_hand");
+    }
+    if self.global_transpose
+      != defaults
+        .global_transpose_semitones
+    {
+      self.global_transpose = defaults
+        .global_transpose_semitones;
+      reset.push("global transpose");
+    }
+
+    if !self
+      .supplemental_bindings
+      .is_empty()
+    {
+      self
+        .supplemental_bindings
+        .clear();
+      reset
+        .push("mapped missing notes");
+    }
+
+    self.keyboard_focus_note = None;
+    self.rebuild_song_context();
+
+    if reset.is_empty() {
+      self.push_activity(
+        "Bindings recompiled from \
+         config; gameplay toggles \
+         already at defaults."
+          .to_string()
+      );
+    } else {
+      self.push_activity(format!(
+        "Reset to config defaults: {}",
+        reset.join(", ")
+      ));
+    }
+    info!(reset = ?reset, "bindings and gameplay reset to config defaults");
+  }
+
+  fn map_missing_notes(&mut self) {
+    if self
+      .missing_song_notes
+      .is_empty()
+    {
+      self.push_activity(
+        "Selected song has no missing \
+         note mappings."
+          .to_string()
+      );
+      return;
+    }
+
+    let used_keys = self
+      .bindings
+      .note_bindings
+      .keys()
+      .chain(
+        self.bindings.control_chords()
+      )
+      .map(ToString::to_string)
+      .chain(
+        self
+          .supplemental_bindings
+          .keys()
+          .cloned()
+      )
+      .collect::<HashSet<_>>();
+    let mut free_keys =
+      keyboard_layout_key_priority(
+        self.config.keyboard.layout
+      )
+      .iter()
+      .filter(|key| {
+        !used_keys.contains(**key)
+      });
+
+    let mut mapped = Vec::new();
+    let mut unmapped = Vec::new();
+    for note in
+      self.missing_song_notes.clone()
+    {
+      if let Some(key) =
+        free_keys.next()
+      {
+        self
+          .supplemental_bindings
+          .insert(
+            (*key).to_string(),
+            note
+          );
+        mapped.push(format!(
+          "{key} -> {}",
+          midi_note_name(
+            note,
+            self
+              .config
+              .ui
+              .octave_convention
+          )
+        ));
+      } else {
+        unmapped.push(midi_note_name(
+          note,
+          self
+            .config
+            .ui
+            .octave_convention
+        ));
+      }
+    }
+
+    self.rebuild_song_context();
+
+    let mut line = format!(
+      "Mapped {} missing note(s)",
+      mapped.len()
+    );
+    if !mapped.is_empty() {
+      line.push_str(&format!(
+        ": {}",
+        mapped.join(", ")
+      ));
+    }
+    if !unmapped.is_empty() {
+      line.push_str(&format!(
+        "; out of keys for {}",
+        unmapped.join(", ")
+      ));
+    }
+    self.push_activity(line);
+    info!(
+      mapped = mapped.len(),
+      unmapped = unmapped.len(),
+      "mapped missing song notes"
+    );
   }
 
   fn push_activity(
@@ -2070,13 +4416,19 @@ impl PianoApp {
 
   fn flash_note(
     &mut self,
-    midi_note: u8
+    midi_note: u8,
+    source: FlashSource
   ) {
-    let expires =
-      Instant::now() + FLASH_DURATION;
-    self
-      .flashed_notes
-      .insert(midi_note, expires);
+    let started = Instant::now();
+    let expires = started
+      + FLASH_DURATION
+      + Duration::from_millis(
+        self.config.ui.flash_fade_ms
+      );
+    self.flashed_notes.insert(
+      midi_note,
+      (started, expires, source)
+    );
   }
 
   fn prune_flashes(
@@ -2084,21 +4436,70 @@ impl PianoApp {
     now: Instant
   ) {
     self.flashed_notes.retain(
-      |_, expires| *expires > now
+      |_, (_, expires, _)| {
+        *expires > now
+      }
     );
   }
 
-  fn is_note_highlighted(
+  fn release_held_notes(&mut self) {
+    for sounding in self
+      .held_notes
+      .drain()
+      .filter_map(|(_, sounding)| {
+        sounding
+      })
+    {
+      self.audio.stop_note(sounding);
+    }
+    self.held_keys.clear();
+    self.clicked_note = None;
+  }
+
+  fn release_clicked_note(&mut self) {
+    if let Some(note) =
+      self.clicked_note.take()
+      && let Some(Some(sounding)) =
+        self.held_notes.remove(&note)
+    {
+      self.audio.stop_note(sounding);
+    }
+  }
+
+  fn note_highlight(
     &self,
     note: u8
-  ) -> bool {
-    self.held_notes.contains(&note)
-      || self
-        .flashed_notes
-        .get(&note)
-        .is_some_and(|until| {
-          *until > Instant::now()
-        })
+  ) -> Option<(FlashSource, f32)> {
+    if self
+      .held_notes
+      .contains_key(&note)
+    {
+      return Some((
+        FlashSource::User,
+        1.0
+      ));
+    }
+
+    let (started, until, source) =
+      self.flashed_notes.get(&note)?;
+    let alpha = flash_alpha(
+      Instant::now(),
+      *started,
+      *until
+    );
+    if alpha <= 0.0 {
+      return None;
+    }
+
+    if self
+      .config
+      .gameplay
+      .distinguish_autoplay_flashes
+    {
+      Some((*source, alpha))
+    } else {
+      Some((FlashSource::User, alpha))
+    }
   }
 
   fn playback_status_line(
@@ -2120,8 +4521,179 @@ impl PianoApp {
     }
   }
 
-  fn keyboard_note_range(
-    &self
+  fn now_next_readout(&self) -> String {
+    let Some(prepared) =
+      &self.prepared_song
+    else {
+      return "Now: - | Next: -"
+        .to_string();
+    };
+
+    let (now_index, next_index) =
+      match &self.playback {
+        | Some(playback)
+          if playback.mode
+            == PlayMode::Tutorial =>
+        {
+          let index = playback
+            .tutorial_event_index;
+          (Some(index), Some(index + 1))
+        }
+        | Some(playback) => {
+          let cursor =
+            playback.cursor_seconds;
+          let now = prepared
+            .events
+            .iter()
+            .rposition(|event| {
+              event.at_seconds <= cursor
+                && cursor
+                  < event.at_seconds
+                    + event
+                      .duration_seconds
+            });
+          let next = prepared
+            .events
+            .iter()
+            .position(|event| {
+              event.at_seconds > cursor
+            });
+          (now, next)
+        }
+        | None => (None, Some(0))
+      };
+
+    let describe = |index: Option<
+      usize
+    >| {
+      index
+        .and_then(|index| {
+          prepared.events.get(index)
+        })
+        .map_or_else(
+          || "-".to_string(),
+          |event| {
+            event
+              .notes
+              .iter()
+              .map(|note| {
+                format!(
+                  "{} ({})",
+                  self
+                    .binding_label_for_song_note(
+                      *note
+                    ),
+                  self
+                    .song_input_note(*note)
+                    .map_or_else(
+                      || "-".to_string(),
+                      |note| midi_note_name(note, self.config.ui.octave_convention)
+                    )
+                )
+              })
+              .collect::<Vec<_>>()
+              .join(" ")
+          }
+        )
+    };
+
+    format!(
+      "Now: {} | Next: {}",
+      describe(now_index),
+      describe(next_index)
+    )
+  }
+
+  fn unsnapped_note_range(
+    &self
+  ) -> (u8, u8) {
+    let song_keys =
+      self.song_key_notes();
+    match (
+      song_keys.first(),
+      song_keys.last()
+    ) {
+      | (Some(min), Some(max)) => {
+        (*min, *max)
+      }
+      | _ => self.visible_note_window()
+    }
+  }
+
+  fn keyboard_note_range(
+    &self
+  ) -> (u8, u8) {
+    let (min_note, max_note) =
+      self.unsnapped_note_range();
+
+    if !self
+      .config
+      .gameplay
+      .snap_keyboard_to_octaves
+    {
+      return (min_note, max_note);
+    }
+
+    (
+      min_note - min_note % 12,
+      max_note
+        .saturating_add(
+          11 - max_note % 12
+        )
+        .min(127)
+    )
+  }
+
+  fn song_key_notes(
+    &self
+  ) -> &BTreeSet<u8> {
+    static NO_SONG_KEYS: BTreeSet<u8> =
+      BTreeSet::new();
+    if self.song_keys_only {
+      &self.song_keys
+    } else {
+      &NO_SONG_KEYS
+    }
+  }
+
+  fn is_octave_padding_key(
+    &self,
+    note: u8
+  ) -> bool {
+    if !self
+      .config
+      .gameplay
+      .snap_keyboard_to_octaves
+      || self
+        .bindings
+        .note_to_chords
+        .contains_key(&note)
+    {
+      return false;
+    }
+
+    let (min_note, max_note) =
+      self.unsnapped_note_range();
+    note < min_note || note > max_note
+  }
+
+  fn is_dimmed_key(
+    &self,
+    note: u8
+  ) -> bool {
+    let song_keys =
+      self.song_key_notes();
+    if !song_keys.is_empty()
+      && !song_keys.contains(&note)
+    {
+      return true;
+    }
+
+    self.is_octave_padding_key(note)
+  }
+
+  fn visible_note_window(
+    &self
   ) -> (u8, u8) {
     let bound_min = self
       .bindings
@@ -2214,6 +4786,16 @@ impl PianoApp {
   fn manual_playback_note(
     &self,
     input_note: u8
+  ) -> u8 {
+    self.globally_transposed(
+      self
+        .song_playback_note(input_note)
+    )
+  }
+
+  fn song_playback_note(
+    &self,
+    input_note: u8
   ) -> u8 {
     if self
       .playback
@@ -2240,520 +4822,550 @@ impl PianoApp {
     input_note
   }
 
-  fn primary_binding_label(
-    &self,
-    note: u8
-  ) -> String {
-    self
-      .bindings
-      .note_to_chords
-      .get(&note)
-      .and_then(|entries| {
-        entries.first()
-      })
-      .cloned()
-      .unwrap_or_else(|| {
-        "-".to_string()
-      })
-  }
-
-  fn song_input_note(
+  fn globally_transposed(
     &self,
-    note: u8
-  ) -> Option<u8> {
-    key_from_song_input(
-      note,
-      self.prepared_transpose_semitones
-    )
+    midi_note: u8
+  ) -> u8 {
+    (i16::from(midi_note)
+      + i16::from(
+        self.global_transpose
+      ))
+    .clamp(0, 127) as u8
   }
 
-  fn binding_label_for_song_note(
-    &self,
-    note: u8
-  ) -> String {
-    self
-      .song_input_note(note)
-      .map_or_else(
-        || "-".to_string(),
-        |input_note| {
-          self.primary_binding_label(
-            input_note
-          )
-        }
-      )
+  fn shift_global_transpose(
+    &mut self,
+    delta: i8
+  ) {
+    self.set_global_transpose(
+      self
+        .global_transpose
+        .saturating_add(delta)
+    );
   }
 
-  fn guided_notes(
-    &self
-  ) -> HashSet<u8> {
-    let mut notes = HashSet::new();
-
-    let Some(playback) = &self.playback
-    else {
-      return notes;
-    };
-    let Some(prepared) =
-      &self.prepared_song
+  fn poll_sync_input(&self) -> Vec<i8> {
+    let Some(socket) =
+      self.sync_input.as_ref()
     else {
-      return notes;
+      return Vec::new();
     };
 
-    match playback.mode {
-      | PlayMode::Tutorial => {
-        if let Some(event) =
-          prepared.events.get(
-            playback
-              .tutorial_event_index
-          )
-        {
-          let expected = event
-            .notes
-            .iter()
-            .filter_map(|note| {
-              self
-                .song_input_note(*note)
-            })
-            .collect::<HashSet<_>>();
-          notes.extend(
-            expected
-              .into_iter()
-              .filter(|note| {
-                !playback
-                  .tutorial_matched
-                  .contains(note)
-              })
-          );
+    let mut commands = Vec::new();
+    let mut buffer = [0_u8; 512];
+    while let Ok(len) =
+      socket.recv(&mut buffer)
+    {
+      let message =
+        String::from_utf8_lossy(
+          &buffer[..len]
+        );
+      match parse_sync_transpose(
+        &message
+      ) {
+        | Some(semitones) => {
+          commands.push(semitones);
         }
-      }
-      | PlayMode::Timer => {
-        let cursor =
-          playback.cursor_seconds;
-        for event in &prepared.events {
-          if (event.at_seconds - cursor)
-            .abs()
-            <= 0.12
-          {
-            notes.extend(
-              event
-                .notes
-                .iter()
-                .filter_map(|note| {
-                  self.song_input_note(
-                    *note
-                  )
-                })
-            );
-          }
+        | None => {
+          debug!(%message, "ignored sync input message");
         }
       }
-      | PlayMode::Autoplay => {}
     }
-
-    notes
+    commands
   }
 
-  fn select_song(
+  fn set_global_transpose(
     &mut self,
-    index: usize
+    semitones: i8
   ) {
-    self.selected_song = Some(index);
-    self.rebuild_song_context();
-
-    self.playback = None;
-    self.last_timer_score = None;
-
-    if let Some(song) =
-      self.songs.get(index)
-    {
-      let song_id =
-        song.song.meta.id.clone();
-      let song_title =
-        song.song.meta.title.clone();
-      let line = format!(
-        "Selected song: {}",
-        song_title
-      );
-      self.push_activity(line);
-      info!(song_id = %song_id, title = %song_title, "song selected");
+    let next = semitones.clamp(-24, 24);
+    if next == self.global_transpose {
+      return;
     }
+    self.audio.stop_all_notes();
+    self.global_transpose = next;
+    self.settings_dirty = true;
+    self.push_activity(format!(
+      "Global transpose: {next:+} st"
+    ));
+    self
+      .refresh_instrument_range_check();
+    info!(
+      semitones = next,
+      "global transpose updated"
+    );
   }
 
-  fn select_next_song(&mut self) {
-    let filtered =
-      self.filtered_song_indices();
-    if filtered.is_empty() {
-      self.push_activity(
-        "No songs available in \
-         current search filter."
-          .to_string()
+  fn keymap_reference(
+    &self
+  ) -> Vec<
+    Vec<(&'static str, KeymapStatus)>
+  > {
+    let layout_keys =
+      keyboard_layout_key_priority(
+        self.config.keyboard.layout
       );
-      return;
-    }
 
-    let next = match self.selected_song
-    {
-      | Some(current) => {
-        let current_pos = filtered
+    KeyRow::ALL
+      .into_iter()
+      .map(|row| {
+        row
+          .keys()
           .iter()
-          .position(|index| {
-            *index == current
+          .filter(|key| {
+            layout_keys.contains(key)
           })
-          .unwrap_or(0);
-        let next_pos = (current_pos
-          + 1)
-          % filtered.len();
-        filtered[next_pos]
-      }
-      | None => filtered[0]
-    };
-
-    self.select_song(next);
+          .map(|key| {
+            let note = self
+              .bindings
+              .note_bindings
+              .iter()
+              .find(|(chord, _)| {
+                chord.key == *key
+                  && chord.modifiers
+                    == KeyModifiers::default()
+              })
+              .map(|(_, note)| *note);
+            let control = self
+              .bindings
+              .control_chords()
+              .any(|chord| {
+                chord.key == *key
+                  && chord.modifiers
+                    == KeyModifiers::default()
+              });
+            let status = match note {
+              | Some(note) => {
+                KeymapStatus::Note(note)
+              }
+              | None if control => {
+                KeymapStatus::Control
+              }
+              | None => {
+                KeymapStatus::Unbound
+              }
+            };
+            (*key, status)
+          })
+          .collect()
+      })
+      .collect()
   }
 
-  fn start_playback(&mut self) {
-    let Some(prepared) =
-      self.prepared_song.as_ref()
+  fn export_song_stems(&mut self) {
+    let Some(loaded) =
+      self.selected_song.and_then(
+        |index| self.songs.get(index)
+      )
     else {
-      self.push_activity(
-        "Select a song before \
-         starting playback."
-          .to_string()
-      );
       return;
     };
+    let out_dir = PathBuf::from(
+      &self
+        .config
+        .audio
+        .stems_export_directory
+    );
 
-    if prepared.events.is_empty() {
-      self.push_activity(
-        "Selected song has no notes \
-         to play."
-          .to_string()
-      );
-      return;
+    match self.audio.render_song_stems(
+      &loaded.song,
+      &out_dir
+    ) {
+      | Ok(paths) => {
+        info!(stems = paths.len(), dir = %out_dir.display(), "song stems exported");
+        self.push_activity(format!(
+          "Exported {} stem(s) to {}.",
+          paths.len(),
+          out_dir.display()
+        ));
+      }
+      | Err(error) => {
+        warn!(dir = %out_dir.display(), %error, "failed exporting song stems");
+        self.push_activity(format!(
+          "Stem export to {} failed: \
+           {error}",
+          out_dir.display()
+        ));
+      }
     }
+  }
 
-    self.held_notes.clear();
-    self.flashed_notes.clear();
-    self.last_timer_score = None;
+  fn export_song_midi(&mut self) {
+    let (Some(loaded), Some(prepared)) = (
+      self.selected_song.and_then(
+        |index| self.songs.get(index)
+      ),
+      self.prepared_song.as_ref()
+    ) else {
+      return;
+    };
 
-    let mut state = PlaybackState::new(
-      self.play_mode,
-      prepared
+    let song = prepared_song_file(
+      &loaded.song,
+      prepared,
+      self.global_transpose
     );
-
-    if state.mode == PlayMode::Tutorial
-    {
-      state.cursor_seconds = prepared
-        .events
-        .first()
-        .map_or(0.0, |event| {
-          event.at_seconds
-        });
-    }
-
-    self.playback = Some(state);
-    self.push_activity(format!(
-      "Playback started in {} mode.",
-      self.play_mode
+    let path = PathBuf::from(
+      &self
+        .config
+        .song_library
+        .midi_export_directory
+    )
+    .join(format!(
+      "{}.mid",
+      song.meta.id
     ));
 
-    info!(mode = %self.play_mode, "playback started");
-  }
-
-  fn stop_playback(&mut self) {
-    if self.playback.is_some() {
-      self.playback = None;
-      self.push_activity(
-        "Playback stopped.".to_string()
-      );
-      info!("playback stopped");
+    match export_midi(&song, &path) {
+      | Ok(()) => {
+        info!(path = %path.display(), events = song.events.len(), "song exported as MIDI");
+        self.push_activity(format!(
+          "Exported MIDI to {}.",
+          path.display()
+        ));
+      }
+      | Err(error) => {
+        warn!(path = %path.display(), %error, "failed exporting MIDI");
+        self.push_activity(format!(
+          "MIDI export to {} failed: \
+           {error}",
+          path.display()
+        ));
+      }
     }
   }
 
-  fn handle_tick(
-    &mut self,
-    now: Instant
-  ) {
-    self.prune_flashes(now);
-
-    let Some(mut playback) =
-      self.playback.take()
-    else {
-      return;
-    };
-
-    let Some(prepared) =
-      self.prepared_song.clone()
-    else {
-      return;
-    };
-
-    let mut keep_running = true;
-
-    match playback.mode {
-      | PlayMode::Timer => {
-        let elapsed = now
-          .duration_since(
-            playback.started_at
+  fn toggle_recording(&mut self) {
+    if !self.audio.is_recording() {
+      match self.audio.start_recording()
+      {
+        | Ok(()) => {
+          self.push_activity(
+            "Recording started."
+              .to_string()
           )
-          .as_secs_f32();
-        playback.cursor_seconds =
-          elapsed;
-
-        while elapsed
-          >= playback
-            .next_metronome_beat_s
-        {
-          let accent = playback
-            .next_metronome_index
-            % self
-              .selected_beats_per_bar()
-              as u64
-            == 0;
-          self
-            .audio
-            .play_metronome_tick(
-              accent
-            );
-          playback
-            .next_metronome_index += 1;
-          playback
-            .next_metronome_beat_s +=
-            prepared.beat_seconds;
         }
-
-        if elapsed
-          > prepared.duration_seconds
-            + 1.2
-        {
-          playback.score.missed_notes =
-            playback
-              .score
-              .expected_notes
-              .saturating_sub(
-                playback
-                  .score
-                  .hit_notes
-              );
-
-          self.last_timer_score = Some(
-            playback.score.clone()
-          );
-
+        | Err(error) => {
           self.push_activity(format!(
-            "Timer complete: {:.1}% \
-             accuracy (perfect {} \
-             good {} wrong {} missed \
-             {}).",
-            playback
-              .score
-              .accuracy_percent(),
-            playback.score.perfect_hits,
-            playback.score.good_hits,
-            playback.score.wrong_notes,
-            playback.score.missed_notes,
-          ));
-
-          keep_running = false;
-          info!(
-            accuracy = playback
-              .score
-              .accuracy_percent(),
-            "timer mode finished"
-          );
+            "Recording failed to \
+             start: {error}"
+          ))
         }
       }
-      | PlayMode::Autoplay => {
-        let elapsed = now
-          .duration_since(
-            playback.started_at
-          )
-          .as_secs_f32();
-        playback.cursor_seconds =
-          elapsed;
-
-        while let Some(event) = prepared
-          .events
-          .get(
-            playback.next_event_index
-          )
-          .cloned()
-        {
-          if event.at_seconds > elapsed
-          {
-            break;
-          }
+      return;
+    }
 
-          self.trigger_event(&event);
-          playback.next_event_index +=
-            1;
+    let song_id = self
+      .selected_song
+      .and_then(|index| {
+        self.songs.get(index)
+      })
+      .map_or_else(
+        || "free-play".to_string(),
+        |loaded| {
+          loaded.song.meta.id.clone()
         }
+      );
+    let stamp = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map_or(0, |elapsed| {
+        elapsed.as_secs()
+      });
+    let path = PathBuf::from(
+      &self
+        .config
+        .audio
+        .recordings_directory
+    )
+    .join(format!(
+      "{song_id}_{stamp}.wav"
+    ));
 
-        if elapsed
-          > prepared.duration_seconds
-            + 0.8
-        {
-          self.push_activity(
-            "Auto Play complete."
-              .to_string()
-          );
-          keep_running = false;
-          info!("autoplay finished");
-        }
+    match self
+      .audio
+      .stop_recording(&path)
+    {
+      | Ok(None) => {
+        self.push_activity(
+          "Recording stopped; nothing \
+           was played."
+            .to_string()
+        )
       }
-      | PlayMode::Tutorial => {
-        if let Some(event) = prepared
-          .events
-          .get(
-            playback
-              .tutorial_event_index
-          )
-          .cloned()
-        {
-          playback.cursor_seconds =
-            event.at_seconds;
-        } else {
-          playback.cursor_seconds =
-            prepared.duration_seconds;
-          self.push_activity(
-            "Tutorial complete."
-              .to_string()
-          );
-          keep_running = false;
-          info!("tutorial finished");
-        }
+      | Ok(Some(seconds)) => {
+        self.push_activity(format!(
+          "Saved recording ({:.1}s) \
+           to {}.",
+          seconds,
+          path.display()
+        ))
+      }
+      | Err(error) => {
+        warn!(path = %path.display(), %error, "failed saving recording");
+        self.push_activity(format!(
+          "Saving recording failed: \
+           {error}"
+        ))
       }
-    }
-
-    if keep_running {
-      self.playback = Some(playback);
     }
   }
 
-  fn process_note_input(
-    &mut self,
-    midi_note: u8
-  ) -> bool {
-    let mut play_out_loud = true;
+  fn export_keymap(&mut self) {
+    let sheet = keymap_cheat_sheet(
+      &self.config,
+      &self.keymap_reference(),
+      &self.bindings.note_to_chords
+    );
+    let path = PathBuf::from(
+      &self
+        .config
+        .keyboard
+        .keymap_export_path
+    );
 
-    let Some(mut playback) =
-      self.playback.take()
-    else {
-      return play_out_loud;
-    };
+    match std::fs::write(&path, sheet) {
+      | Ok(()) => {
+        info!(path = %path.display(), "keymap exported");
+        self.push_activity(format!(
+          "Exported keymap to {}.",
+          path.display()
+        ));
+      }
+      | Err(error) => {
+        warn!(path = %path.display(), %error, "failed exporting keymap");
+        self.push_activity(format!(
+          "Keymap export to {} \
+           failed: {error}",
+          path.display()
+        ));
+      }
+    }
+  }
 
-    let Some(prepared) =
-      self.prepared_song.clone()
-    else {
-      self.playback = Some(playback);
-      return play_out_loud;
-    };
+  fn note_for_chord(
+    &self,
+    chord: &KeyChord
+  ) -> Option<u8> {
+    if let Some(midi_note) = self
+      .bindings
+      .note_bindings
+      .get(chord)
+    {
+      return Some(*midi_note);
+    }
 
-    let mut keep_running = true;
+    if !self
+      .config
+      .input
+      .ignore_shift_for_note_keys
+    {
+      return None;
+    }
 
-    match playback.mode {
-      | PlayMode::Timer => {
-        let now = Instant::now();
-        let cursor = now
-          .duration_since(
-            playback.started_at
-          )
-          .as_secs_f32();
-        playback.cursor_seconds =
-          cursor;
+    chord.without_shift().and_then(
+      |unshifted| {
+        self
+          .bindings
+          .note_bindings
+          .get(&unshifted)
+          .copied()
+      }
+    )
+  }
 
-        let mut best_match: Option<(
-          usize,
-          f32
-        )> = None;
+  fn primary_binding_label(
+    &self,
+    note: u8
+  ) -> String {
+    let key_priority =
+      keyboard_layout_key_priority(
+        self.config.keyboard.layout
+      );
 
-        for (index, expected) in
-          prepared
-            .expected_notes
-            .iter()
-            .enumerate()
+    self
+      .bindings
+      .note_to_chords
+      .get(&note)
+      .and_then(|entries| {
+        match self
+          .config
+          .keyboard
+          .primary_label_order
         {
-          let Some(expected_note) =
-            self.song_input_note(
-              expected.midi_note
+          | PrimaryLabelOrder::Alphabetical => {
+            entries.first()
+          }
+          | PrimaryLabelOrder::LayoutPriority => {
+            entries.iter().min_by_key(
+              |chord| {
+                chord_layout_rank(
+                  chord,
+                  key_priority
+                )
+              }
             )
-          else {
-            continue;
-          };
-
-          if expected_note != midi_note
-          {
-            continue;
-          }
-          if playback
-            .matched_note_indices
-            .contains(&index)
-          {
-            continue;
           }
+        }
+      })
+      .cloned()
+      .unwrap_or_else(|| {
+        "-".to_string()
+      })
+  }
 
-          let delta = (expected
-            .at_seconds
-            - cursor)
-            .abs();
-          if delta
-            > TIMER_WINDOW_SECONDS
-          {
-            continue;
-          }
+  fn original_song_note_label(
+    &self,
+    note: u8
+  ) -> Option<String> {
+    let transpose =
+      self.prepared_transpose_semitones;
+    if transpose == 0
+      || self.playback.is_none()
+      || !self
+        .config
+        .gameplay
+        .show_original_note_names
+    {
+      return None;
+    }
 
-          match best_match {
-            | Some((_, best_delta))
-              if delta
-                >= best_delta => {}
-            | _ => {
-              best_match =
-                Some((index, delta));
-            }
-          }
+    let original = i16::from(note)
+      - i16::from(transpose);
+    u8::try_from(original).ok().map(
+      |original| {
+        format!(
+          "orig {}",
+          midi_note_name(
+            original,
+            self
+              .config
+              .ui
+              .octave_convention
+          )
+        )
+      }
+    )
+  }
+
+  fn song_input_note(
+    &self,
+    note: u8
+  ) -> Option<u8> {
+    key_from_song_input(
+      note,
+      self.prepared_transpose_semitones
+    )
+  }
+
+  fn binding_label_for_song_note(
+    &self,
+    note: u8
+  ) -> String {
+    self
+      .song_input_note(note)
+      .map_or_else(
+        || "-".to_string(),
+        |input_note| {
+          self.primary_binding_label(
+            input_note
+          )
         }
+      )
+  }
 
-        if let Some((index, delta)) =
-          best_match
-        {
-          playback
-            .matched_note_indices
-            .insert(index);
-          playback.score.hit_notes += 1;
+  fn in_scale_assist(
+    &self,
+    note: u8
+  ) -> bool {
+    self.playback.is_none()
+      && scale_pitch_classes(
+        self.scale_root.0,
+        self.scale_kind
+      )
+      .contains(&(note % 12))
+  }
 
-          if delta
-            <= TIMER_PERFECT_SECONDS
-          {
-            playback
-              .score
-              .perfect_hits += 1;
-          } else {
-            playback.score.good_hits +=
-              1;
-          }
+  fn guided_finger(
+    &self,
+    input_note: u8
+  ) -> Option<u8> {
+    let playback =
+      self.playback.as_ref()?;
+    let prepared =
+      self.prepared_song.as_ref()?;
+
+    let fingered =
+      |event: &PreparedEvent| {
+        event
+          .notes
+          .iter()
+          .zip(&event.fingering)
+          .find(|(note, _)| {
+            self.song_input_note(**note)
+              == Some(input_note)
+          })
+          .map(|(_, finger)| *finger)
+      };
 
-          debug!(
-            midi_note,
-            delta, "timer note matched"
-          );
-        } else {
-          playback.score.wrong_notes +=
-            1;
-          debug!(
-            midi_note,
-            "timer note missed"
-          );
-        }
-      }
+    match playback.mode {
       | PlayMode::Tutorial => {
-        if let Some(event) = prepared
+        if playback
+          .tutorial_matched
+          .contains(&input_note)
+        {
+          return None;
+        }
+        prepared
           .events
           .get(
             playback
               .tutorial_event_index
           )
-          .cloned()
+          .and_then(fingered)
+      }
+      | PlayMode::Autoplay => {
+        let cursor =
+          playback.cursor_seconds;
+        prepared
+          .events
+          .iter()
+          .filter(|event| {
+            event.at_seconds <= cursor
+              && cursor
+                < event.at_seconds
+                  + event
+                    .duration_seconds
+          })
+          .find_map(fingered)
+      }
+      | PlayMode::Timer => None
+    }
+  }
+
+  fn guided_notes(
+    &self
+  ) -> HashSet<u8> {
+    let mut notes = HashSet::new();
+
+    let Some(playback) = &self.playback
+    else {
+      return notes;
+    };
+    let Some(prepared) =
+      &self.prepared_song
+    else {
+      return notes;
+    };
+
+    match playback.mode {
+      | PlayMode::Tutorial => {
+        if let Some(event) =
+          prepared.events.get(
+            playback
+              .tutorial_event_index
+          )
         {
-          let expected_notes = event
+          let expected = event
             .notes
             .iter()
             .filter_map(|note| {
@@ -2761,917 +5373,5620 @@ impl PianoApp {
                 .song_input_note(*note)
             })
             .collect::<HashSet<_>>();
-          let correct = expected_notes
-            .contains(&midi_note);
-
-          if correct {
-            playback
-              .tutorial_matched
-              .insert(midi_note);
-
-            let expected_unique =
-              expected_notes.len();
-
-            if playback
-              .tutorial_matched
-              .len()
-              >= expected_unique
-            {
-              playback
-                .tutorial_event_index += 1;
-              playback
-                .tutorial_matched
-                .clear();
-            }
-          } else {
-            play_out_loud = self
-              .tutorial_options
-              .play_bad_notes_out_loud;
-
-            if self
-              .tutorial_options
-              .only_advance_on_correct_note
-            {
-              self.push_activity(format!(
-                "Tutorial expects: {}",
-                event
-                  .notes
-                  .iter()
-                  .map(|note| self
-                    .binding_label_for_song_note(
-                      *note
-                    ))
-                  .collect::<Vec<_>>()
-                  .join(" ")
-              ));
-            } else {
-              playback
-                .tutorial_event_index += 1;
-              playback
-                .tutorial_matched
-                .clear();
-            }
-          }
-
-          if playback
-            .tutorial_event_index
-            >= prepared.events.len()
-          {
-            keep_running = false;
-            self.push_activity(
-              "Tutorial complete."
-                .to_string()
-            );
-          }
+          notes.extend(
+            expected
+              .into_iter()
+              .filter(|note| {
+                !playback
+                  .tutorial_matched
+                  .contains(note)
+              })
+          );
         }
       }
+      | PlayMode::Timer
       | PlayMode::Autoplay => {
-        // Manual notes are allowed
-        // while autoplay runs.
+        if playback.mode
+          == PlayMode::Autoplay
+          && !playback.paused
+        {
+          return notes;
+        }
+        notes.extend(
+          song_notes_near_cursor(
+            prepared,
+            playback.cursor_seconds
+          )
+          .filter_map(
+            |note| {
+              self.song_input_note(note)
+            }
+          )
+        );
       }
     }
 
-    if keep_running {
-      self.playback = Some(playback);
+    notes
+  }
+
+  fn lead_in_intensity(
+    &self,
+    note: u8
+  ) -> f32 {
+    if !self.config.ui.lead_in_highlight
+    {
+      return 0.0;
+    }
+    let (
+      Some(playback),
+      Some(prepared)
+    ) = (
+      &self.playback,
+      &self.prepared_song
+    )
+    else {
+      return 0.0;
+    };
+    if playback.mode != PlayMode::Timer
+    {
+      return 0.0;
     }
 
-    play_out_loud
+    prepared
+      .events
+      .iter()
+      .filter(|event| {
+        event.notes.iter().any(
+          |song_note| {
+            self.song_input_note(
+              *song_note
+            ) == Some(note)
+          }
+        )
+      })
+      .map(|event| {
+        lead_in_intensity(
+          event.at_seconds
+            - playback.cursor_seconds,
+          self
+            .config
+            .ui
+            .lead_in_seconds
+        )
+      })
+      .fold(0.0, f32::max)
   }
 
-  fn trigger_event(
+  fn report_library_stats(
     &mut self,
-    event: &PreparedEvent
+    stats: LibraryLoadStats
   ) {
-    for midi_note in &event.notes {
-      self.audio
-        .play_note_with_velocity_duration(
-          *midi_note,
-          event.velocity,
-          event.duration_ms
-        );
-      if let Some(input_note) =
-        self.song_input_note(*midi_note)
-      {
-        self.flash_note(input_note);
-        self.set_focus_note(input_note);
-      } else {
-        self.flash_note(*midi_note);
-        self.set_focus_note(*midi_note);
-      }
+    if !self
+      .config
+      .song_library
+      .cache_enabled
+      || !stats.rebuilt_cache()
+    {
+      return;
     }
+
+    self.push_activity(format!(
+      "Rebuilt song cache: {} \
+       reparsed, {} cache hit(s), {} \
+       from index.",
+      stats.cache_misses,
+      stats.cache_hits,
+      stats.indexed
+    ));
   }
 
-  fn selected_beats_per_bar(
-    &self
-  ) -> u8 {
-    self
+  fn reload_song_library(&mut self) {
+    let songs = match load_song_library(
+      &self.config.song_library
+    ) {
+      | Ok((songs, stats)) => {
+        self
+          .report_library_stats(stats);
+        songs
+      }
+      | Err(error) => {
+        self.push_activity(format!(
+          "Failed to reload song \
+           library: {error:#}"
+        ));
+        return;
+      }
+    };
+
+    let previous_id = self
       .selected_song
       .and_then(|index| {
         self.songs.get(index)
       })
-      .map(|song| {
-        song.song.meta.beats_per_bar
-      })
-      .filter(|beats| *beats > 0)
-      .unwrap_or(4)
-  }
-}
-
-fn configured_config_path() -> PathBuf {
-  env::var("SYMFOSE_CONFIG")
-    .map(PathBuf::from)
-    .unwrap_or_else(|_| {
-      PathBuf::from(DEFAULT_CONFIG_PATH)
-    })
-}
+      .map(|loaded| {
+        loaded.song.meta.id.clone()
+      });
+    self.song_thumbnails =
+      build_song_thumbnails(&songs);
+    self.songs = songs;
+    self.song_page = 0;
+    self.push_activity(format!(
+      "Reloaded song library: {} \
+       song(s)",
+      self.songs.len()
+    ));
 
-fn init_tracing(
-  config: &AppConfig
-) -> Result<WorkerGuard> {
-  let filter =
-    EnvFilter::try_from_default_env()
-      .unwrap_or_else(|_| {
-        EnvFilter::new(
-          config.logging.filter.clone()
+    let index = previous_id
+      .and_then(|id| {
+        self.songs.iter().position(
+          |loaded| {
+            loaded.song.meta.id == id
+          }
         )
+      })
+      .or_else(|| {
+        (!self.songs.is_empty())
+          .then_some(0)
       });
-
-  std::fs::create_dir_all(Path::new(
-    &config.logging.directory
-  ))
-  .with_context(|| {
-    format!(
-      "failed creating {}",
-      config.logging.directory
-    )
-  })?;
-
-  let file_appender =
-    tracing_appender::rolling::daily(
-      &config.logging.directory,
-      "symfose"
-    );
-  let (file_writer, guard) =
-    tracing_appender::non_blocking(
-      file_appender
-    );
-
-  let console_layer =
-    tracing_fmt::layer()
-      .with_target(true)
-      .with_file(true)
-      .with_line_number(true)
-      .with_thread_ids(true)
-      .with_thread_names(true);
-
-  let file_layer = tracing_fmt::layer()
-    .with_ansi(false)
-    .with_target(true)
-    .with_file(true)
-    .with_line_number(true)
-    .with_thread_ids(true)
-    .with_thread_names(true)
-    .with_writer(file_writer);
-
-  tracing_subscriber::registry()
-    .with(filter)
-    .with(console_layer)
-    .with(file_layer)
-    .init();
-
-  Ok(guard)
-}
-
-fn compile_runtime_bindings(
-  config: &AppConfig
-) -> Result<RuntimeBindings> {
-  let note_bindings =
-    compile_note_bindings(
-      &config.effective_keybindings()
-    )?;
-  let quit = compile_chord_set(
-    &config.control_bindings.quit,
-    "quit"
-  )?;
-  let list_songs = compile_chord_set(
-    &config.control_bindings.list_songs,
-    "list_songs"
-  )?;
-  let print_bindings =
-    compile_chord_set(
-      &config
-        .control_bindings
-        .print_bindings,
-      "print_bindings"
-    )?;
-  let play_song = compile_chord_set(
-    &config.control_bindings.play_song,
-    "play_song"
-  )?;
-
-  let mut note_to_chords =
-    BTreeMap::<u8, Vec<String>>::new();
-  for (chord, note) in &note_bindings {
-    note_to_chords
-      .entry(*note)
-      .or_default()
-      .push(chord.to_string());
-  }
-
-  for chords in
-    note_to_chords.values_mut()
-  {
-    chords.sort_unstable();
+    match index {
+      | Some(index) => {
+        self.select_song(index)
+      }
+      | None => {
+        self.selected_song = None;
+        self.playback = None;
+        self.auto_advance_at = None;
+        self.rebuild_song_context();
+      }
+    }
   }
 
-  Ok(RuntimeBindings {
-    note_bindings,
-    note_to_chords,
-    quit,
-    list_songs,
-    print_bindings,
-    play_song
-  })
-}
+  fn ensure_song_parsed(
+    &mut self,
+    index: usize
+  ) {
+    let Some(loaded) =
+      self.songs.get_mut(index)
+    else {
+      return;
+    };
+    if loaded.parsed {
+      return;
+    }
 
-fn apply_song_ergonomic_bindings(
-  bindings: &mut RuntimeBindings,
-  song: &SongFile,
-  layout: KeyboardLayout,
-  transpose_semitones: i8
-) {
-  let mut note_scores =
-    HashMap::<u8, usize>::new();
-  let mut cooccur =
-    HashMap::<(u8, u8), usize>::new();
+    match ensure_song_parsed(
+      loaded,
+      &self.config.song_library
+    ) {
+      | Ok(()) => {
+        if let Some(thumbnail) =
+          build_song_thumbnail(loaded)
+        {
+          self.song_thumbnails.insert(
+            loaded.song.meta.id.clone(),
+            thumbnail
+          );
+        }
+      }
+      | Err(error) => {
+        warn!(path = %loaded.path.display(), error = %error, "failed parsing indexed song");
+        self.push_activity(format!(
+          "Failed to load song: \
+           {error:#}"
+        ));
+      }
+    }
+  }
 
-  for event in &song.events {
-    let mut notes = event
-      .notes
+  fn select_song(
+    &mut self,
+    index: usize
+  ) {
+    self.ensure_song_parsed(index);
+    self.selected_song = Some(index);
+    if let Some(position) = self
+      .filtered_song_indices()
       .iter()
-      .filter_map(|note| {
-        key_from_song_input(
-          *note,
-          transpose_semitones
-        )
-      })
-      .collect::<Vec<_>>();
-    notes.sort_unstable();
-    notes.dedup();
-    if notes.is_empty() {
-      continue;
+      .position(|entry| *entry == index)
+    {
+      self.song_page = position
+        / self
+          .config
+          .ui
+          .song_page_size
+          .max(1);
     }
+    self.supplemental_bindings.clear();
+    self.practice_start_index = None;
+    self.rebuild_song_context();
 
-    let chord_bonus =
-      notes.len().saturating_sub(1) * 3;
-    for note in &notes {
-      *note_scores
-        .entry(*note)
-        .or_default() +=
-        1 + chord_bonus;
-    }
+    self.playback = None;
+    self.auto_advance_at = None;
+    self.last_timer_score = None;
+    self.last_performance.clear();
+    self.worst_section = None;
 
-    for left in 0..notes.len() {
-      for right in
-        (left + 1)..notes.len()
+    if let Some(song) =
+      self.songs.get(index)
+    {
+      let song_id =
+        song.song.meta.id.clone();
+      let song_title =
+        song.song.meta.title.clone();
+      let line = format!(
+        "Selected song: {}",
+        song_title
+      );
+      self.push_activity(line);
+      info!(song_id = %song_id, title = %song_title, "song selected");
+
+      let beats_per_bar =
+        self.selected_beats_per_bar();
+      let pattern_len = self
+        .config
+        .metronome
+        .accent_pattern
+        .len();
+      if pattern_len > 0
+        && !self
+          .config
+          .metronome
+          .pattern_fits_bar(
+            beats_per_bar
+          )
       {
-        *cooccur
-          .entry((
-            notes[left],
-            notes[right]
-          ))
-          .or_default() += 1;
+        self.push_activity(format!(
+          "Metronome accent pattern \
+           has {pattern_len} beat(s), \
+           which does not divide \
+           {beats_per_bar} beats per \
+           bar; accenting the \
+           downbeat instead."
+        ));
+        warn!(
+          pattern_len,
+          beats_per_bar,
+          "metronome accent pattern \
+           does not fit song meter"
+        );
       }
     }
   }
 
-  if note_scores.is_empty() {
-    return;
-  }
-
-  let mut ranked_notes = note_scores
-    .into_iter()
-    .collect::<Vec<_>>();
-  ranked_notes.sort_by(
-    |left, right| {
-      right
-        .1
-        .cmp(&left.1)
-        .then(left.0.cmp(&right.0))
+  fn begin_song_jump(&mut self) {
+    if self.song_jump.is_some() {
+      return;
     }
-  );
 
-  let song_notes = ranked_notes
-    .iter()
-    .map(|(note, _)| *note)
-    .collect::<HashSet<_>>();
-  let mut next_map = HashMap::new();
+    self.song_jump = Some(SongJump {
+      previous_query:
+        std::mem::take(
+          &mut self.song_search_query
+        ),
+      previous_selection: self
+        .selected_song
+    });
+    self.push_activity(
+      "Go to song: type an id or \
+       title, Enter selects, Esc \
+       cancels."
+        .to_string()
+    );
+  }
 
-  let mut available_keys =
-    ergonomic_key_priority(layout);
-  if song_notes.len()
-    > available_keys.len()
-  {
-    let shifted =
-      ergonomic_key_priority(layout)
-        .into_iter()
+  fn handle_song_jump_key(
+    &mut self,
+    key: &keyboard::Key
+  ) {
+    match key.as_ref() {
+      | keyboard::Key::Named(
+        keyboard::key::Named::Escape
+      ) => {
+        if let Some(jump) =
+          self.song_jump.take()
+        {
+          self.song_search_query =
+            jump.previous_query;
+          if jump.previous_selection
+            != self.selected_song
+            && let Some(index) =
+              jump.previous_selection
+          {
+            self.select_song(index);
+          }
+          self.push_activity(
+            "Go to song cancelled."
+              .to_string()
+          );
+        }
+      }
+      | keyboard::Key::Named(
+        keyboard::key::Named::Enter
+      ) => {
+        let Some(first) = self
+          .filtered_song_indices()
+          .first()
+          .copied()
+        else {
+          self.push_activity(format!(
+            "No song matches '{}'.",
+            self.song_search_query
+          ));
+          return;
+        };
+
+        self.song_jump = None;
+        self.select_song(first);
+      }
+      | keyboard::Key::Named(
+        keyboard::key::Named::Backspace
+      ) => {
+        self.song_search_query.pop();
+      }
+      | keyboard::Key::Named(
+        keyboard::key::Named::Space
+      ) => {
+        self
+          .song_search_query
+          .push(' ');
+      }
+      | keyboard::Key::Character(
+        text
+      ) => {
+        self
+          .song_search_query
+          .push_str(
+            &text.to_ascii_lowercase()
+          );
+      }
+      | _ => {}
+    }
+  }
+
+  fn select_next_song(&mut self) {
+    let filtered =
+      self.filtered_song_indices();
+    if filtered.is_empty() {
+      self.push_activity(
+        "No songs available in \
+         current search filter."
+          .to_string()
+      );
+      return;
+    }
+
+    let next = match self.selected_song
+    {
+      | Some(current) => {
+        let current_pos = filtered
+          .iter()
+          .position(|index| {
+            *index == current
+          })
+          .unwrap_or(0);
+        let next_pos = (current_pos
+          + 1)
+          % filtered.len();
+        filtered[next_pos]
+      }
+      | None => filtered[0]
+    };
+
+    self.select_song(next);
+  }
+
+  fn log_unmapped_key(
+    &mut self,
+    chord: String
+  ) {
+    if !self
+      .config
+      .app
+      .print_unmapped_keys
+    {
+      return;
+    }
+
+    let now = Instant::now();
+    let window = Duration::from_millis(
+      self
+        .config
+        .app
+        .unmapped_key_log_window_ms
+    );
+    self.unmapped_key_log.retain(
+      |_, logged_at| {
+        now.duration_since(*logged_at)
+          < window
+      }
+    );
+    if self
+      .unmapped_key_log
+      .contains_key(&chord)
+    {
+      return;
+    }
+
+    debug!(%chord, "unmapped key chord");
+    self.push_activity(format!(
+      "Unmapped chord: {chord}"
+    ));
+    self
+      .unmapped_key_log
+      .insert(chord, now);
+  }
+
+  fn start_playback(&mut self) {
+    self.begin_playback(
+      self.play_mode,
+      None
+    );
+  }
+
+  fn start_worst_section_drill(
+    &mut self
+  ) {
+    let Some(window) =
+      self.worst_section
+    else {
+      self.push_activity(
+        "Finish a Timer run with \
+         misses to drill its worst \
+         section."
+          .to_string()
+      );
+      return;
+    };
+
+    let mode = match self.play_mode {
+      | PlayMode::Autoplay => {
+        PlayMode::Autoplay
+      }
+      | PlayMode::Timer
+      | PlayMode::Tutorial => {
+        PlayMode::Tutorial
+      }
+    };
+    if mode == PlayMode::Autoplay
+      && self.playback_speed
+        > DRILL_AUTOPLAY_SPEED
+    {
+      self
+        .drill_restore_speed
+        .get_or_insert(
+          self.playback_speed
+        );
+      self.set_playback_speed(
+        DRILL_AUTOPLAY_SPEED
+      );
+    }
+    self.begin_playback(
+      mode,
+      Some(window)
+    );
+  }
+
+  fn restore_drill_speed(&mut self) {
+    let drilling = self
+      .playback
+      .as_ref()
+      .is_some_and(|playback| {
+        playback.loop_window.is_some()
+      });
+    if drilling {
+      return;
+    }
+    if let Some(speed) =
+      self.drill_restore_speed.take()
+    {
+      self.set_playback_speed(speed);
+      debug!(
+        speed,
+        "playback speed restored \
+         after drill"
+      );
+    }
+  }
+
+  fn seek_to(
+    &mut self,
+    seconds: f32
+  ) {
+    let Some(prepared) =
+      self.prepared_song.as_ref()
+    else {
+      return;
+    };
+    let Some(playback) =
+      self.playback.as_mut()
+    else {
+      return;
+    };
+
+    let seconds = seconds.clamp(
+      0.0,
+      prepared.duration_seconds
+    );
+    let index = first_event_index_at(
+      prepared, seconds
+    );
+    playback.started_at =
+      playback_started_at(
+        Instant::now(),
+        seconds,
+        self.playback_speed
+      );
+    playback.cursor_seconds = seconds;
+    playback.next_event_index = index;
+    playback.tutorial_event_index =
+      index.min(
+        prepared
+          .events
+          .len()
+          .saturating_sub(1)
+      );
+    playback.tutorial_matched.clear();
+    if playback.mode
+      == PlayMode::Tutorial
+    {
+      playback.cursor_seconds =
+        prepared
+          .events
+          .get(
+            playback
+              .tutorial_event_index
+          )
+          .map_or(seconds, |event| {
+            event.at_seconds
+          });
+    }
+
+    if prepared.beat_seconds > 0.0 {
+      let beats = prepared
+        .beat_at_seconds(seconds)
+        .ceil()
+        .max(0.0);
+      playback.next_metronome_beat_s =
+        prepared.seconds_at_beat(beats);
+      playback.next_metronome_index =
+        beats as u64;
+    }
+
+    trace!(
+      seconds,
+      index, "playback seeked"
+    );
+  }
+
+  fn record_performance_note(
+    &mut self,
+    input_note: u8,
+    velocity: u8
+  ) {
+    let Some(playback) = &self.playback
+    else {
+      return;
+    };
+    let seconds = playback
+      .started_at
+      .elapsed()
+      .as_secs_f32();
+    let note = self
+      .manual_playback_note(input_note);
+    self
+      .last_performance
+      .push((seconds, note, velocity));
+  }
+
+  fn replay_last_performance(
+    &mut self
+  ) {
+    if self.playback.is_some()
+      || self
+        .last_performance
+        .is_empty()
+    {
+      return;
+    }
+
+    let duration_ms = self
+      .config
+      .audio
+      .note_duration_ms;
+    let mut previous_seconds = self
+      .last_performance
+      .first()
+      .map_or(0.0, |entry| entry.0);
+    let sequence = self
+      .last_performance
+      .iter()
+      .map(
+        |&(seconds, note, velocity)| {
+          let delay_ms = ((seconds
+            - previous_seconds)
+            .max(0.0)
+            * 1000.0)
+            .round()
+            as u64;
+          previous_seconds = seconds;
+          (
+            note,
+            velocity,
+            delay_ms,
+            duration_ms
+          )
+        }
+      )
+      .collect::<Vec<_>>();
+
+    self.audio.play_sequence(&sequence);
+    self.push_activity(format!(
+      "Replaying last take: {} note(s)",
+      sequence.len()
+    ));
+  }
+
+  fn begin_playback(
+    &mut self,
+    mode: PlayMode,
+    loop_window: Option<PracticeWindow>
+  ) {
+    if loop_window.is_none()
+      && let Some(speed) =
+        self.drill_restore_speed.take()
+    {
+      self.set_playback_speed(speed);
+    }
+    let Some(prepared) =
+      self.prepared_song.as_ref()
+    else {
+      self.push_activity(
+        "Select a song before \
+         starting playback."
+          .to_string()
+      );
+      return;
+    };
+
+    if prepared.events.is_empty() {
+      self.push_activity(
+        "Selected song has no notes \
+         to play."
+          .to_string()
+      );
+      return;
+    }
+
+    for sounding in self
+      .held_notes
+      .drain()
+      .filter_map(|(_, sounding)| {
+        sounding
+      })
+    {
+      self.audio.stop_note(sounding);
+    }
+    self.held_keys.clear();
+    self.clicked_note = None;
+    self.flashed_notes.clear();
+    self.last_timer_score = None;
+    self.last_performance.clear();
+    self.auto_advance_at = None;
+
+    let mut state = PlaybackState::new(
+      mode,
+      prepared,
+      self
+        .config
+        .gameplay
+        .duration_weighted_scoring
+    );
+
+    let start_paused =
+      self.config.gameplay.start_paused
+        && mode != PlayMode::Tutorial;
+
+    if let Some(window) = loop_window {
+      let first_index =
+        first_event_index_at(
+          prepared,
+          window.start_seconds
+        );
+      state.loop_window = Some(window);
+      state.tutorial_event_index =
+        first_index;
+      state.next_event_index =
+        first_index;
+      state.cursor_seconds =
+        window.start_seconds;
+      state.started_at =
+        playback_started_at(
+          Instant::now(),
+          window.start_seconds,
+          self.playback_speed
+        );
+    } else if let Some(index) = self
+      .practice_start_index
+      .filter(|index| {
+        *index < prepared.events.len()
+      })
+    {
+      start_playback_at_event(
+        &mut state,
+        prepared,
+        index,
+        self.playback_speed
+      );
+    }
+
+    if start_paused
+      && state.next_event_index
+        < prepared.events.len()
+    {
+      let index =
+        state.next_event_index;
+      start_playback_at_event(
+        &mut state,
+        prepared,
+        index,
+        self.playback_speed
+      );
+      state.paused = true;
+    }
+
+    if state.mode == PlayMode::Tutorial
+    {
+      state.cursor_seconds = prepared
+        .events
+        .get(state.tutorial_event_index)
+        .map_or(0.0, |event| {
+          event.at_seconds
+        });
+    }
+
+    self.playback = Some(state);
+
+    let session_minutes = self
+      .config
+      .gameplay
+      .session_minutes;
+    if session_minutes > 0
+      && self.practice_session.is_none()
+    {
+      self.practice_session = Some(
+        PracticeSession {
+          ends_at:          Instant::now(
+          )
+            + Duration::from_secs(
+              u64::from(
+                session_minutes
+              ) * 60
+            ),
+          runs_completed:   0,
+          timer_accuracies: Vec::new()
+        }
+      );
+      self.push_activity(format!(
+        "Practice session started: \
+         {session_minutes} minute(s)."
+      ));
+      info!(
+        session_minutes,
+        "practice session started"
+      );
+    }
+
+    if let Some(window) = loop_window {
+      self.push_activity(format!(
+        "Drilling bars {}-{} in {} \
+         mode ({} missed note(s)).",
+        window.first_bar,
+        window.last_bar,
+        mode,
+        window.missed_notes
+      ));
+    } else {
+      self.push_activity(format!(
+        "Playback started in {mode} \
+         mode."
+      ));
+    }
+
+    info!(%mode, looping = loop_window.is_some(), "playback started");
+  }
+
+  fn record_session_run(
+    &mut self,
+    timer_accuracy: Option<f32>
+  ) {
+    if let Some(session) =
+      &mut self.practice_session
+    {
+      session.runs_completed += 1;
+      session
+        .timer_accuracies
+        .extend(timer_accuracy);
+    }
+  }
+
+  fn finish_practice_session(
+    &mut self
+  ) {
+    let Some(session) =
+      self.practice_session.take()
+    else {
+      return;
+    };
+
+    self.stop_playback();
+
+    let summary = if session
+      .timer_accuracies
+      .is_empty()
+    {
+      format!(
+        "Practice session over: {} \
+         run(s) completed.",
+        session.runs_completed
+      )
+    } else {
+      let average = session
+        .timer_accuracies
+        .iter()
+        .sum::<f32>()
+        / session.timer_accuracies.len()
+          as f32;
+      format!(
+        "Practice session over: {} \
+         run(s) completed, {} timed, \
+         {average:.1}% average \
+         accuracy.",
+        session.runs_completed,
+        session.timer_accuracies.len()
+      )
+    };
+    self.push_activity(summary);
+    info!(
+      runs = session.runs_completed,
+      timed_runs =
+        session.timer_accuracies.len(),
+      "practice session finished"
+    );
+  }
+
+  fn confirm_quit(&mut self) -> bool {
+    if !self
+      .config
+      .app
+      .require_confirm_quit
+    {
+      return true;
+    }
+
+    let now = Instant::now();
+    let window = Duration::from_millis(
+      self
+        .config
+        .app
+        .confirm_quit_window_ms
+    );
+    if self.pending_quit_at.is_some_and(
+      |requested| {
+        now.duration_since(requested)
+          <= window
+      }
+    ) {
+      return true;
+    }
+
+    self.pending_quit_at = Some(now);
+    self.push_activity(format!(
+      "Press the quit chord again \
+       within {:.1}s to quit.",
+      window.as_secs_f32()
+    ));
+    false
+  }
+
+  fn persisted_config(
+    &self
+  ) -> AppConfig {
+    let mut config =
+      self.config.clone();
+    config.audio.master_volume =
+      self.volume;
+    config.audio.instrument =
+      self.selected_instrument.clone();
+    config
+      .gameplay
+      .transpose_song_to_fit_bindings =
+      self
+        .transpose_song_to_fit_bindings;
+    config
+      .gameplay
+      .warn_on_missing_song_notes =
+      self.warn_on_missing_song_notes;
+    config
+      .gameplay
+      .optimize_bindings_for_song =
+      self.optimize_bindings_for_song;
+    config
+      .gameplay
+      .auto_jump_pressed_key_into_view =
+      self
+        .auto_jump_pressed_key_into_view;
+    config
+      .gameplay
+      .auto_scroll_song_lane_follow_playback =
+      self
+        .auto_scroll_song_lane_follow_playback;
+    config.gameplay.song_keys_only =
+      self.song_keys_only;
+    config.gameplay.practice_hand =
+      self.practice_hand;
+    config
+      .gameplay
+      .global_transpose_semitones =
+      self.global_transpose;
+    config
+      .gameplay
+      .tutorial_only_advance_on_correct_note =
+      self
+        .tutorial_options
+        .only_advance_on_correct_note;
+    config
+      .gameplay
+      .tutorial_play_bad_notes_out_loud =
+      self
+        .tutorial_options
+        .play_bad_notes_out_loud;
+    config.gameplay.left_hand_volume =
+      self.left_hand_volume;
+    config.gameplay.right_hand_volume =
+      self.right_hand_volume;
+    config.gameplay.playback_speed =
+      self
+        .drill_restore_speed
+        .unwrap_or(self.playback_speed);
+    config
+  }
+
+  fn auto_save_settings(
+    &mut self,
+    now: Instant
+  ) {
+    let interval = self
+      .config
+      .app
+      .auto_save_interval_seconds;
+    if interval == 0
+      || !self.settings_dirty
+      || now.duration_since(
+        self.last_auto_save
+      ) < Duration::from_secs(
+        interval
+      )
+    {
+      return;
+    }
+
+    self.last_auto_save = now;
+    let config =
+      self.persisted_config();
+    match config::write_default(
+      &self.config_path,
+      &config
+    ) {
+      | Ok(()) => {
+        self.settings_dirty = false;
+        info!(path = %self.config_path.display(), "settings auto-saved");
+      }
+      | Err(error) => {
+        warn!(path = %self.config_path.display(), %error, "settings auto-save failed");
+        self.push_activity(format!(
+          "Auto-save failed: {error}"
+        ));
+      }
+    }
+  }
+
+  fn start_latency_calibration(
+    &mut self
+  ) {
+    self.stop_playback();
+    self.latency_calibration =
+      Some(LatencyCalibration {
+        started_at:     Instant::now()
+          + Duration::from_millis(
+            CALIBRATION_CLICK_INTERVAL_MS
+          ),
+        clicks_played:  0,
+        tap_offsets_ms: Vec::new()
+      });
+    self.push_activity(format!(
+      "Latency calibration: tap any \
+       mapped key on each of the \
+       {CALIBRATION_CLICKS} clicks."
+    ));
+  }
+
+  fn tick_latency_calibration(
+    &mut self,
+    now: Instant
+  ) {
+    let Some(calibration) =
+      self.latency_calibration.as_mut()
+    else {
+      return;
+    };
+    let Some(elapsed) = now
+      .checked_duration_since(
+        calibration.started_at
+      )
+    else {
+      return;
+    };
+    let elapsed_ms =
+      elapsed.as_millis() as u64;
+
+    if calibration.clicks_played
+      < CALIBRATION_CLICKS
+      && elapsed_ms
+        >= u64::from(
+          calibration.clicks_played
+        ) * CALIBRATION_CLICK_INTERVAL_MS
+    {
+      self.audio.play_metronome_tick(
+        calibration.clicks_played == 0,
+        MetronomeNoteOff::Cut,
+        CALIBRATION_CLICK_INTERVAL_MS
+      );
+      calibration.clicks_played += 1;
+    }
+
+    if elapsed_ms
+      < u64::from(CALIBRATION_CLICKS)
+        * CALIBRATION_CLICK_INTERVAL_MS
+    {
+      return;
+    }
+
+    let Some(calibration) =
+      self.latency_calibration.take()
+    else {
+      return;
+    };
+    match median_offset_ms(
+      calibration.tap_offsets_ms
+    ) {
+      | Some(offset_ms) => {
+        let offset_ms = offset_ms
+          .clamp(-1_000, 1_000);
+        self
+          .config
+          .audio
+          .input_offset_ms = offset_ms;
+        self.settings_dirty = true;
+        self.push_activity(format!(
+          "Latency calibration: input \
+           offset set to {offset_ms} \
+           ms."
+        ));
+        info!(
+          offset_ms,
+          "latency calibration \
+           finished"
+        );
+      }
+      | None => {
+        self.push_activity(
+          "Latency calibration \
+           cancelled: no taps \
+           recorded."
+            .to_string()
+        );
+      }
+    }
+  }
+
+  fn record_calibration_tap(
+    &mut self
+  ) -> bool {
+    let Some(calibration) =
+      self.latency_calibration.as_mut()
+    else {
+      return false;
+    };
+
+    let elapsed_ms = Instant::now()
+      .checked_duration_since(
+        calibration.started_at
+      )
+      .map_or(0, |elapsed| {
+        elapsed.as_millis() as i64
+      });
+    calibration.tap_offsets_ms.push(
+      calibration_tap_offset_ms(
+        elapsed_ms,
+        CALIBRATION_CLICK_INTERVAL_MS
+          as i64
+      )
+    );
+    true
+  }
+
+  fn visual_cursor_seconds(
+    &self,
+    elapsed: f32
+  ) -> f32 {
+    elapsed
+      - self
+        .config
+        .audio
+        .visual_offset_ms as f32
+        / 1000.0
+  }
+
+  fn set_playback_speed(
+    &mut self,
+    speed: f32
+  ) {
+    let speed = speed.clamp(
+      MIN_PLAYBACK_SPEED,
+      MAX_PLAYBACK_SPEED
+    );
+    let now = Instant::now();
+    if let Some(playback) =
+      self.playback.as_mut()
+      && playback.mode
+        != PlayMode::Tutorial
+      && !playback.paused
+    {
+      let elapsed = playback_elapsed(
+        playback.started_at,
+        now,
+        self.playback_speed
+      );
+      playback.started_at =
+        playback_started_at(
+          now, elapsed, speed
+        );
+    }
+    self.playback_speed = speed;
+    debug!(
+      speed,
+      "playback speed updated"
+    );
+  }
+
+  fn set_playback_paused(
+    &mut self,
+    paused: bool
+  ) {
+    let Some(playback) =
+      self.playback.as_mut()
+    else {
+      return;
+    };
+    if playback.mode
+      == PlayMode::Tutorial
+      || playback.paused == paused
+    {
+      return;
+    }
+
+    playback.paused = paused;
+    if !paused {
+      // The cursor lags the clock by
+      // the visual offset; resume from
+      // the unshifted elapsed time.
+      let elapsed = playback
+        .cursor_seconds
+        + self
+          .config
+          .audio
+          .visual_offset_ms
+          as f32
+          / 1000.0;
+      playback.started_at =
+        playback_started_at(
+          Instant::now(),
+          elapsed,
+          self.playback_speed
+        );
+    }
+    self.push_activity(
+      if paused {
+        "Playback paused."
+      } else {
+        "Playback resumed."
+      }
+      .to_string()
+    );
+    info!(
+      paused,
+      "playback pause toggled"
+    );
+  }
+
+  fn set_sustain(
+    &mut self,
+    down: bool
+  ) {
+    if self.sustain_down == down {
+      return;
+    }
+    self.sustain_down = down;
+    self.audio.set_sustain(down);
+    trace!(down, "sustain pedal");
+  }
+
+  fn stop_playback(&mut self) {
+    self.auto_advance_at = None;
+    self.audio.stop_all_notes();
+    if self.playback.is_some() {
+      self.playback = None;
+      self.push_activity(
+        "Playback stopped.".to_string()
+      );
+      info!("playback stopped");
+    }
+    self
+      .apply_pending_binding_rebuild();
+  }
+
+  fn apply_pending_binding_rebuild(
+    &mut self
+  ) {
+    if !self.binding_rebuild_pending {
+      return;
+    }
+
+    self.release_held_notes();
+    self.flashed_notes.clear();
+    self.rebuild_song_context();
+    self.push_activity(
+      "Applied deferred binding \
+       optimization change."
+        .to_string()
+    );
+    info!(
+      "deferred binding rebuild \
+       applied"
+    );
+  }
+
+  fn send_sync_update(
+    &self,
+    playback: &PlaybackState,
+    prepared: &PreparedSong
+  ) {
+    let Some(socket) =
+      self.sync_socket.as_ref()
+    else {
+      return;
+    };
+
+    let active_notes = self
+      .held_notes
+      .keys()
+      .chain(self.flashed_notes.keys())
+      .copied()
+      .collect::<BTreeSet<u8>>();
+    let message = sync_message(
+      playback.cursor_seconds,
+      prepared.beat_at_seconds(
+        playback.cursor_seconds
+      ),
+      &active_notes
+    );
+    let _ =
+      socket.send(message.as_bytes());
+  }
+
+  fn midi_clock_seconds(
+    &self,
+    now: Instant
+  ) -> Option<f32> {
+    let prepared =
+      self.prepared_song.as_ref()?;
+    let beats = self
+      .midi_clock
+      .as_ref()?
+      .state
+      .lock()
+      .ok()?
+      .beats(now)?;
+    Some(
+      prepared.seconds_at_beat(beats)
+    )
+  }
+
+  fn apply_midi_clock_transport(
+    &mut self
+  ) {
+    let Some(clock) =
+      self.midi_clock.as_ref()
+    else {
+      return;
+    };
+    let Ok(mut state) =
+      clock.state.lock()
+    else {
+      return;
+    };
+    let transport =
+      state.take_transport();
+    let bpm = state.bpm();
+    drop(state);
+
+    if !self
+      .config
+      .midi_sync
+      .follow_transport
+    {
+      return;
+    }
+
+    for message in transport {
+      match message {
+        | ClockTransport::Start
+        | ClockTransport::Continue => {
+          self.push_activity(format!(
+            "MIDI clock {} ({:.0} \
+             BPM).",
+            if message
+              == ClockTransport::Start
+            {
+              "start"
+            } else {
+              "continue"
+            },
+            bpm.unwrap_or(0.0)
+          ));
+          if self.playback.is_none() {
+            self.start_playback();
+          }
+          if let Some(seconds) = self
+            .midi_clock_seconds(
+              Instant::now()
+            )
+          {
+            self.seek_to(seconds);
+          }
+        }
+        | ClockTransport::Stop => {
+          self.stop_playback();
+        }
+      }
+    }
+  }
+
+  fn handle_tick(
+    &mut self,
+    now: Instant
+  ) {
+    self.prune_flashes(now);
+    self.restore_drill_speed();
+    self.auto_save_settings(now);
+    self.audio.collect_live_recording();
+
+    match self
+      .audio
+      .recover_lost_stream()
+    {
+      | Some(Ok(device_name)) => {
+        self.push_activity(format!(
+          "Audio device lost; \
+           reconnected to \
+           {device_name}."
+        ));
+      }
+      | Some(Err(error)) => {
+        self.push_activity(format!(
+          "Audio device lost; \
+           reconnect failed: {error}; \
+           retrying in the background."
+        ));
+      }
+      | None => {}
+    }
+
+    if self
+      .practice_session
+      .as_ref()
+      .is_some_and(|session| {
+        now >= session.ends_at
+      })
+    {
+      self.finish_practice_session();
+      return;
+    }
+
+    if let Some(advance_at) =
+      self.auto_advance_at
+      && now >= advance_at
+    {
+      self.auto_advance_at = None;
+      self.select_next_song();
+      self.start_playback();
+      return;
+    }
+
+    self.tick_latency_calibration(now);
+    self.apply_midi_clock_transport();
+
+    let Some(mut playback) =
+      self.playback.take()
+    else {
+      return;
+    };
+
+    let Some(prepared) =
+      self.prepared_song.clone()
+    else {
+      return;
+    };
+
+    if playback.paused {
+      self.playback = Some(playback);
+      return;
+    }
+
+    if playback.mode
+      != PlayMode::Tutorial
+      && let Some(started_at) = self
+        .midi_clock_seconds(now)
+        .and_then(|seconds| {
+          now.checked_sub(
+            Duration::from_secs_f32(
+              seconds
+                / self.playback_speed
+            )
+          )
+        })
+    {
+      playback.started_at = started_at;
+    }
+
+    let mut keep_running = true;
+
+    match playback.mode {
+      | PlayMode::Timer => {
+        let elapsed = playback_elapsed(
+          playback.started_at,
+          now,
+          self.playback_speed
+        );
+        playback.cursor_seconds = self
+          .visual_cursor_seconds(
+            elapsed
+          );
+
+        let ghost_volume = self
+          .config
+          .gameplay
+          .timer_ghost_volume;
+        while let Some(event) = prepared
+          .events
+          .get(
+            playback.next_event_index
+          )
+          .filter(|event| {
+            event.at_seconds <= elapsed
+          })
+        {
+          if ghost_volume > 0.0 {
+            self.sound_event(
+              event,
+              ghost_volume
+            );
+          }
+          playback.next_event_index +=
+            1;
+        }
+
+        if elapsed
+          >= playback
+            .next_metronome_beat_s
+        {
+          let accent = self
+            .config
+            .metronome
+            .is_accent(
+              playback
+                .next_metronome_index,
+              self
+                .selected_beats_per_bar(
+                )
+            );
+          if self.metronome_enabled() {
+            self
+              .audio
+              .play_metronome_tick(
+              accent,
+              self
+                .config
+                .metronome
+                .note_off,
+              ((prepared
+                .seconds_at_beat(
+                (playback
+                  .next_metronome_index
+                  + 1)
+                  as f32
+              ) - prepared
+                .seconds_at_beat(
+                playback
+                  .next_metronome_index
+                  as f32
+              )) / self.playback_speed
+                * 1000.0)
+                as u64
+            );
+          }
+
+          if prepared.beat_seconds > 0.0
+          {
+            let next_index = (prepared
+              .beat_at_seconds(elapsed)
+              .floor()
+              .max(0.0)
+              as u64
+              + 1)
+              .max(
+                playback
+                  .next_metronome_index
+                  + 1
+              );
+            playback
+              .next_metronome_index =
+              next_index;
+            playback
+              .next_metronome_beat_s =
+              prepared.seconds_at_beat(
+                next_index as f32
+              );
+          } else {
+            playback
+              .next_metronome_beat_s =
+              f32::INFINITY;
+          }
+        }
+
+        if elapsed
+          > prepared.duration_seconds
+            + 1.2
+        {
+          playback.score.missed_notes =
+            playback
+              .score
+              .expected_notes
+              .saturating_sub(
+                playback
+                  .score
+                  .hit_notes
+              );
+
+          self.last_timer_score = Some(
+            playback.score.clone()
+          );
+          self.worst_section =
+            worst_practice_window(
+              &prepared,
+              &playback
+                .matched_note_indices,
+              self
+                .selected_beats_per_bar(
+                )
+            );
+
+          self.push_activity(format!(
+            "Timer complete: {:.1}% \
+             accuracy (perfect {} \
+             good {} wrong {} missed \
+             {}{}).",
+            playback
+              .score
+              .accuracy_percent(),
+            playback.score.perfect_hits,
+            playback.score.good_hits,
+            playback.score.wrong_notes,
+            playback.score.missed_notes,
+            playback
+              .score
+              .octave_summary(),
+          ));
+
+          keep_running = false;
+          info!(
+            accuracy = playback
+              .score
+              .accuracy_percent(),
+            "timer mode finished"
+          );
+        }
+      }
+      | PlayMode::Autoplay => {
+        let mut elapsed =
+          playback_elapsed(
+            playback.started_at,
+            now,
+            self.playback_speed
+          );
+
+        if let Some(window) =
+          playback.loop_window
+          && elapsed
+            >= window.end_seconds
+        {
+          playback.started_at =
+            playback_started_at(
+              now,
+              window.start_seconds,
+              self.playback_speed
+            );
+          playback.next_event_index =
+            first_event_index_at(
+              &prepared,
+              window.start_seconds
+            );
+          playback.loops_completed += 1;
+          elapsed =
+            window.start_seconds;
+        }
+        playback.cursor_seconds = self
+          .visual_cursor_seconds(
+            elapsed
+          );
+
+        while let Some(event) = prepared
+          .events
+          .get(
+            playback.next_event_index
+          )
+          .cloned()
+        {
+          if event.at_seconds > elapsed
+          {
+            break;
+          }
+          if playback
+            .loop_window
+            .is_some_and(|window| {
+              event.at_seconds
+                >= window.end_seconds
+            })
+          {
+            break;
+          }
+
+          self.trigger_event(&event);
+          playback.next_event_index +=
+            1;
+        }
+
+        if elapsed
+          > prepared.duration_seconds
+            + 0.8
+        {
+          self.push_activity(
+            "Auto Play complete."
+              .to_string()
+          );
+          keep_running = false;
+          info!("autoplay finished");
+        }
+      }
+      | PlayMode::Tutorial => {
+        if let Some(window) =
+          playback.loop_window
+          && prepared
+            .events
+            .get(
+              playback
+                .tutorial_event_index
+            )
+            .is_none_or(|event| {
+              event.at_seconds
+                >= window.end_seconds
+            })
+        {
+          playback
+            .tutorial_event_index =
+            first_event_index_at(
+              &prepared,
+              window.start_seconds
+            );
+          playback
+            .tutorial_matched
+            .clear();
+          playback.loops_completed += 1;
+          self.push_activity(format!(
+            "Drill loop {} complete.",
+            playback.loops_completed
+          ));
+        }
+
+        if let Some(event) = prepared
+          .events
+          .get(
+            playback
+              .tutorial_event_index
+          )
+          .cloned()
+        {
+          playback.cursor_seconds =
+            event.at_seconds;
+        } else {
+          playback.cursor_seconds =
+            prepared.duration_seconds;
+          self.push_activity(
+            "Tutorial complete."
+              .to_string()
+          );
+          keep_running = false;
+          info!("tutorial finished");
+        }
+      }
+    }
+
+    self.send_sync_update(
+      &playback, &prepared
+    );
+
+    if keep_running {
+      self.playback = Some(playback);
+    } else {
+      self.finish_playback_run(
+        &playback, now
+      );
+    }
+  }
+
+  fn finish_playback_run(
+    &mut self,
+    playback: &PlaybackState,
+    now: Instant
+  ) {
+    self.record_session_run(
+      (playback.mode
+        == PlayMode::Timer)
+        .then(|| {
+          playback
+            .score
+            .accuracy_percent()
+        })
+    );
+    self
+      .apply_pending_binding_rebuild();
+
+    if self.config.gameplay.auto_advance
+    {
+      let delay = Duration::from_millis(
+        self
+          .config
+          .gameplay
+          .auto_advance_delay_ms
+      );
+      self.auto_advance_at =
+        Some(now + delay);
+      self.push_activity(format!(
+        "Advancing to the next song \
+         in {:.1}s.",
+        delay.as_secs_f32()
+      ));
+    }
+  }
+
+  fn process_note_input(
+    &mut self,
+    midi_note: u8,
+    now: Instant
+  ) -> bool {
+    if self.record_calibration_tap() {
+      return false;
+    }
+
+    let mut play_out_loud = true;
+
+    let Some(mut playback) =
+      self.playback.take()
+    else {
+      return play_out_loud;
+    };
+
+    let Some(prepared) =
+      self.prepared_song.clone()
+    else {
+      self.playback = Some(playback);
+      return play_out_loud;
+    };
+
+    if playback.paused {
+      self.playback = Some(playback);
+      return play_out_loud;
+    }
+
+    let mut keep_running = true;
+
+    match playback.mode {
+      | PlayMode::Timer => {
+        let elapsed = playback_elapsed(
+          playback.started_at,
+          now,
+          self.playback_speed
+        );
+        playback.cursor_seconds = self
+          .visual_cursor_seconds(
+            elapsed
+          );
+        let cursor = elapsed
+          - self
+            .config
+            .audio
+            .input_offset_ms
+            as f32
+            / 1000.0;
+
+        let ignore_octave = self
+          .config
+          .gameplay
+          .ignore_octave_errors;
+        let find_match = |kind| {
+          closest_timer_match(
+            &prepared.expected_notes,
+            cursor,
+            |index, expected| {
+              timer_match_kind(
+                self.song_input_note(
+                  expected.midi_note
+                ),
+                midi_note,
+                ignore_octave
+              ) == Some(kind)
+                && !playback
+                  .matched_note_indices
+                  .contains(&index)
+                && !playback
+                  .strict_missed_indices
+                  .contains(&index)
+            }
+          )
+        };
+        let exact_match =
+          find_match(TimerMatch::Exact);
+        let octave_match = exact_match
+          .is_none()
+          .then(|| {
+            find_match(
+              TimerMatch::Octave
+            )
+          })
+          .flatten();
+        let best_match =
+          exact_match.or(octave_match);
+
+        if let Some((index, delta)) =
+          best_match
+          && delta
+            > TIMER_PERFECT_SECONDS
+          && self
+            .config
+            .gameplay
+            .strict_perfect
+        {
+          playback
+            .strict_missed_indices
+            .insert(index);
+          playback
+            .score
+            .missed_notes += 1;
+          debug!(
+            midi_note,
+            delta,
+            "timer note outside \
+             strict perfect window"
+          );
+        } else if let Some((
+          index,
+          delta
+        )) = best_match
+        {
+          playback
+            .matched_note_indices
+            .insert(index);
+          playback.score.hit_notes += 1;
+          playback.score.hit_beats +=
+            prepared.expected_notes
+              [index]
+              .duration_beats;
+          playback
+            .score
+            .hit_offsets
+            .push(
+              cursor
+                - prepared
+                  .expected_notes
+                  [index]
+                  .at_seconds
+            );
+
+          if octave_match.is_some() {
+            playback.score.good_hits +=
+              1;
+            playback
+              .score
+              .octave_hits += 1;
+          } else if delta
+            <= TIMER_PERFECT_SECONDS
+          {
+            playback
+              .score
+              .perfect_hits += 1;
+          } else {
+            playback.score.good_hits +=
+              1;
+          }
+
+          debug!(
+            midi_note,
+            delta, "timer note matched"
+          );
+        } else {
+          playback.score.wrong_notes +=
+            1;
+          debug!(
+            midi_note,
+            "timer note missed"
+          );
+        }
+      }
+      | PlayMode::Tutorial => {
+        if let Some(event) = prepared
+          .events
+          .get(
+            playback
+              .tutorial_event_index
+          )
+          .cloned()
+        {
+          let expected_notes = event
+            .notes
+            .iter()
+            .filter_map(|note| {
+              self
+                .song_input_note(*note)
+            })
+            .collect::<HashSet<_>>();
+          let correct = expected_notes
+            .contains(&midi_note);
+
+          if correct {
+            playback
+              .tutorial_matched
+              .insert(midi_note);
+            playback
+              .tutorial_last_correct_at =
+              Some(now);
+
+            let expected_unique =
+              expected_notes.len();
+
+            if playback
+              .tutorial_matched
+              .len()
+              >= expected_unique
+            {
+              playback
+                .tutorial_event_index += 1;
+              playback
+                .tutorial_matched
+                .clear();
+            }
+          } else {
+            play_out_loud = self
+              .tutorial_options
+              .play_bad_notes_out_loud;
+
+            if within_wrong_note_grace(
+              playback
+                .tutorial_last_correct_at,
+              now,
+              self
+                .tutorial_options
+                .wrong_note_grace
+            ) {
+              debug!(
+                midi_note,
+                "tutorial wrong note \
+                 ignored within grace \
+                 period"
+              );
+            } else if self
+              .tutorial_options
+              .only_advance_on_correct_note
+            {
+              self.push_activity(format!(
+                "Tutorial expects: {}",
+                event
+                  .notes
+                  .iter()
+                  .map(|note| self
+                    .binding_label_for_song_note(
+                      *note
+                    ))
+                  .collect::<Vec<_>>()
+                  .join(" ")
+              ));
+            } else {
+              playback
+                .tutorial_event_index += 1;
+              playback
+                .tutorial_matched
+                .clear();
+            }
+          }
+
+          if playback
+            .tutorial_event_index
+            >= prepared.events.len()
+          {
+            keep_running = false;
+            self.push_activity(
+              "Tutorial complete."
+                .to_string()
+            );
+          }
+        }
+      }
+      | PlayMode::Autoplay => {
+        // Manual notes are allowed
+        // while autoplay runs.
+      }
+    }
+
+    if keep_running {
+      self.playback = Some(playback);
+    } else {
+      self.finish_playback_run(
+        &playback, now
+      );
+    }
+
+    play_out_loud
+  }
+
+  fn trigger_event(
+    &mut self,
+    event: &PreparedEvent
+  ) {
+    self.sound_event(event, 1.0);
+    for midi_note in &event.notes {
+      if let Some(input_note) =
+        self.song_input_note(*midi_note)
+      {
+        self.flash_note(
+          input_note,
+          FlashSource::Autoplay
+        );
+        self.set_focus_note(input_note);
+      } else {
+        self.flash_note(
+          *midi_note,
+          FlashSource::Autoplay
+        );
+        self.set_focus_note(*midi_note);
+      }
+    }
+  }
+
+  fn sound_event(
+    &mut self,
+    event: &PreparedEvent,
+    gain: f32
+  ) {
+    let velocity =
+      self.hand_scaled_velocity(event);
+    if velocity == 0 {
+      return;
+    }
+    let song_gain = self
+      .selected_song
+      .and_then(|index| {
+        self.songs.get(index)
+      })
+      .map_or(1.0, |loaded| {
+        loaded.song.meta.gain()
+      });
+    let channel_profile = event
+      .channel
+      .and_then(|channel| {
+        self
+          .config
+          .audio
+          .channel_profile_map
+          .get(&channel.to_string())
+          .cloned()
+      });
+    for (index, midi_note) in
+      event.notes.iter().enumerate()
+    {
+      let duration_ms = event
+        .note_duration_ms
+        .get(index)
+        .copied()
+        .unwrap_or(event.duration_ms);
+      let duration_ms =
+        (duration_ms as f32
+          / self.playback_speed)
+          .round() as u64;
+      let sounding_note = self
+        .globally_transposed(
+          *midi_note
+        );
+      let profile_name =
+        channel_profile
+          .clone()
+          .unwrap_or_else(|| {
+            self
+              .audio
+              .active_profile_name()
+              .to_string()
+          });
+      self
+        .audio
+        .play_note_on_profile_with_gain(
+          &profile_name,
+          sounding_note,
+          velocity,
+          duration_ms,
+          gain * song_gain
+        );
+    }
+  }
+
+  fn hand_scaled_velocity(
+    &self,
+    event: &PreparedEvent
+  ) -> u8 {
+    let multiplier = match event.hand {
+      | Some(Hand::Left) => {
+        self.left_hand_volume
+      }
+      | Some(Hand::Right) => {
+        self.right_hand_volume
+      }
+      | Some(Hand::Both) => {
+        self
+          .left_hand_volume
+          .max(self.right_hand_volume)
+      }
+      | None => 1.0
+    };
+
+    (f32::from(event.velocity)
+      * multiplier)
+      .round()
+      .clamp(0.0, 127.0) as u8
+  }
+
+  fn metronome_enabled(&self) -> bool {
+    let globally_enabled =
+      self.config.metronome.enabled;
+    self
+      .selected_song
+      .and_then(|index| {
+        self.songs.get(index)
+      })
+      .map_or(
+        globally_enabled,
+        |song| {
+          song
+            .song
+            .meta
+            .metronome
+            .resolve(globally_enabled)
+        }
+      )
+  }
+
+  fn selected_beats_per_bar(
+    &self
+  ) -> u8 {
+    self
+      .selected_song
+      .and_then(|index| {
+        self.songs.get(index)
+      })
+      .map(|song| {
+        song.song.meta.beats_per_bar
+      })
+      .filter(|beats| *beats > 0)
+      .unwrap_or(4)
+  }
+}
+
+fn first_event_index_at(
+  prepared: &PreparedSong,
+  at_seconds: f32
+) -> usize {
+  prepared.events.partition_point(
+    |event| {
+      event.at_seconds < at_seconds
+    }
+  )
+}
+
+fn worst_practice_window(
+  prepared: &PreparedSong,
+  matched_note_indices: &HashSet<usize>,
+  beats_per_bar: u8
+) -> Option<PracticeWindow> {
+  if prepared.beat_seconds <= 0.0 {
+    return None;
+  }
+  let bar_beats =
+    f32::from(beats_per_bar.max(1));
+  let bar_at = |seconds: f32| {
+    prepared.beat_at_seconds(seconds)
+      / bar_beats
+  };
+
+  let mut misses_by_bar =
+    BTreeMap::<usize, usize>::new();
+  for (index, expected) in prepared
+    .expected_notes
+    .iter()
+    .enumerate()
+  {
+    if matched_note_indices
+      .contains(&index)
+    {
+      continue;
+    }
+    let bar =
+      bar_at(expected.at_seconds)
+        .max(0.0) as usize;
+    *misses_by_bar
+      .entry(bar)
+      .or_default() += 1;
+  }
+
+  let (worst_bar, _) = misses_by_bar
+    .iter()
+    .max_by(|left, right| {
+      left
+        .1
+        .cmp(right.1)
+        .then(right.0.cmp(left.0))
+    })?;
+  let last_song_bar =
+    bar_at(prepared.duration_seconds)
+      .ceil()
+      .max(1.0) as usize
+      - 1;
+  let first_bar = worst_bar
+    .saturating_sub(DRILL_CONTEXT_BARS);
+  let last_bar = (worst_bar
+    + DRILL_CONTEXT_BARS)
+    .min(last_song_bar)
+    .max(first_bar);
+  let missed_notes = misses_by_bar
+    .range(first_bar..=last_bar)
+    .map(|(_, count)| count)
+    .sum();
+
+  Some(PracticeWindow {
+    start_seconds: prepared
+      .seconds_at_beat(
+        first_bar as f32 * bar_beats
+      )
+      .max(0.0),
+    end_seconds: prepared
+      .seconds_at_beat(
+        (last_bar + 1) as f32
+          * bar_beats
+      ),
+    first_bar: first_bar + 1,
+    last_bar: last_bar + 1,
+    missed_notes
+  })
+}
+
+#[derive(Debug, Default)]
+struct CliArgs {
+  config_path:     Option<PathBuf>,
+  normalize_songs: Vec<PathBuf>,
+  no_audio:        bool
+}
+
+fn configured_config_path(
+  cli: &CliArgs
+) -> PathBuf {
+  if let Some(path) = &cli.config_path {
+    return path.clone();
+  }
+
+  env::var("SYMFOSE_CONFIG")
+    .map(PathBuf::from)
+    .unwrap_or_else(|_| {
+      PathBuf::from(DEFAULT_CONFIG_PATH)
+    })
+}
+
+fn parse_cli_args(
+  mut args: impl Iterator<Item = String>
+) -> Result<CliArgs> {
+  let mut cli = CliArgs::default();
+
+  while let Some(arg) = args.next() {
+    if arg == "--config" {
+      let Some(value) = args.next()
+      else {
+        bail!(
+          "--config requires a path \
+           argument"
+        );
+      };
+      cli.config_path =
+        Some(PathBuf::from(value));
+    } else if let Some(value) =
+      arg.strip_prefix("--config=")
+    {
+      cli.config_path =
+        Some(PathBuf::from(value));
+    } else if arg == "--normalize-song"
+    {
+      let Some(value) = args.next()
+      else {
+        bail!(
+          "--normalize-song requires \
+           a song TOML path"
+        );
+      };
+      cli
+        .normalize_songs
+        .push(PathBuf::from(value));
+    } else if arg == "--no-audio" {
+      cli.no_audio = true;
+    } else {
+      bail!(
+        "unrecognized argument \
+         {arg}\n{CLI_USAGE}"
+      );
+    }
+  }
+
+  if cli
+    .config_path
+    .as_ref()
+    .is_some_and(|path| {
+      path.as_os_str().is_empty()
+    })
+  {
+    bail!(
+      "--config path must not be empty"
+    );
+  }
+
+  Ok(cli)
+}
+
+fn run_normalize_songs(
+  paths: &[PathBuf]
+) -> Result<()> {
+  let mut failures = 0usize;
+
+  for path in paths {
+    match songs::normalize_song_file(
+      path
+    ) {
+      | Ok(true) => {
+        println!(
+          "normalized {}",
+          path.display()
+        );
+      }
+      | Ok(false) => {
+        println!(
+          "already canonical {}",
+          path.display()
+        );
+      }
+      | Err(error) => {
+        failures += 1;
+        eprintln!(
+          "failed normalizing {}: \
+           {error:#}",
+          path.display()
+        );
+      }
+    }
+  }
+
+  if failures > 0 {
+    bail!(
+      "{failures} of {} song(s) \
+       failed normalization",
+      paths.len()
+    );
+  }
+
+  Ok(())
+}
+
+fn init_tracing(
+  config: &AppConfig
+) -> Result<WorkerGuard> {
+  let filter =
+    EnvFilter::try_from_default_env()
+      .unwrap_or_else(|_| {
+        EnvFilter::new(
+          config.logging.filter.clone()
+        )
+      });
+
+  std::fs::create_dir_all(Path::new(
+    &config.logging.directory
+  ))
+  .with_context(|| {
+    format!(
+      "failed creating {}",
+      config.logging.directory
+    )
+  })?;
+
+  let file_appender =
+    tracing_appender::rolling::daily(
+      &config.logging.directory,
+      "symfose"
+    );
+  let (file_writer, guard) =
+    tracing_appender::non_blocking(
+      file_appender
+    );
+
+  let console_layer =
+    tracing_fmt::layer()
+      .with_target(true)
+      .with_file(true)
+      .with_line_number(true)
+      .with_thread_ids(true)
+      .with_thread_names(true);
+
+  let file_layer = tracing_fmt::layer()
+    .with_ansi(false)
+    .with_target(true)
+    .with_file(true)
+    .with_line_number(true)
+    .with_thread_ids(true)
+    .with_thread_names(true)
+    .with_writer(file_writer);
+
+  tracing_subscriber::registry()
+    .with(filter)
+    .with(console_layer)
+    .with(file_layer)
+    .init();
+
+  Ok(guard)
+}
+
+fn compile_runtime_bindings(
+  config: &AppConfig
+) -> Result<RuntimeBindings> {
+  let note_bindings =
+    compile_note_bindings(
+      &config.effective_keybindings(),
+      config
+        .keyboard
+        .keybindings_on_conflict
+    )?;
+  let quit = compile_chord_set(
+    &config.control_bindings.quit,
+    "quit"
+  )?;
+  let list_songs = compile_chord_set(
+    &config.control_bindings.list_songs,
+    "list_songs"
+  )?;
+  let print_bindings =
+    compile_chord_set(
+      &config
+        .control_bindings
+        .print_bindings,
+      "print_bindings"
+    )?;
+  let play_song = compile_chord_set(
+    &config.control_bindings.play_song,
+    "play_song"
+  )?;
+  let go_to_song = compile_chord_set(
+    &config.control_bindings.go_to_song,
+    "go_to_song"
+  )?;
+  let global_transpose_up =
+    compile_chord_set(
+      &config
+        .control_bindings
+        .global_transpose_up,
+      "global_transpose_up"
+    )?;
+  let global_transpose_down =
+    compile_chord_set(
+      &config
+        .control_bindings
+        .global_transpose_down,
+      "global_transpose_down"
+    )?;
+  let sustain = compile_chord_set(
+    &config.control_bindings.sustain,
+    "sustain"
+  )?;
+  let note_velocity =
+    compile_velocity_map(
+      config.input.note_velocity,
+      &config.input.velocity_map
+    )?;
+
+  let mut note_to_chords =
+    BTreeMap::<u8, Vec<String>>::new();
+  for (chord, note) in &note_bindings {
+    note_to_chords
+      .entry(*note)
+      .or_default()
+      .push(chord.to_string());
+  }
+
+  for chords in
+    note_to_chords.values_mut()
+  {
+    chords.sort_unstable();
+  }
+
+  Ok(RuntimeBindings {
+    note_bindings,
+    note_to_chords,
+    quit,
+    list_songs,
+    print_bindings,
+    play_song,
+    go_to_song,
+    global_transpose_up,
+    global_transpose_down,
+    sustain,
+    note_velocity
+  })
+}
+
+fn extend_runtime_bindings(
+  bindings: &mut RuntimeBindings,
+  extra: &BTreeMap<String, u8>
+) {
+  for (chord_spec, midi_note) in extra {
+    let Ok(chord) =
+      crate::input::parse_chord(
+        chord_spec
+      )
+    else {
+      continue;
+    };
+    if bindings
+      .note_bindings
+      .contains_key(&chord)
+    {
+      continue;
+    }
+
+    let chords = bindings
+      .note_to_chords
+      .entry(*midi_note)
+      .or_default();
+    chords.push(chord.to_string());
+    chords.sort_unstable();
+    bindings
+      .note_bindings
+      .insert(chord, *midi_note);
+  }
+}
+
+fn apply_song_ergonomic_bindings(
+  bindings: &mut RuntimeBindings,
+  song: &SongFile,
+  layout: KeyboardLayout,
+  transpose_semitones: i8
+) {
+  let mut note_scores =
+    BTreeMap::<u8, usize>::new();
+  let mut cooccur =
+    BTreeMap::<(u8, u8), usize>::new();
+
+  for event in &song.events {
+    let mut notes = event
+      .notes
+      .iter()
+      .filter_map(|note| {
+        key_from_song_input(
+          *note,
+          transpose_semitones
+        )
+      })
+      .collect::<Vec<_>>();
+    notes.sort_unstable();
+    notes.dedup();
+    if notes.is_empty() {
+      continue;
+    }
+
+    let chord_bonus =
+      notes.len().saturating_sub(1) * 3;
+    for note in &notes {
+      *note_scores
+        .entry(*note)
+        .or_default() +=
+        1 + chord_bonus;
+    }
+
+    for left in 0..notes.len() {
+      for right in
+        (left + 1)..notes.len()
+      {
+        *cooccur
+          .entry((
+            notes[left],
+            notes[right]
+          ))
+          .or_default() += 1;
+      }
+    }
+  }
+
+  if note_scores.is_empty() {
+    return;
+  }
+
+  let mut ranked_notes = note_scores
+    .into_iter()
+    .collect::<Vec<_>>();
+  ranked_notes.sort_by(
+    |left, right| {
+      right
+        .1
+        .cmp(&left.1)
+        .then(left.0.cmp(&right.0))
+    }
+  );
+
+  let song_notes = ranked_notes
+    .iter()
+    .map(|(note, _)| *note)
+    .collect::<HashSet<_>>();
+  let mut next_map = HashMap::new();
+
+  let mut available_keys =
+    ergonomic_key_priority(layout);
+  if song_notes.len()
+    > available_keys.len()
+  {
+    let shifted =
+      ergonomic_key_priority(layout)
+        .into_iter()
         .map(|key| {
           format!("shift+{key}")
         })
-        .collect::<Vec<_>>();
-    available_keys.extend(shifted);
+        .collect::<Vec<_>>();
+    available_keys.extend(shifted);
+  }
+  let already_used =
+    HashSet::<String>::new();
+  available_keys.retain(|key| {
+    !already_used.contains(key)
+  });
+  if available_keys.is_empty() {
+    return;
+  }
+
+  let mut assigned_key =
+    BTreeMap::<u8, String>::new();
+  let median_note = ranked_notes
+    .iter()
+    .map(|(note, _)| *note as f32)
+    .sum::<f32>()
+    / ranked_notes.len() as f32;
+
+  for (note, score) in &ranked_notes {
+    let mut best: Option<(usize, i64)> =
+      None;
+
+    for (index, candidate_key) in
+      available_keys.iter().enumerate()
+    {
+      let mut cost = (index as i64) * 6;
+
+      if let Some((is_left, _)) =
+        ergonomic_key_meta(
+          candidate_key
+        )
+      {
+        let lower_note =
+          (*note as f32) <= median_note;
+        if lower_note != is_left {
+          cost += 20;
+        }
+      }
+
+      for (other_note, other_key) in
+        &assigned_key
+      {
+        let pair = if note < other_note
+        {
+          (*note, *other_note)
+        } else {
+          (*other_note, *note)
+        };
+        let weight = cooccur
+          .get(&pair)
+          .copied()
+          .unwrap_or(0)
+          as i64;
+        if weight == 0 {
+          continue;
+        }
+
+        let candidate_meta =
+          ergonomic_key_meta(
+            candidate_key
+          );
+        let other_meta =
+          ergonomic_key_meta(other_key);
+
+        match (
+          candidate_meta,
+          other_meta
+        ) {
+          | (
+            Some((
+              cand_left,
+              cand_finger
+            )),
+            Some((
+              other_left,
+              other_finger
+            ))
+          ) => {
+            if cand_finger
+              == other_finger
+            {
+              cost += 10_000 * weight;
+            } else if cand_left
+              == other_left
+            {
+              cost += 700 * weight;
+            } else {
+              cost -= 80 * weight;
+            }
+          }
+          | _ => {
+            cost += 120 * weight;
+          }
+        }
+      }
+
+      match best {
+        | Some((_, best_cost))
+          if cost >= best_cost => {}
+        | _ => {
+          best = Some((index, cost));
+        }
+      }
+    }
+
+    let Some((chosen_index, _)) = best
+    else {
+      continue;
+    };
+    let key = available_keys
+      .remove(chosen_index);
+
+    let Ok(chord) =
+      crate::input::parse_chord(&key)
+    else {
+      continue;
+    };
+    next_map.insert(chord, *note);
+    assigned_key.insert(*note, key);
+
+    trace!(
+      midi_note = note,
+      score,
+      mapped_key = assigned_key
+        .get(note)
+        .cloned()
+        .unwrap_or_default(),
+      "song ergonomic binding assigned"
+    );
+  }
+
+  let mut note_to_chords =
+    BTreeMap::<u8, Vec<String>>::new();
+  for (chord, note) in &next_map {
+    note_to_chords
+      .entry(*note)
+      .or_default()
+      .push(chord.to_string());
+  }
+
+  for chords in
+    note_to_chords.values_mut()
+  {
+    chords.sort_unstable();
+  }
+
+  bindings.note_bindings = next_map;
+  bindings.note_to_chords =
+    note_to_chords;
+
+  info!(
+    song_notes = song_notes.len(),
+    mapped_notes =
+      bindings.note_to_chords.len(),
+    assigned_song_notes =
+      assigned_key.len(),
+    "applied ergonomic bindings for \
+     selected song"
+  );
+}
+
+fn ergonomic_key_priority(
+  layout: KeyboardLayout
+) -> Vec<String> {
+  match layout {
+    | KeyboardLayout::Ansi104 => {
+      vec![
+        // Home row.
+        "f", "j", "d", "k", "s", "l",
+        "a", ";", "g", "h",
+        // Around home.
+        "r", "u", "e", "i", "w", "o",
+        "q", "p", "v", "n", "c", "m",
+        "x", ",", "z", ".", "t", "y",
+        "b", "/",
+        // Far punctuation.
+        "'", "[", "]", "\\", "`", "-",
+        "=",
+        // Number row last.
+        "5", "6", "4", "7", "3", "8",
+        "2", "9", "1", "0",
+      ]
+      .into_iter()
+      .map(str::to_string)
+      .collect()
+    }
+  }
+}
+
+fn ergonomic_key_meta(
+  key: &str
+) -> Option<(bool, u8)> {
+  let base_key = key
+    .rsplit('+')
+    .next()
+    .unwrap_or(key);
+  let (is_left, finger) = match base_key
+  {
+    | "`" | "1" | "q" | "a" | "z" => {
+      (true, 1)
+    }
+    | "2" | "w" | "s" | "x" => {
+      (true, 2)
+    }
+    | "3" | "e" | "d" | "c" => {
+      (true, 3)
+    }
+    | "4" | "5" | "r" | "t" | "f"
+    | "g" | "v" | "b" => (true, 4),
+    | "6" | "7" | "y" | "u" | "h"
+    | "j" | "n" | "m" => (false, 7),
+    | "8" | "i" | "k" | "," => {
+      (false, 8)
+    }
+    | "9" | "o" | "l" | "." => {
+      (false, 9)
+    }
+    | "0" | "p" | ";" | "/" | "-"
+    | "=" | "[" | "]" | "\\"
+    | "'" => (false, 10),
+    | _ => return None
+  };
+
+  Some((is_left, finger))
+}
+
+fn key_from_song_input(
+  song_note: u8,
+  semitones: i8
+) -> Option<u8> {
+  let shifted = i16::from(song_note)
+    + i16::from(semitones);
+  if (0..=127).contains(&shifted) {
+    Some(shifted as u8)
+  } else {
+    None
+  }
+}
+
+fn prepare_song_for_bindings(
+  source_song: &SongFile,
+  bindings: &RuntimeBindings,
+  transpose_to_fit: bool,
+  forced_transpose: Option<i8>,
+  gameplay: &GameplayConfig
+) -> (Option<PreparedSong>, i8, Vec<u8>)
+{
+  let out_of_range = gameplay
+    .out_of_range_transposed_notes;
+
+  let available_notes = bindings
+    .note_to_chords
+    .keys()
+    .copied()
+    .collect::<HashSet<_>>();
+
+  let transpose = forced_transpose
+    .unwrap_or_else(|| {
+      if transpose_to_fit {
+        choose_transpose_for_fit(
+          source_song,
+          &available_notes,
+          gameplay.transpose_strategy
+        )
+      } else {
+        0
+      }
+    });
+
+  let mut prepared = prepare_song(
+    source_song,
+    gameplay.trim_leading_silence
+  );
+  if gameplay.cut_overlapping_same_pitch
+  {
+    cut_same_pitch_overlaps(
+      &mut prepared.events
+    );
+  }
+  let adjusted = fit_transposed_notes(
+    &mut prepared,
+    transpose,
+    out_of_range
+  );
+  if adjusted > 0 {
+    warn!(
+      adjusted,
+      transpose,
+      policy = ?out_of_range,
+      "song notes fell outside the \
+       MIDI range after transpose"
+    );
+  }
+  let mut missing = prepared
+    .expected_notes
+    .iter()
+    .filter_map(|entry| {
+      key_from_song_input(
+        entry.midi_note,
+        transpose
+      )
+    })
+    .filter(|note| {
+      !available_notes.contains(note)
+    })
+    .collect::<Vec<_>>();
+  missing.sort_unstable();
+  missing.dedup();
+
+  (Some(prepared), transpose, missing)
+}
+
+fn fit_transposed_notes(
+  prepared: &mut PreparedSong,
+  transpose: i8,
+  policy: NoteRangePolicy
+) -> usize {
+  let fitted = |note: u8| {
+    let key = policy.fit(
+      i16::from(note)
+        + i16::from(transpose),
+      0,
+      127
+    )?;
+    u8::try_from(
+      i16::from(key)
+        - i16::from(transpose)
+    )
+    .ok()
+  };
+  let in_range = |note: u8| {
+    key_from_song_input(note, transpose)
+      .is_some()
+  };
+
+  let mut adjusted = 0usize;
+  for event in &mut prepared.events {
+    let mut index = 0;
+    while index < event.notes.len() {
+      let note = event.notes[index];
+      if in_range(note) {
+        index += 1;
+        continue;
+      }
+
+      adjusted += 1;
+      match fitted(note) {
+        | Some(note) => {
+          event.notes[index] = note;
+          index += 1;
+        }
+        | None => {
+          event.notes.remove(index);
+          if index
+            < event
+              .note_duration_ms
+              .len()
+          {
+            event
+              .note_duration_ms
+              .remove(index);
+          }
+          if index
+            < event.fingering.len()
+          {
+            event
+              .fingering
+              .remove(index);
+          }
+        }
+      }
+    }
+  }
+  prepared.events.retain(|event| {
+    !event.notes.is_empty()
+  });
+
+  prepared.expected_notes.retain_mut(
+    |expected| {
+      let Some(note) =
+        fitted(expected.midi_note)
+      else {
+        return false;
+      };
+      expected.midi_note = note;
+      true
+    }
+  );
+
+  adjusted
+}
+
+fn notes_outside_profile_range(
+  song: &SongFile,
+  profile: &SoundFontProfile,
+  transpose: i8
+) -> Vec<u8> {
+  song
+    .events
+    .iter()
+    .flat_map(|event| {
+      event.notes.iter().copied()
+    })
+    .collect::<BTreeSet<u8>>()
+    .into_iter()
+    .filter(|note| {
+      key_from_song_input(
+        *note, transpose
+      )
+      .and_then(|sounding| {
+        profile
+          .fit_note_to_range(sounding)
+      })
+      .is_none()
+    })
+    .collect()
+}
+
+fn practice_hand_song(
+  song: &SongFile,
+  practice_hand: PracticeHand,
+  shared_notes: SharedHandNotes
+) -> Cow<'_, SongFile> {
+  if practice_hand == PracticeHand::Both
+  {
+    return Cow::Borrowed(song);
+  }
+
+  let mut filtered = song.clone();
+  filtered.events.retain(|event| {
+    match (event.hand, practice_hand) {
+      | (None, _) => true,
+      | (Some(Hand::Left), hand) => {
+        hand == PracticeHand::Left
+      }
+      | (Some(Hand::Right), hand) => {
+        hand == PracticeHand::Right
+      }
+      | (Some(Hand::Both), hand) => {
+        match shared_notes {
+          | SharedHandNotes::IncludeInBoth => true,
+          | SharedHandNotes::MelodyHandOnly => {
+            hand == PracticeHand::Right
+          }
+          | SharedHandNotes::Skip => false
+        }
+      }
+    }
+  });
+  Cow::Owned(filtered)
+}
+
+fn choose_transpose_for_fit(
+  song: &SongFile,
+  available_notes: &HashSet<u8>,
+  strategy: TransposeStrategy
+) -> i8 {
+  let unique_notes = song
+    .events
+    .iter()
+    .flat_map(|event| {
+      event.notes.iter()
+    })
+    .copied()
+    .collect::<HashSet<_>>();
+
+  if unique_notes.is_empty() {
+    return 0;
+  }
+
+  let shifts = [
+    -48, -36, -24, -12, 0, 12, 24, 36,
+    48
+  ];
+
+  let mut all_notes = song
+    .events
+    .iter()
+    .flat_map(|event| {
+      event.notes.iter().copied()
+    })
+    .collect::<Vec<_>>();
+  all_notes.sort_unstable();
+  let median = i16::from(
+    all_notes[all_notes.len() / 2]
+  );
+  let distance_from_middle_c =
+    |shift: i16| {
+      match strategy {
+        | TransposeStrategy::MaxCoverage => 0,
+        | TransposeStrategy::CenterRange => {
+          (median + shift - 60).abs()
+        }
+      }
+    };
+
+  let mut best_shift = 0i8;
+  let mut best_score = 0usize;
+
+  for shift in shifts {
+    let mut score = 0usize;
+    for note in &unique_notes {
+      let shifted =
+        i16::from(*note) + shift;
+      if !(0..=127).contains(&shifted) {
+        continue;
+      }
+      if available_notes
+        .contains(&(shifted as u8))
+      {
+        score += 1;
+      }
+    }
+
+    let shift_abs = shift.abs() as i16;
+    let best_abs =
+      i16::from(best_shift).abs();
+    let distance =
+      distance_from_middle_c(shift);
+    let best_distance =
+      distance_from_middle_c(
+        i16::from(best_shift)
+      );
+    let is_better = score > best_score
+      || (score == best_score
+        && (distance < best_distance
+          || (distance
+            == best_distance
+            && shift_abs < best_abs)));
+    if is_better {
+      best_score = score;
+      best_shift = shift as i8;
+    }
+  }
+
+  best_shift
+}
+
+fn cut_same_pitch_overlaps(
+  events: &mut [PreparedEvent]
+) {
+  let mut last_onset =
+    HashMap::<u8, (usize, usize)>::new(
+    );
+
+  for event_index in 0..events.len() {
+    let at_seconds =
+      events[event_index].at_seconds;
+    for note_index in
+      0..events[event_index].notes.len()
+    {
+      let note = events[event_index]
+        .notes[note_index];
+      if let Some(&(
+        previous_event,
+        previous_note
+      )) = last_onset.get(&note)
+      {
+        let previous =
+          &mut events[previous_event];
+        let gap_ms = ((at_seconds
+          - previous.at_seconds)
+          * 1000.0)
+          .floor()
+          as u64;
+        let held_ms = &mut previous
+          .note_duration_ms
+          [previous_note];
+        if gap_ms > 0
+          && *held_ms > gap_ms
+        {
+          *held_ms = gap_ms;
+        }
+      }
+      last_onset.insert(
+        note,
+        (event_index, note_index)
+      );
+    }
+  }
+}
+
+fn prepare_song(
+  song: &SongFile,
+  trim_leading_silence: bool
+) -> PreparedSong {
+  let beat_seconds =
+    60.0 / song.meta.tempo_bpm.max(1.0);
+
+  let song_end_beats = song
+    .events
+    .iter()
+    .map(|event| {
+      event.at_beats.max(0.0)
+        + event.duration_beats.max(0.0)
+    })
+    .fold(0.0_f32, f32::max);
+
+  let mut placements = Vec::new();
+  let mut beat_times = Vec::new();
+  let mut segment_start_seconds = 0.0;
+  for segment in
+    section_timeline(song, beat_seconds)
+  {
+    let tempo_mapped = segment
+      .follows_tempo_map
+      && !song.tempo_map.is_empty();
+    let segment_offset =
+      |beats: f32| {
+        if tempo_mapped {
+          song.beats_to_seconds(beats)
+            - song.beats_to_seconds(
+              segment.start_beats
+            )
+        } else {
+          (beats - segment.start_beats)
+            * segment.beat_seconds
+        }
+      };
+    let segment_seconds =
+      segment_offset(segment.end_beats);
+    let last_beat = segment
+      .end_beats
+      .min(song_end_beats.ceil() + 1.0);
+    for _ in 0..segment.repeats {
+      let mut beat =
+        segment.start_beats.ceil();
+      while beat < last_beat {
+        beat_times.push(
+          segment_start_seconds
+            + segment_offset(beat)
+        );
+        beat += 1.0;
+      }
+      for event in &song.events {
+        let at_beats =
+          event.at_beats.max(0.0);
+        if event.notes.is_empty()
+          || at_beats
+            < segment.start_beats
+          || at_beats
+            >= segment.end_beats
+        {
+          continue;
+        }
+
+        let event_seconds =
+          segment_offset(at_beats);
+        let duration_seconds =
+          if event.duration_beats > 0.0
+          {
+            (segment_offset(
+              at_beats
+                + event.duration_beats
+            ) - event_seconds)
+              .max(0.04)
+          } else {
+            0.32
+          };
+        placements.push((
+          event,
+          segment_start_seconds
+            + event_seconds,
+          duration_seconds
+        ));
+      }
+      segment_start_seconds +=
+        segment_seconds;
+    }
+  }
+  placements.sort_by(|left, right| {
+    left.1.total_cmp(&right.1)
+  });
+
+  let trim_offset_seconds =
+    if trim_leading_silence {
+      placements
+        .first()
+        .map_or(0.0, |placement| {
+          placement.1
+        })
+    } else {
+      0.0
+    };
+
+  for time in &mut beat_times {
+    *time -= trim_offset_seconds;
+  }
+
+  let mut expected_notes = Vec::new();
+  let mut prepared_events = Vec::new();
+
+  let mut duration_seconds: f32 = 0.0;
+
+  for (
+    event,
+    at_seconds,
+    duration_seconds_for_event
+  ) in placements
+  {
+    let at_seconds = (at_seconds
+      - trim_offset_seconds)
+      .max(0.0);
+
+    let duration_ms =
+      (duration_seconds_for_event
+        * 1000.0)
+        .round()
+        .max(45.0) as u64;
+
+    let velocity = event
+      .velocity
+      .unwrap_or(
+        song.meta.default_velocity
+      )
+      .clamp(1, 127);
+
+    for midi_note in &event.notes {
+      expected_notes.push(
+        ExpectedNote {
+          at_seconds,
+          midi_note: *midi_note,
+          duration_beats: if event
+            .duration_beats
+            > 0.0
+          {
+            event.duration_beats
+          } else {
+            1.0
+          }
+        }
+      );
+    }
+
+    duration_seconds = duration_seconds
+      .max(
+        at_seconds
+          + duration_seconds_for_event
+      );
+
+    prepared_events.push(
+      PreparedEvent {
+        at_seconds,
+        at_beats: event
+          .at_beats
+          .max(0.0),
+        duration_beats: event
+          .duration_beats,
+        duration_seconds:
+          duration_seconds_for_event,
+        duration_ms,
+        note_duration_ms: vec![
+          duration_ms;
+          event.notes.len()
+        ],
+        velocity,
+        hand: event.hand,
+        channel: event.channel,
+        notes: event.notes.clone(),
+        fingering: event
+          .fingering
+          .clone()
+      }
+    );
+  }
+
+  PreparedSong {
+    events: prepared_events,
+    expected_notes,
+    duration_seconds,
+    beat_seconds,
+    beat_times,
+    trim_offset_seconds
+  }
+}
+
+struct TimelineSegment {
+  start_beats:       f32,
+  end_beats:         f32,
+  beat_seconds:      f32,
+  follows_tempo_map: bool,
+  repeats:           u32
+}
+
+fn section_timeline(
+  song: &SongFile,
+  beat_seconds: f32
+) -> Vec<TimelineSegment> {
+  let mut sections = song
+    .sections
+    .iter()
+    .filter(|section| {
+      section.tempo_bpm.is_some()
+        || section.loop_count.is_some()
+    })
+    .collect::<Vec<_>>();
+  sections.sort_by(|left, right| {
+    left
+      .start_beats
+      .total_cmp(&right.start_beats)
+  });
+
+  let mut segments = Vec::new();
+  let mut cursor_beats = 0.0;
+  for section in sections {
+    let start_beats = section
+      .start_beats
+      .max(cursor_beats);
+    if section.end_beats <= start_beats
+    {
+      continue;
+    }
+
+    if start_beats > cursor_beats {
+      segments.push(TimelineSegment {
+        start_beats: cursor_beats,
+        end_beats: start_beats,
+        beat_seconds,
+        follows_tempo_map: true,
+        repeats: 1
+      });
+    }
+    segments.push(TimelineSegment {
+      start_beats,
+      end_beats: section.end_beats,
+      beat_seconds: section
+        .tempo_bpm
+        .map_or(
+          beat_seconds,
+          |tempo| 60.0 / tempo.max(1.0)
+        ),
+      follows_tempo_map: section
+        .tempo_bpm
+        .is_none(),
+      repeats: section
+        .loop_count
+        .unwrap_or(1)
+        .max(1)
+    });
+    cursor_beats = section.end_beats;
   }
-  let already_used =
-    HashSet::<String>::new();
-  available_keys.retain(|key| {
-    !already_used.contains(key)
+
+  segments.push(TimelineSegment {
+    start_beats: cursor_beats,
+    end_beats: f32::INFINITY,
+    beat_seconds,
+    follows_tempo_map: true,
+    repeats: 1
   });
-  if available_keys.is_empty() {
-    return;
+
+  segments
+}
+
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq,
+)]
+enum TimerMatch {
+  Exact,
+  Octave
+}
+
+fn timer_match_kind(
+  expected: Option<u8>,
+  pressed: u8,
+  ignore_octave: bool
+) -> Option<TimerMatch> {
+  let expected = expected?;
+  if expected == pressed {
+    Some(TimerMatch::Exact)
+  } else if ignore_octave
+    && expected % 12 == pressed % 12
+  {
+    Some(TimerMatch::Octave)
+  } else {
+    None
   }
+}
 
-  let mut assigned_key =
-    HashMap::<u8, String>::new();
-  let median_note = ranked_notes
+fn prepared_song_file(
+  source: &SongFile,
+  prepared: &PreparedSong,
+  global_transpose: i8
+) -> SongFile {
+  let events = prepared
+    .events
     .iter()
-    .map(|(note, _)| *note as f32)
-    .sum::<f32>()
-    / ranked_notes.len() as f32;
+    .map(|event| {
+      SongEvent {
+        at_beats: event.at_beats,
+        duration_beats: if event
+          .duration_beats
+          > 0.0
+        {
+          event.duration_beats
+        } else {
+          prepared.beat_at_seconds(
+            event.at_seconds
+              + event.duration_seconds
+          ) - prepared.beat_at_seconds(
+            event.at_seconds
+          )
+        },
+        notes: event
+          .notes
+          .iter()
+          .filter_map(|note| {
+            u8::try_from(
+              i16::from(*note)
+                + i16::from(
+                  global_transpose
+                )
+            )
+            .ok()
+            .filter(|note| *note <= 127)
+          })
+          .collect(),
+        velocity: Some(event.velocity),
+        hand: event.hand,
+        channel: event.channel,
+        fingering: event
+          .fingering
+          .clone(),
+        ..SongEvent::default()
+      }
+    })
+    .filter(|event| {
+      !event.notes.is_empty()
+    })
+    .collect();
 
-  for (note, score) in &ranked_notes {
-    let mut best: Option<(usize, i64)> =
-      None;
+  SongFile {
+    events,
+    note_stream: Vec::new(),
+    ..source.clone()
+  }
+}
 
-    for (index, candidate_key) in
-      available_keys.iter().enumerate()
-    {
-      let mut cost = (index as i64) * 6;
+fn calibration_tap_offset_ms(
+  elapsed_ms: i64,
+  interval_ms: i64
+) -> i32 {
+  let nearest_click =
+    (elapsed_ms as f64
+      / interval_ms as f64)
+      .round() as i64
+      * interval_ms;
+  (elapsed_ms - nearest_click) as i32
+}
 
-      if let Some((is_left, _)) =
-        ergonomic_key_meta(
-          candidate_key
-        )
-      {
-        let lower_note =
-          (*note as f32) <= median_note;
-        if lower_note != is_left {
-          cost += 20;
+fn median_offset_ms(
+  mut offsets: Vec<i32>
+) -> Option<i32> {
+  if offsets.is_empty() {
+    return None;
+  }
+
+  offsets.sort_unstable();
+  Some(offsets[offsets.len() / 2])
+}
+
+fn song_notes_near_cursor(
+  prepared: &PreparedSong,
+  cursor: f32
+) -> impl Iterator<Item = u8> + '_ {
+  prepared
+    .events
+    .iter()
+    .filter(move |event| {
+      (event.at_seconds - cursor).abs()
+        <= 0.12
+    })
+    .flat_map(|event| {
+      event.notes.iter().copied()
+    })
+}
+
+fn playback_elapsed(
+  started_at: Instant,
+  now: Instant,
+  speed: f32
+) -> f32 {
+  now
+    .duration_since(started_at)
+    .as_secs_f32()
+    * speed
+}
+
+fn playback_started_at(
+  now: Instant,
+  seconds: f32,
+  speed: f32
+) -> Instant {
+  now
+    - Duration::from_secs_f32(
+      seconds.max(0.0) / speed
+    )
+}
+
+fn start_playback_at_event(
+  state: &mut PlaybackState,
+  prepared: &PreparedSong,
+  index: usize,
+  speed: f32
+) {
+  let at_seconds = prepared.events
+    [index]
+    .at_seconds
+    .max(0.0);
+  state.next_event_index = index;
+  state.tutorial_event_index = index;
+  state.cursor_seconds = at_seconds;
+  state.started_at =
+    playback_started_at(
+      Instant::now(),
+      at_seconds,
+      speed
+    );
+  let remaining = prepared
+    .expected_notes
+    .iter()
+    .filter(|expected| {
+      expected.at_seconds
+        >= at_seconds - 1e-4
+    })
+    .cloned()
+    .collect::<Vec<_>>();
+  state.score = TimerScore::new(
+    &remaining,
+    state.score.duration_weighted
+  );
+
+  if prepared.beat_seconds > 0.0
+    && state.next_metronome_beat_s
+      < at_seconds
+  {
+    let beats = prepared
+      .beat_at_seconds(at_seconds)
+      .ceil()
+      .max(0.0);
+    state.next_metronome_beat_s =
+      prepared.seconds_at_beat(beats);
+    state.next_metronome_index =
+      beats as u64;
+  }
+}
+
+fn first_metronome_beat(
+  prepared: &PreparedSong
+) -> (f32, u64) {
+  if prepared.beat_seconds <= 0.0 {
+    return (0.0, 0);
+  }
+
+  let beats_trimmed = prepared
+    .beat_at_seconds(0.0)
+    .ceil()
+    .max(0.0);
+  (
+    prepared
+      .seconds_at_beat(beats_trimmed),
+    beats_trimmed as u64
+  )
+}
+
+fn is_white_key(midi_note: u8) -> bool {
+  !is_black_key(midi_note)
+}
+
+fn is_black_key(midi_note: u8) -> bool {
+  matches!(
+    midi_note % 12,
+    1 | 3 | 6 | 8 | 10
+  )
+}
+
+fn black_key_after(
+  white_note: u8
+) -> Option<u8> {
+  match white_note % 12 {
+    | 0 | 2 | 5 | 7 | 9 => {
+      Some(white_note + 1)
+    }
+    | _ => None
+  }
+}
+
+fn white_key_style(
+  active: Option<(FlashSource, f32)>,
+  guided: bool,
+  lead_in: f32,
+  padding: bool,
+  label_rgb: [u8; 3]
+) -> container::Style {
+  let idle = if guided {
+    [255, 242, 204]
+  } else if lead_in > 0.0 {
+    mix_rgb(
+      [245, 245, 245],
+      [255, 242, 204],
+      lead_in
+    )
+  } else if padding {
+    [214, 214, 214]
+  } else {
+    [245, 245, 245]
+  };
+  let mut style =
+    container::Style::default()
+      .background(match active {
+        | Some((
+          FlashSource::Autoplay,
+          alpha
+        )) => {
+          mix_rgb8(
+            idle,
+            [110, 214, 200],
+            alpha
+          )
+        }
+        | Some((
+          FlashSource::User,
+          alpha
+        )) => {
+          mix_rgb8(
+            idle,
+            [255, 180, 95],
+            alpha
+          )
+        }
+        | None => {
+          Color::from_rgb8(
+            idle[0], idle[1], idle[2]
+          )
+        }
+      })
+      .color(
+        if padding {
+          Color::from_rgb8(
+            120, 120, 120
+          )
+        } else {
+          Color::from_rgb8(
+            label_rgb[0],
+            label_rgb[1],
+            label_rgb[2]
+          )
         }
+      );
+
+  style.border =
+    border::rounded(0).width(1).color(
+      Color::from_rgb8(140, 140, 140)
+    );
+
+  style
+}
+
+fn keymap_cell_style(
+  status: KeymapStatus
+) -> container::Style {
+  let (background, text_color) =
+    match status {
+      | KeymapStatus::Note(_) => {
+        (
+          Color::from_rgb8(
+            214, 236, 255
+          ),
+          Color::from_rgb8(20, 52, 96)
+        )
+      }
+      | KeymapStatus::Control => {
+        (
+          Color::from_rgb8(
+            255, 236, 196
+          ),
+          Color::from_rgb8(120, 72, 0)
+        )
+      }
+      | KeymapStatus::Unbound => {
+        (
+          Color::from_rgb8(
+            238, 238, 238
+          ),
+          Color::from_rgb8(
+            140, 140, 140
+          )
+        )
+      }
+    };
+
+  container::Style::default()
+    .background(background)
+    .color(text_color)
+    .border(
+      border::rounded(3)
+        .width(1)
+        .color(Color::from_rgb8(
+          190, 190, 190
+        ))
+    )
+}
+
+fn transpose_badge_style()
+-> container::Style {
+  container::Style::default()
+    .background(Color::from_rgb8(
+      255, 236, 196
+    ))
+    .color(Color::from_rgb8(120, 72, 0))
+    .border(
+      border::rounded(10)
+        .width(1)
+        .color(Color::from_rgb8(
+          214, 160, 64
+        ))
+    )
+}
+
+fn tag_chip_button_style(
+  _theme: &Theme,
+  status: button::Status
+) -> button::Style {
+  let base = button::Style {
+    background: Some(
+      Color::from_rgba(
+        0.12, 0.62, 0.20, 0.12
+      )
+      .into()
+    ),
+    border: border::rounded(12)
+      .width(1)
+      .color(Color::from_rgba(
+        0.12, 0.62, 0.20, 0.55
+      )),
+    text_color: Color::from_rgb(
+      0.10, 0.62, 0.18
+    ),
+    ..Default::default()
+  };
+
+  match status {
+    | button::Status::Hovered => {
+      button::Style {
+        background: Some(
+          Color::from_rgba(
+            0.12, 0.62, 0.20, 0.22
+          )
+          .into()
+        ),
+        ..base
+      }
+    }
+    | button::Status::Pressed => {
+      button::Style {
+        background: Some(
+          Color::from_rgba(
+            0.12, 0.62, 0.20, 0.34
+          )
+          .into()
+        ),
+        ..base
+      }
+    }
+    | button::Status::Disabled => {
+      button::Style {
+        text_color: Color::from_rgba(
+          0.10, 0.62, 0.18, 0.5
+        ),
+        ..base
       }
+    }
+    | _ => base
+  }
+}
+
+fn black_key_style(
+  active: Option<(FlashSource, f32)>,
+  guided: bool,
+  lead_in: f32,
+  padding: bool,
+  label_rgb: [u8; 3]
+) -> container::Style {
+  let idle = if guided {
+    [84, 84, 84]
+  } else if lead_in > 0.0 {
+    mix_rgb(
+      [26, 26, 26],
+      [84, 84, 84],
+      lead_in
+    )
+  } else if padding {
+    [112, 112, 112]
+  } else {
+    [26, 26, 26]
+  };
+  let mut style =
+    container::Style::default()
+      .background(match active {
+        | Some((
+          FlashSource::Autoplay,
+          alpha
+        )) => {
+          mix_rgb8(
+            idle,
+            [0, 140, 128],
+            alpha
+          )
+        }
+        | Some((
+          FlashSource::User,
+          alpha
+        )) => {
+          mix_rgb8(
+            idle,
+            [255, 136, 70],
+            alpha
+          )
+        }
+        | None => {
+          Color::from_rgb8(
+            idle[0], idle[1], idle[2]
+          )
+        }
+      })
+      .color(Color::from_rgb8(
+        label_rgb[0],
+        label_rgb[1],
+        label_rgb[2]
+      ));
+
+  style.border =
+    border::rounded(0).width(1).color(
+      Color::from_rgb8(16, 16, 16)
+    );
+
+  style
+}
+
+fn mix_rgb(
+  from: [u8; 3],
+  to: [u8; 3],
+  amount: f32
+) -> [u8; 3] {
+  let amount = amount.clamp(0.0, 1.0);
+  let channel = |index: usize| {
+    let from = f32::from(from[index]);
+    let to = f32::from(to[index]);
+    (from + (to - from) * amount)
+      .round() as u8
+  };
+  [channel(0), channel(1), channel(2)]
+}
+
+fn mix_rgb8(
+  from: [u8; 3],
+  to: [u8; 3],
+  amount: f32
+) -> Color {
+  let [red, green, blue] =
+    mix_rgb(from, to, amount);
+  Color::from_rgb8(red, green, blue)
+}
+
+fn flash_alpha(
+  now: Instant,
+  started: Instant,
+  expires: Instant
+) -> f32 {
+  if now >= expires {
+    return 0.0;
+  }
+
+  let fade_start =
+    started + FLASH_DURATION;
+  if now < fade_start {
+    return 1.0;
+  }
+
+  let fade = expires - fade_start;
+  (expires - now).as_secs_f32()
+    / fade.as_secs_f32()
+}
+
+fn lead_in_intensity(
+  seconds_until_note: f32,
+  lead_in_seconds: f32
+) -> f32 {
+  if seconds_until_note <= 0.0
+    || seconds_until_note
+      > lead_in_seconds
+  {
+    return 0.0;
+  }
 
-      for (other_note, other_key) in
-        &assigned_key
-      {
-        let pair = if note < other_note
-        {
-          (*note, *other_note)
-        } else {
-          (*other_note, *note)
-        };
-        let weight = cooccur
-          .get(&pair)
-          .copied()
-          .unwrap_or(0)
-          as i64;
-        if weight == 0 {
-          continue;
+  1.0
+    - seconds_until_note
+      / lead_in_seconds
+}
+
+/// Parses the song selected at startup,
+/// which may be an index stub without
+/// events.
+fn parse_initial_song(
+  songs: &mut [LoadedSong],
+  config: &SongLibraryConfig
+) -> Option<usize> {
+  let first = songs.first_mut()?;
+  if let Err(error) =
+    ensure_song_parsed(first, config)
+  {
+    warn!(path = %first.path.display(), error = %error, "failed parsing initial song");
+  }
+  Some(0)
+}
+
+fn build_song_thumbnails(
+  songs: &[LoadedSong]
+) -> HashMap<String, SongThumbnail> {
+  songs
+    .iter()
+    .filter_map(|loaded| {
+      build_song_thumbnail(loaded).map(
+        |thumbnail| {
+          (
+            loaded.song.meta.id.clone(),
+            thumbnail
+          )
         }
+      )
+    })
+    .collect()
+}
 
-        let candidate_meta =
-          ergonomic_key_meta(
-            candidate_key
-          );
-        let other_meta =
-          ergonomic_key_meta(other_key);
+fn build_song_thumbnail(
+  loaded: &LoadedSong
+) -> Option<SongThumbnail> {
+  if loaded.outline.is_empty() {
+    return None;
+  }
 
-        match (
-          candidate_meta,
-          other_meta
-        ) {
-          | (
-            Some((
-              cand_left,
-              cand_finger
-            )),
-            Some((
-              other_left,
-              other_finger
-            ))
-          ) => {
-            if cand_finger
-              == other_finger
-            {
-              cost += 10_000 * weight;
-            } else if cand_left
-              == other_left
-            {
-              cost += 700 * weight;
-            } else {
-              cost -= 80 * weight;
-            }
+  let rows = loaded
+    .outline
+    .iter()
+    .map(|row_mask| {
+      let mut segments =
+        Vec::<(u16, bool)>::new();
+      for column in 0..OUTLINE_COLUMNS {
+        let filled =
+          row_mask & (1 << column) != 0;
+        match segments.last_mut() {
+          | Some((width, last))
+            if *last == filled =>
+          {
+            *width += 1;
           }
           | _ => {
-            cost += 120 * weight;
+            segments.push((1, filled))
           }
         }
       }
+      segments
+    })
+    .collect();
 
-      match best {
-        | Some((_, best_cost))
-          if cost >= best_cost => {}
-        | _ => {
-          best = Some((index, cost));
-        }
+  Some(SongThumbnail {
+    rows
+  })
+}
+
+fn song_thumbnail_view(
+  thumbnail: Option<&SongThumbnail>
+) -> Element<'_, Message> {
+  let mut grid = column!()
+    .width(Length::Fixed(
+      THUMBNAIL_WIDTH
+    ))
+    .height(Length::Fixed(
+      THUMBNAIL_HEIGHT
+    ));
+
+  if let Some(thumbnail) = thumbnail {
+    for segments in &thumbnail.rows {
+      let mut segment_row = row!()
+        .width(Length::Fill)
+        .height(Length::FillPortion(1));
+      for &(width, filled) in segments {
+        segment_row = segment_row.push(
+          container(space())
+            .width(Length::FillPortion(
+              width
+            ))
+            .height(Length::Fill)
+            .style(move |_| {
+              thumbnail_cell_style(
+                filled
+              )
+            })
+        );
       }
+      grid = grid.push(segment_row);
     }
+  }
 
-    let Some((chosen_index, _)) = best
-    else {
-      continue;
-    };
-    let key = available_keys
-      .remove(chosen_index);
-
-    let Ok(chord) =
-      crate::input::parse_chord(&key)
-    else {
-      continue;
-    };
-    next_map.insert(chord, *note);
-    assigned_key.insert(*note, key);
+  container(grid)
+    .padding(2)
+    .style(|_| thumbnail_frame_style())
+    .into()
+}
 
-    trace!(
-      midi_note = note,
-      score,
-      mapped_key = assigned_key
-        .get(note)
-        .cloned()
-        .unwrap_or_default(),
-      "song ergonomic binding assigned"
-    );
+fn thumbnail_cell_style(
+  filled: bool
+) -> container::Style {
+  if filled {
+    container::Style::default()
+      .background(Color::from_rgb8(
+        74, 122, 196
+      ))
+  } else {
+    container::Style::default()
   }
+}
 
-  let mut note_to_chords =
-    BTreeMap::<u8, Vec<String>>::new();
-  for (chord, note) in &next_map {
-    note_to_chords
-      .entry(*note)
-      .or_default()
-      .push(chord.to_string());
+fn thumbnail_frame_style()
+-> container::Style {
+  container::Style::default()
+    .background(Color::from_rgb8(
+      244, 244, 244
+    ))
+    .border(
+      border::rounded(4)
+        .width(1)
+        .color(Color::from_rgb8(
+          180, 180, 180
+        ))
+    )
+}
+
+fn timing_histogram_buckets(
+  offsets: &[f32]
+) -> [usize; TIMING_HISTOGRAM_BUCKETS] {
+  let mut buckets =
+    [0; TIMING_HISTOGRAM_BUCKETS];
+  let span = TIMER_WINDOW_SECONDS * 2.0;
+  for offset in offsets {
+    let position = ((offset
+      + TIMER_WINDOW_SECONDS)
+      / span
+      * TIMING_HISTOGRAM_BUCKETS
+        as f32)
+      .floor();
+    let index =
+      (position.max(0.0) as usize).min(
+        TIMING_HISTOGRAM_BUCKETS - 1
+      );
+    buckets[index] += 1;
   }
+  buckets
+}
 
-  for chords in
-    note_to_chords.values_mut()
+fn timing_histogram<'a>(
+  offsets: &[f32]
+) -> Element<'a, Message> {
+  let buckets =
+    timing_histogram_buckets(offsets);
+  let tallest = buckets
+    .iter()
+    .copied()
+    .max()
+    .unwrap_or(0)
+    .max(1);
+  let center =
+    TIMING_HISTOGRAM_BUCKETS / 2;
+
+  let mut bars = row!()
+    .spacing(2)
+    .align_y(iced::Bottom)
+    .height(TIMING_HISTOGRAM_HEIGHT);
+  for (index, count) in
+    buckets.into_iter().enumerate()
   {
-    chords.sort_unstable();
+    let shade = if index == center {
+      Color::from_rgb8(46, 160, 67)
+    } else {
+      Color::from_rgb8(90, 120, 200)
+    };
+    let style =
+      container::Style::default()
+        .background(shade);
+    let height = (count as f32
+      / tallest as f32
+      * TIMING_HISTOGRAM_HEIGHT)
+      .max(1.0);
+    bars = bars.push(
+      container(space())
+        .width(14)
+        .height(height)
+        .style(move |_| style)
+    );
   }
 
-  bindings.note_bindings = next_map;
-  bindings.note_to_chords =
-    note_to_chords;
+  let mean_ms =
+    offsets.iter().sum::<f32>()
+      / offsets.len().max(1) as f32
+      * 1000.0;
+  let tendency = if mean_ms < 0.0 {
+    "early"
+  } else {
+    "late"
+  };
 
-  info!(
-    song_notes = song_notes.len(),
-    mapped_notes =
-      bindings.note_to_chords.len(),
-    assigned_song_notes =
-      assigned_key.len(),
-    "applied ergonomic bindings for \
-     selected song"
-  );
+  column![
+    text("Hit timing (early <-> late)")
+      .size(12),
+    bars,
+    text(format!(
+      "Average: {:.0} ms {tendency}",
+      mean_ms.abs()
+    ))
+    .size(12),
+  ]
+  .spacing(2)
+  .into()
 }
 
-fn ergonomic_key_priority(
-  layout: KeyboardLayout
-) -> Vec<String> {
-  match layout {
-    | KeyboardLayout::Ansi104 => {
-      vec![
-        // Home row.
-        "f", "j", "d", "k", "s", "l",
-        "a", ";", "g", "h",
-        // Around home.
-        "r", "u", "e", "i", "w", "o",
-        "q", "p", "v", "n", "c", "m",
-        "x", ",", "z", ".", "t", "y",
-        "b", "/",
-        // Far punctuation.
-        "'", "[", "]", "\\", "`", "-",
-        "=",
-        // Number row last.
-        "5", "6", "4", "7", "3", "8",
-        "2", "9", "1", "0",
-      ]
-      .into_iter()
-      .map(str::to_string)
-      .collect()
-    }
-  }
+fn beats_crossed(
+  previous_beat: f32,
+  beat: f32
+) -> impl Iterator<Item = f32> {
+  let first = previous_beat + 1.0;
+  let count = (beat - previous_beat)
+    .max(0.0) as usize;
+  (0..count).map(move |step| {
+    first + step as f32
+  })
 }
 
-fn ergonomic_key_meta(
-  key: &str
-) -> Option<(bool, u8)> {
-  let base_key = key
-    .rsplit('+')
-    .next()
-    .unwrap_or(key);
-  let (is_left, finger) = match base_key
-  {
-    | "`" | "1" | "q" | "a" | "z" => {
-      (true, 1)
-    }
-    | "2" | "w" | "s" | "x" => {
-      (true, 2)
-    }
-    | "3" | "e" | "d" | "c" => {
-      (true, 3)
-    }
-    | "4" | "5" | "r" | "t" | "f"
-    | "g" | "v" | "b" => (true, 4),
-    | "6" | "7" | "y" | "u" | "h"
-    | "j" | "n" | "m" => (false, 7),
-    | "8" | "i" | "k" | "," => {
-      (false, 8)
-    }
-    | "9" | "o" | "l" | "." => {
-      (false, 9)
-    }
-    | "0" | "p" | ";" | "/" | "-"
-    | "=" | "[" | "]" | "\\"
-    | "'" => (false, 10),
-    | _ => return None
+fn beat_grid_marker<'a>(
+  bar_start: bool,
+  height: f32
+) -> Element<'a, Message> {
+  let (width, shade) = if bar_start {
+    (3, 96)
+  } else {
+    (1, 200)
   };
+  let style =
+    container::Style::default()
+      .background(Color::from_rgb8(
+        shade, shade, shade
+      ));
 
-  Some((is_left, finger))
+  container(space())
+    .width(width)
+    .height(height)
+    .style(move |_| style)
+    .into()
 }
 
-fn key_from_song_input(
-  song_note: u8,
-  semitones: i8
-) -> Option<u8> {
-  let shifted = i16::from(song_note)
-    + i16::from(semitones);
-  if (0..=127).contains(&shifted) {
-    Some(shifted as u8)
-  } else {
-    None
-  }
+fn timeline_tile_style(
+  is_current: bool,
+  is_past: bool
+) -> container::Style {
+  let mut style =
+    container::Style::default().color(
+      Color::from_rgb8(20, 20, 20)
+    );
+
+  style.background = Some(
+    if is_current {
+      Color::from_rgb8(255, 212, 138)
+    } else if is_past {
+      Color::from_rgb8(220, 220, 220)
+    } else {
+      Color::from_rgb8(244, 244, 244)
+    }
+    .into()
+  );
+  style.border =
+    border::rounded(6).width(1).color(
+      Color::from_rgb8(160, 160, 160)
+    );
+
+  style
 }
 
-fn prepare_song_for_bindings(
-  source_song: &SongFile,
-  bindings: &RuntimeBindings,
-  transpose_to_fit: bool,
-  forced_transpose: Option<i8>
-) -> (Option<PreparedSong>, i8, Vec<u8>)
-{
-  let available_notes = bindings
-    .note_to_chords
-    .keys()
-    .copied()
-    .collect::<HashSet<_>>();
+fn scale_pitch_classes(
+  root: u8,
+  kind: ScaleKind
+) -> HashSet<u8> {
+  kind
+    .intervals()
+    .iter()
+    .map(|interval| {
+      (root + interval) % 12
+    })
+    .collect()
+}
 
-  let transpose = forced_transpose
-    .unwrap_or_else(|| {
-      if transpose_to_fit {
-        choose_transpose_for_fit(
-          source_song,
-          &available_notes
+fn within_wrong_note_grace(
+  last_correct_at: Option<Instant>,
+  now: Instant,
+  grace: Duration
+) -> bool {
+  !grace.is_zero()
+    && last_correct_at.is_some_and(
+      |last| {
+        now.saturating_duration_since(
+          last
+        ) < grace
+      }
+    )
+}
+
+fn midi_note_name(
+  midi_note: u8,
+  convention: OctaveConvention
+) -> String {
+  let note_name = PITCH_CLASS_NAMES
+    [usize::from(midi_note % 12)];
+  let octave =
+    i16::from(midi_note / 12) - 1;
+
+  match convention {
+    | OctaveConvention::Scientific => {
+      format!("{note_name}{octave}")
+    }
+    | OctaveConvention::Yamaha => {
+      format!(
+        "{note_name}{}",
+        octave - 1
+      )
+    }
+    | OctaveConvention::Helmholtz => {
+      if octave >= 3 {
+        format!(
+          "{}{}",
+          note_name.to_lowercase(),
+          "'".repeat(
+            (octave - 3) as usize
+          )
         )
       } else {
-        0
+        format!(
+          "{note_name}{}",
+          ",".repeat(
+            (2 - octave) as usize
+          )
+        )
       }
-    });
+    }
+  }
+}
 
-  let prepared =
-    prepare_song(source_song);
-  let mut missing = prepared
-    .expected_notes
-    .iter()
-    .filter_map(|entry| {
-      key_from_song_input(
-        entry.midi_note,
-        transpose
-      )
-    })
-    .filter(|note| {
-      !available_notes.contains(note)
-    })
-    .collect::<Vec<_>>();
-  missing.sort_unstable();
-  missing.dedup();
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use super::*;
+  use crate::songs::SongEvent;
+
+  #[test]
+  fn buckets_timing_offsets_from_early_to_late()
+   {
+    let buckets =
+      timing_histogram_buckets(&[
+        -0.5, -0.17, 0.0, 0.01, 0.17,
+        0.5
+      ]);
+
+    assert_eq!(buckets[0], 2);
+    assert_eq!(
+      buckets
+        [TIMING_HISTOGRAM_BUCKETS / 2],
+      2
+    );
+    assert_eq!(
+      buckets
+        [TIMING_HISTOGRAM_BUCKETS - 1],
+      2
+    );
+  }
 
-  (Some(prepared), transpose, missing)
-}
+  #[test]
+  fn cuts_same_pitch_overlaps_before_reonset()
+   {
+    let song = SongFile {
+      events: vec![
+        SongEvent {
+          at_beats: 0.0,
+          duration_beats: 4.0,
+          notes: vec![60, 64],
+          ..SongEvent::default()
+        },
+        SongEvent {
+          at_beats: 1.0,
+          duration_beats: 1.0,
+          notes: vec![60],
+          ..SongEvent::default()
+        },
+      ],
+      ..SongFile::default()
+    };
 
-fn choose_transpose_for_fit(
-  song: &SongFile,
-  available_notes: &HashSet<u8>
-) -> i8 {
-  let unique_notes = song
-    .events
-    .iter()
-    .flat_map(|event| {
-      event.notes.iter()
-    })
-    .copied()
-    .collect::<HashSet<_>>();
+    let mut prepared =
+      prepare_song(&song, false);
+    assert_eq!(
+      prepared.events[0]
+        .note_duration_ms,
+      vec![2_000, 2_000]
+    );
 
-  if unique_notes.is_empty() {
-    return 0;
+    cut_same_pitch_overlaps(
+      &mut prepared.events
+    );
+    assert_eq!(
+      prepared.events[0]
+        .note_duration_ms,
+      vec![500, 2_000]
+    );
+    assert_eq!(
+      prepared.events[1]
+        .note_duration_ms,
+      vec![500]
+    );
   }
 
-  let shifts = [
-    -48, -36, -24, -12, 0, 12, 24, 36,
-    48
-  ];
+  #[test]
+  fn duration_weighted_accuracy_favors_long_notes()
+   {
+    let expected = [4.0, 1.0]
+      .into_iter()
+      .enumerate()
+      .map(|(index, duration_beats)| {
+        ExpectedNote {
+          at_seconds: index as f32,
+          midi_note: 60,
+          duration_beats
+        }
+      })
+      .collect::<Vec<_>>();
 
-  let mut best_shift = 0i8;
-  let mut best_score = 0usize;
+    let mut flat =
+      TimerScore::new(&expected, false);
+    let mut weighted =
+      TimerScore::new(&expected, true);
+    for score in
+      [&mut flat, &mut weighted]
+    {
+      score.hit_notes = 1;
+      score.hit_beats = 4.0;
+    }
 
-  for shift in shifts {
-    let mut score = 0usize;
-    for note in &unique_notes {
-      let shifted =
-        i16::from(*note) + shift;
-      if !(0..=127).contains(&shifted) {
-        continue;
-      }
-      if available_notes
-        .contains(&(shifted as u8))
-      {
-        score += 1;
-      }
+    assert_eq!(
+      flat.accuracy_percent(),
+      50.0
+    );
+    assert_eq!(
+      weighted.accuracy_percent(),
+      80.0
+    );
+  }
+
+  #[test]
+  fn ergonomic_bindings_are_stable() {
+    let config = AppConfig::default();
+    let mut song = SongFile::default();
+    for (beat, notes) in [
+      vec![60, 64, 67],
+      vec![62, 65, 69],
+      vec![60, 62],
+      vec![64, 67, 71, 72],
+      vec![59, 65]
+    ]
+    .into_iter()
+    .enumerate()
+    {
+      song.events.push(SongEvent {
+        at_beats: beat as f32,
+        notes,
+        ..SongEvent::default()
+      });
     }
 
-    let shift_abs = shift.abs() as i16;
-    let best_abs =
-      i16::from(best_shift).abs();
-    let is_better = score > best_score
-      || (score == best_score
-        && shift_abs < best_abs);
-    if is_better {
-      best_score = score;
-      best_shift = shift as i8;
+    let assign = || {
+      let mut bindings =
+        compile_runtime_bindings(
+          &config
+        )
+        .expect("default bindings");
+      apply_song_ergonomic_bindings(
+        &mut bindings,
+        &song,
+        config.keyboard.layout,
+        0
+      );
+      bindings.note_to_chords
+    };
+
+    let first = assign();
+    assert!(!first.is_empty());
+    for _ in 0..16 {
+      assert_eq!(assign(), first);
     }
   }
 
-  best_shift
-}
+  #[test]
+  fn initial_song_is_parsed_from_an_index_stub()
+   {
+    let dir = std::env::temp_dir()
+      .join(format!(
+        "symfose-initial-song-{}",
+        std::process::id()
+      ));
+    let songs_dir = dir.join("songs");
+    fs::create_dir_all(&songs_dir)
+      .expect("temp dir");
+    fs::copy(
+      "res/songs/twinkle.toml",
+      songs_dir.join("twinkle.toml")
+    )
+    .expect("copy song");
+    let config = SongLibraryConfig {
+      directory: songs_dir
+        .to_string_lossy()
+        .to_string(),
+      midi_directory: dir
+        .join("midi")
+        .to_string_lossy()
+        .to_string(),
+      cache_enabled: false,
+      index_file: dir
+        .join("library.index.toml")
+        .to_string_lossy()
+        .to_string(),
+      ..SongLibraryConfig::default()
+    };
 
-fn prepare_song(
-  song: &SongFile
-) -> PreparedSong {
-  let beat_seconds =
-    60.0 / song.meta.tempo_bpm.max(1.0);
+    load_song_library(&config)
+      .expect("first load");
+    let (mut songs, stats) =
+      load_song_library(&config)
+        .expect("indexed load");
+    assert_eq!(stats.indexed, 1);
+    assert!(!songs[0].parsed);
 
-  let mut expected_notes = Vec::new();
-  let mut prepared_events = Vec::new();
+    let selected = parse_initial_song(
+      &mut songs, &config
+    );
+    let _ = fs::remove_dir_all(&dir);
 
-  let mut duration_seconds: f32 = 0.0;
+    assert_eq!(selected, Some(0));
+    assert!(songs[0].parsed);
+    assert!(
+      !songs[0].song.events.is_empty()
+    );
+  }
 
-  for event in &song.events {
-    if event.notes.is_empty() {
-      continue;
+  #[test]
+  fn cli_rejects_unknown_arguments() {
+    let parse = |args: &[&str]| {
+      parse_cli_args(
+        args
+          .iter()
+          .map(ToString::to_string)
+      )
+    };
+
+    assert!(
+      parse(&["--no-audio"])
+        .is_ok_and(|cli| cli.no_audio)
+    );
+    let error = parse(&["--no-adio"])
+      .expect_err("typo rejected");
+    assert!(
+      error
+        .to_string()
+        .contains(CLI_USAGE)
+    );
+  }
+
+  #[test]
+  fn sync_message_reports_beat_and_active_notes()
+   {
+    let notes =
+      BTreeSet::from([64_u8, 60]);
+    assert_eq!(
+      sync_message(2.5, 2.5, &notes),
+      "symfose cursor=2.500 beat=2 \
+       notes=60,64"
+    );
+    assert_eq!(
+      sync_message(
+        3.0,
+        -0.5,
+        &BTreeSet::new()
+      ),
+      "symfose cursor=3.000 beat=0 \
+       notes="
+    );
+  }
+
+  #[test]
+  fn keymap_cheat_sheet_groups_keys_by_row()
+   {
+    let config = AppConfig::default();
+    let rows = vec![
+      vec![],
+      vec![(
+        "q",
+        KeymapStatus::Unbound
+      )],
+      vec![
+        ("a", KeymapStatus::Note(60)),
+        ("s", KeymapStatus::Control),
+      ],
+      vec![],
+    ];
+    let note_to_chords =
+      BTreeMap::from([(60, vec![
+        "a".to_string(),
+        "shift+a".to_string(),
+      ])]);
+
+    let sheet = keymap_cheat_sheet(
+      &config,
+      &rows,
+      &note_to_chords
+    );
+
+    assert!(
+      !sheet.contains("Number row")
+    );
+    let top = sheet
+      .find("## Top row")
+      .expect("top row");
+    let home = sheet
+      .find("## Home row")
+      .expect("home row");
+    assert!(top < home);
+    assert!(
+      sheet.contains("| `q` | - |")
+    );
+    assert!(
+      sheet
+        .contains("| `a` | C4 (60) |")
+    );
+    assert!(
+      sheet
+        .contains("| `s` | control |")
+    );
+    assert!(
+      sheet.contains(
+        "C4 (60): a, shift+a"
+      )
+    );
+    assert!(sheet.contains(
+      "- Quit: esc or ctrl+c"
+    ));
+  }
+
+  #[test]
+  fn timer_hits_are_judged_at_press_time_for_any_tick_rate()
+   {
+    let song = SongFile {
+      events: [60_u8, 62, 64]
+        .into_iter()
+        .enumerate()
+        .map(|(index, note)| {
+          SongEvent {
+            at_beats: (index + 1)
+              as f32,
+            notes: vec![note],
+            ..SongEvent::default()
+          }
+        })
+        .collect(),
+      ..SongFile::default()
+    };
+    let presses = [
+      (60_u8, 520_u64),
+      (62, 1_090),
+      (65, 1_300),
+      (64, 1_530)
+    ];
+
+    let mut judgments = Vec::new();
+    for tick_rate_ms in [4_u64, 16, 250]
+    {
+      let mut config =
+        AppConfig::default();
+      config.audio.backend =
+        AudioBackend::Null;
+      let audio =
+        AudioEngine::new(&config.audio)
+          .expect("null audio engine");
+      let bindings =
+        compile_runtime_bindings(
+          &config
+        )
+        .expect("bindings");
+      let mut app = PianoApp::new(
+        config,
+        std::env::temp_dir().join(
+          "symfose-tick-rate.toml"
+        ),
+        bindings,
+        Vec::new(),
+        LibraryLoadStats::default(),
+        audio
+      );
+
+      let prepared =
+        prepare_song(&song, false);
+      let mut playback =
+        PlaybackState::new(
+          PlayMode::Timer,
+          &prepared,
+          false
+        );
+      let origin = Instant::now();
+      playback.started_at = origin;
+      app.prepared_song =
+        Some(prepared);
+      app.playback = Some(playback);
+
+      let mut pending =
+        presses.iter().peekable();
+      let mut tick_ms = 0;
+      while app.playback.is_some() {
+        tick_ms += tick_rate_ms;
+        while let Some((note, at_ms)) =
+          pending.next_if(
+            |(_, at_ms)| {
+              *at_ms < tick_ms
+            }
+          )
+        {
+          app.process_note_input(
+            *note,
+            origin
+              + Duration::from_millis(
+                *at_ms
+              )
+          );
+        }
+        app.handle_tick(
+          origin
+            + Duration::from_millis(
+              tick_ms
+            )
+        );
+      }
+
+      let score = app
+        .last_timer_score
+        .expect("timer score");
+      judgments.push((
+        score.perfect_hits,
+        score.good_hits,
+        score.wrong_notes,
+        score.missed_notes,
+        score.accuracy_percent(),
+        score.hit_offsets
+      ));
     }
 
-    let at_seconds =
-      event.at_beats.max(0.0)
-        * beat_seconds;
-    let duration_seconds_for_event =
-      if event.duration_beats > 0.0 {
-        (event.duration_beats
-          * beat_seconds)
-          .max(0.04)
-      } else {
-        0.32
-      };
+    assert_eq!(judgments[0].0, 2);
+    assert_eq!(judgments[0].1, 1);
+    assert_eq!(judgments[0].2, 1);
+    assert!(judgments.iter().all(
+      |judgment| {
+        *judgment == judgments[0]
+      }
+    ));
+  }
 
-    let duration_ms =
-      (duration_seconds_for_event
-        * 1000.0)
-        .round()
-        .max(45.0) as u64;
+  #[test]
+  fn center_range_transpose_prefers_median_near_middle_c()
+   {
+    let song = SongFile {
+      events: [84_u8, 86, 88, 91]
+        .into_iter()
+        .enumerate()
+        .map(|(index, note)| {
+          SongEvent {
+            at_beats: index as f32,
+            notes: vec![note],
+            ..SongEvent::default()
+          }
+        })
+        .collect(),
+      ..SongFile::default()
+    };
+    let every_note = (0..=127)
+      .collect::<HashSet<u8>>();
+
+    assert_eq!(
+      choose_transpose_for_fit(
+        &song,
+        &every_note,
+        TransposeStrategy::MaxCoverage
+      ),
+      0
+    );
+    assert_eq!(
+      choose_transpose_for_fit(
+        &song,
+        &every_note,
+        TransposeStrategy::CenterRange
+      ),
+      -24
+    );
 
-    let velocity = event
-      .velocity
-      .unwrap_or(
-        song.meta.default_velocity
+    let upper_only = (72..=127)
+      .collect::<HashSet<u8>>();
+    assert_eq!(
+      choose_transpose_for_fit(
+        &song,
+        &upper_only,
+        TransposeStrategy::CenterRange
+      ),
+      -12
+    );
+  }
+
+  #[test]
+  fn shared_hand_notes_follow_policy_in_single_hand_practice()
+   {
+    let song = SongFile {
+      events: [
+        (60, Some(Hand::Right)),
+        (48, Some(Hand::Left)),
+        (55, Some(Hand::Both)),
+        (67, None)
+      ]
+      .into_iter()
+      .map(|(note, hand)| {
+        SongEvent {
+          notes: vec![note],
+          hand,
+          ..SongEvent::default()
+        }
+      })
+      .collect(),
+      ..SongFile::default()
+    };
+    let notes = |hand, shared| {
+      practice_hand_song(
+        &song, hand, shared
       )
-      .clamp(1, 127);
+      .events
+      .iter()
+      .flat_map(|event| {
+        event.notes.clone()
+      })
+      .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+      notes(
+        PracticeHand::Both,
+        SharedHandNotes::Skip
+      ),
+      vec![60, 48, 55, 67]
+    );
+    assert_eq!(
+      notes(
+        PracticeHand::Left,
+        SharedHandNotes::IncludeInBoth
+      ),
+      vec![48, 55, 67]
+    );
+    assert_eq!(
+      notes(
+        PracticeHand::Left,
+        SharedHandNotes::MelodyHandOnly
+      ),
+      vec![48, 67]
+    );
+    assert_eq!(
+      notes(
+        PracticeHand::Right,
+        SharedHandNotes::MelodyHandOnly
+      ),
+      vec![60, 55, 67]
+    );
+    assert_eq!(
+      notes(
+        PracticeHand::Right,
+        SharedHandNotes::Skip
+      ),
+      vec![60, 67]
+    );
+  }
+
+  #[test]
+  fn reports_song_notes_outside_instrument_range()
+   {
+    let song = SongFile {
+      events: vec![SongEvent {
+        notes: vec![40, 60, 84],
+        ..SongEvent::default()
+      }],
+      ..SongFile::default()
+    };
+    let profile = SoundFontProfile {
+      min_note: Some(55),
+      max_note: Some(79),
+      ..SoundFontProfile::default()
+    };
+
+    assert_eq!(
+      notes_outside_profile_range(
+        &song, &profile, 0
+      ),
+      vec![40, 84]
+    );
+    assert_eq!(
+      notes_outside_profile_range(
+        &song, &profile, -12
+      ),
+      vec![40, 60]
+    );
+    assert!(
+      notes_outside_profile_range(
+        &song,
+        &SoundFontProfile::default(),
+        0
+      )
+      .is_empty()
+    );
+  }
+
+  #[test]
+  fn lead_in_glow_grows_as_note_approaches()
+   {
+    assert_eq!(
+      lead_in_intensity(1.0, 0.8),
+      0.0
+    );
+    assert_eq!(
+      lead_in_intensity(-0.1, 0.8),
+      0.0
+    );
+    let far =
+      lead_in_intensity(0.6, 0.8);
+    let near =
+      lead_in_intensity(0.2, 0.8);
+    assert!(far > 0.0 && far < near);
+    assert!((near - 0.75).abs() < 1e-6);
+
+    assert_eq!(
+      mix_rgb8(
+        [0, 100, 200],
+        [100, 100, 0],
+        0.5
+      ),
+      Color::from_rgb8(50, 100, 100)
+    );
+  }
 
-    for midi_note in &event.notes {
-      expected_notes.push(
-        ExpectedNote {
-          at_seconds,
-          midi_note: *midi_note
-        }
-      );
-    }
+  #[test]
+  fn flash_fades_out_after_full_brightness()
+   {
+    let started = Instant::now();
+    let fade =
+      Duration::from_millis(200);
+    let expires =
+      started + FLASH_DURATION + fade;
 
-    duration_seconds = duration_seconds
-      .max(
-        at_seconds
-          + duration_seconds_for_event
-      );
+    assert_eq!(
+      flash_alpha(
+        started, started, expires
+      ),
+      1.0
+    );
+    let halfway = started
+      + FLASH_DURATION
+      + fade / 2;
+    assert!(
+      (flash_alpha(
+        halfway, started, expires
+      ) - 0.5)
+        .abs()
+        < 1e-3
+    );
+    assert_eq!(
+      flash_alpha(
+        expires, started, expires
+      ),
+      0.0
+    );
 
-    prepared_events.push(
-      PreparedEvent {
-        at_seconds,
-        duration_seconds:
-          duration_seconds_for_event,
-        duration_ms,
-        velocity,
-        notes: event.notes.clone()
-      }
+    let hard_expiry =
+      started + FLASH_DURATION;
+    assert_eq!(
+      flash_alpha(
+        started,
+        started,
+        hard_expiry
+      ),
+      1.0
+    );
+    assert_eq!(
+      flash_alpha(
+        hard_expiry,
+        started,
+        hard_expiry
+      ),
+      0.0
     );
   }
 
-  PreparedSong {
-    events: prepared_events,
-    expected_notes,
-    duration_seconds,
-    beat_seconds
+  #[test]
+  fn names_middle_c_in_each_octave_convention()
+   {
+    assert_eq!(
+      midi_note_name(
+        60,
+        OctaveConvention::Scientific
+      ),
+      "C4"
+    );
+    assert_eq!(
+      midi_note_name(
+        60,
+        OctaveConvention::Yamaha
+      ),
+      "C3"
+    );
+    assert_eq!(
+      midi_note_name(
+        60,
+        OctaveConvention::Helmholtz
+      ),
+      "c'"
+    );
+    assert_eq!(
+      midi_note_name(
+        49,
+        OctaveConvention::Helmholtz
+      ),
+      "c#"
+    );
+    assert_eq!(
+      midi_note_name(
+        24,
+        OctaveConvention::Helmholtz
+      ),
+      "C,"
+    );
   }
-}
 
-fn is_white_key(midi_note: u8) -> bool {
-  !is_black_key(midi_note)
-}
+  #[test]
+  fn ignores_tutorial_wrong_notes_only_inside_grace()
+   {
+    let last = Instant::now();
+    let grace =
+      Duration::from_millis(200);
 
-fn is_black_key(midi_note: u8) -> bool {
-  matches!(
-    midi_note % 12,
-    1 | 3 | 6 | 8 | 10
-  )
-}
+    assert!(!within_wrong_note_grace(
+      None, last, grace
+    ));
+    assert!(within_wrong_note_grace(
+      Some(last),
+      last + Duration::from_millis(150),
+      grace
+    ));
+    assert!(!within_wrong_note_grace(
+      Some(last),
+      last + Duration::from_millis(250),
+      grace
+    ));
+    assert!(!within_wrong_note_grace(
+      Some(last),
+      last,
+      Duration::ZERO
+    ));
+  }
 
-fn black_key_after(
-  white_note: u8
-) -> Option<u8> {
-  match white_note % 12 {
-    | 0 | 2 | 5 | 7 | 9 => {
-      Some(white_note + 1)
-    }
-    | _ => None
+  #[test]
+  fn parses_transpose_sync_input() {
+    assert_eq!(
+      parse_sync_transpose(
+        "symfose transpose=-3\n"
+      ),
+      Some(-3)
+    );
+    assert_eq!(
+      parse_sync_transpose(
+        "transpose=7"
+      ),
+      Some(7)
+    );
+    assert_eq!(
+      parse_sync_transpose(
+        "symfose transpose=30"
+      ),
+      None
+    );
+    assert_eq!(
+      parse_sync_transpose(
+        "symfose cursor=1.0"
+      ),
+      None
+    );
   }
-}
 
-fn white_key_style(
-  active: bool,
-  guided: bool
-) -> container::Style {
-  let mut style =
-    container::Style::default()
-      .background(
-        if active {
-          Color::from_rgb8(255, 180, 95)
-        } else if guided {
-          Color::from_rgb8(
-            255, 242, 204
-          )
-        } else {
-          Color::from_rgb8(
-            245, 245, 245
+  #[test]
+  fn fits_notes_pushed_past_midi_range()
+  {
+    let song = SongFile {
+      events: vec![
+        SongEvent {
+          at_beats: 0.0,
+          notes: vec![60, 120],
+          ..SongEvent::default()
+        },
+        SongEvent {
+          at_beats: 1.0,
+          notes: vec![125],
+          ..SongEvent::default()
+        },
+      ],
+      ..SongFile::default()
+    };
+    let transpose = 10;
+    let all_notes_shift =
+      |prepared: &PreparedSong| {
+        prepared
+          .events
+          .iter()
+          .flat_map(|event| {
+            &event.notes
+          })
+          .chain(
+            prepared
+              .expected_notes
+              .iter()
+              .map(|entry| {
+                &entry.midi_note
+              })
           )
-        }
-      )
-      .color(Color::from_rgb8(
-        25, 25, 25
-      ));
+          .all(|note| {
+            key_from_song_input(
+              *note, transpose
+            )
+            .is_some()
+          })
+      };
 
-  style.border =
-    border::rounded(0).width(1).color(
-      Color::from_rgb8(140, 140, 140)
+    let mut dropped =
+      prepare_song(&song, false);
+    assert_eq!(
+      fit_transposed_notes(
+        &mut dropped,
+        transpose,
+        NoteRangePolicy::Drop
+      ),
+      2
+    );
+    assert!(all_notes_shift(&dropped));
+    assert_eq!(dropped.events.len(), 1);
+    assert_eq!(
+      dropped.events[0].notes,
+      vec![60]
+    );
+    assert_eq!(
+      dropped.events[0]
+        .note_duration_ms
+        .len(),
+      1
+    );
+    assert_eq!(
+      dropped.expected_notes.len(),
+      1
     );
 
-  style
-}
-
-fn tag_chip_button_style(
-  _theme: &Theme,
-  status: button::Status
-) -> button::Style {
-  let base = button::Style {
-    background: Some(
-      Color::from_rgba(
-        0.12, 0.62, 0.20, 0.12
-      )
-      .into()
-    ),
-    border: border::rounded(12)
-      .width(1)
-      .color(Color::from_rgba(
-        0.12, 0.62, 0.20, 0.55
-      )),
-    text_color: Color::from_rgb(
-      0.10, 0.62, 0.18
-    ),
-    ..Default::default()
-  };
+    let mut clamped =
+      prepare_song(&song, false);
+    assert_eq!(
+      fit_transposed_notes(
+        &mut clamped,
+        transpose,
+        NoteRangePolicy::Clamp
+      ),
+      2
+    );
+    assert!(all_notes_shift(&clamped));
+    assert_eq!(
+      clamped.events[0].notes,
+      vec![60, 117]
+    );
+    assert_eq!(
+      clamped.events[1].notes,
+      vec![117]
+    );
 
-  match status {
-    | button::Status::Hovered => {
-      button::Style {
-        background: Some(
-          Color::from_rgba(
-            0.12, 0.62, 0.20, 0.22
-          )
-          .into()
-        ),
-        ..base
-      }
-    }
-    | button::Status::Pressed => {
-      button::Style {
-        background: Some(
-          Color::from_rgba(
-            0.12, 0.62, 0.20, 0.34
-          )
-          .into()
-        ),
-        ..base
-      }
-    }
-    | button::Status::Disabled => {
-      button::Style {
-        text_color: Color::from_rgba(
-          0.10, 0.62, 0.18, 0.5
-        ),
-        ..base
-      }
-    }
-    | _ => base
+    let mut folded =
+      prepare_song(&song, false);
+    assert_eq!(
+      fit_transposed_notes(
+        &mut folded,
+        transpose,
+        NoteRangePolicy::OctaveFold
+      ),
+      2
+    );
+    assert!(all_notes_shift(&folded));
+    assert_eq!(
+      folded.events[0].notes,
+      vec![60, 108]
+    );
+    assert_eq!(
+      folded.events[1].notes,
+      vec![113]
+    );
   }
-}
 
-fn black_key_style(
-  active: bool,
-  guided: bool
-) -> container::Style {
-  let mut style =
-    container::Style::default()
-      .background(
-        if active {
-          Color::from_rgb8(255, 136, 70)
-        } else if guided {
-          Color::from_rgb8(84, 84, 84)
-        } else {
-          Color::from_rgb8(26, 26, 26)
-        }
-      )
-      .color(Color::from_rgb8(
-        242, 242, 242
-      ));
+  #[test]
+  fn practice_start_aligns_playback_to_event()
+   {
+    let song = SongFile {
+      events: (0..4)
+        .map(|beat| {
+          SongEvent {
+            at_beats: beat as f32,
+            duration_beats: 1.0,
+            notes: vec![60 + beat],
+            ..SongEvent::default()
+          }
+        })
+        .collect(),
+      ..SongFile::default()
+    };
+    let prepared =
+      prepare_song(&song, false);
+    let mut state = PlaybackState::new(
+      PlayMode::Timer,
+      &prepared,
+      false
+    );
 
-  style.border =
-    border::rounded(0).width(1).color(
-      Color::from_rgb8(16, 16, 16)
+    start_playback_at_event(
+      &mut state, &prepared, 2, 1.0
     );
 
-  style
-}
+    let start =
+      prepared.events[2].at_seconds;
+    assert_eq!(
+      state.next_event_index,
+      2
+    );
+    assert_eq!(
+      state.tutorial_event_index,
+      2
+    );
+    assert!(
+      (state.cursor_seconds - start)
+        .abs()
+        < 1e-4
+    );
+    assert!(
+      state.next_metronome_beat_s
+        >= start - 1e-4
+    );
+    assert_eq!(
+      state.next_metronome_index,
+      2
+    );
+    assert_eq!(
+      state.score.expected_notes,
+      2
+    );
+  }
 
-fn timeline_tile_style(
-  is_current: bool,
-  is_past: bool
-) -> container::Style {
-  let mut style =
-    container::Style::default().color(
-      Color::from_rgb8(20, 20, 20)
+  #[test]
+  fn finds_song_notes_at_paused_cursor()
+  {
+    let song = SongFile {
+      events: vec![
+        SongEvent {
+          at_beats: 1.0,
+          notes: vec![60, 64, 67],
+          ..SongEvent::default()
+        },
+        SongEvent {
+          at_beats: 2.0,
+          notes: vec![72],
+          ..SongEvent::default()
+        },
+      ],
+      ..SongFile::default()
+    };
+    let prepared =
+      prepare_song(&song, false);
+    let mut state = PlaybackState::new(
+      PlayMode::Autoplay,
+      &prepared,
+      false
+    );
+    start_playback_at_event(
+      &mut state, &prepared, 0, 1.0
     );
 
-  style.background = Some(
-    if is_current {
-      Color::from_rgb8(255, 212, 138)
-    } else if is_past {
-      Color::from_rgb8(220, 220, 220)
-    } else {
-      Color::from_rgb8(244, 244, 244)
-    }
-    .into()
-  );
-  style.border =
-    border::rounded(6).width(1).color(
-      Color::from_rgb8(160, 160, 160)
+    let notes = song_notes_near_cursor(
+      &prepared,
+      state.cursor_seconds
+    )
+    .collect::<Vec<_>>();
+    assert_eq!(notes, vec![60, 64, 67]);
+  }
+
+  #[test]
+  fn matches_octave_displaced_presses_only_when_lenient()
+   {
+    assert_eq!(
+      timer_match_kind(
+        Some(60),
+        60,
+        false
+      ),
+      Some(TimerMatch::Exact)
+    );
+    assert_eq!(
+      timer_match_kind(
+        Some(60),
+        72,
+        false
+      ),
+      None
+    );
+    assert_eq!(
+      timer_match_kind(
+        Some(60),
+        48,
+        true
+      ),
+      Some(TimerMatch::Octave)
+    );
+    assert_eq!(
+      timer_match_kind(
+        Some(60),
+        61,
+        true
+      ),
+      None
     );
+    assert_eq!(
+      timer_match_kind(None, 60, true),
+      None
+    );
+  }
 
-  style
-}
+  #[test]
+  fn calibration_taps_measure_offset_from_nearest_click()
+   {
+    assert_eq!(
+      calibration_tap_offset_ms(
+        1_240, 600
+      ),
+      40
+    );
+    assert_eq!(
+      calibration_tap_offset_ms(
+        1_170, 600
+      ),
+      -30
+    );
+    assert_eq!(
+      median_offset_ms(vec![
+        50, 10, 40, 300, 45
+      ]),
+      Some(45)
+    );
+    assert_eq!(
+      median_offset_ms(Vec::new()),
+      None
+    );
+  }
 
-fn midi_note_name(
-  midi_note: u8
-) -> String {
-  const NOTE_NAMES: [&str; 12] = [
-    "C", "C#", "D", "D#", "E", "F",
-    "F#", "G", "G#", "A", "A#", "B"
-  ];
+  #[test]
+  fn prepare_song_follows_tempo_map() {
+    let song = SongFile {
+      events: vec![
+        SongEvent {
+          at_beats: 0.0,
+          duration_beats: 1.0,
+          notes: vec![60],
+          ..SongEvent::default()
+        },
+        SongEvent {
+          at_beats: 2.0,
+          duration_beats: 2.0,
+          notes: vec![62],
+          ..SongEvent::default()
+        },
+        SongEvent {
+          at_beats: 4.0,
+          duration_beats: 2.0,
+          notes: vec![64],
+          ..SongEvent::default()
+        },
+      ],
+      tempo_map: vec![
+        (0.0, 120.0),
+        (3.0, 60.0),
+      ],
+      ..SongFile::default()
+    };
 
-  let note_name = NOTE_NAMES
-    [usize::from(midi_note % 12)];
-  let octave =
-    i16::from(midi_note / 12) - 1;
+    let prepared =
+      prepare_song(&song, false);
+    let timing = prepared
+      .events
+      .iter()
+      .map(|event| {
+        (
+          event.at_seconds,
+          event.duration_seconds
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(timing, vec![
+      (0.0, 0.5),
+      (1.0, 1.5),
+      (2.5, 2.0)
+    ]);
+    assert_eq!(
+      prepared.expected_notes[2]
+        .at_seconds,
+      2.5
+    );
+    assert_eq!(
+      prepared.duration_seconds,
+      4.5
+    );
+    assert_eq!(
+      prepared.beat_times[..6],
+      [0.0, 0.5, 1.0, 1.5, 2.5, 3.5]
+    );
+    assert_eq!(
+      prepared.beat_at_seconds(2.0),
+      3.5
+    );
+    assert_eq!(
+      prepared.seconds_at_beat(4.5),
+      3.0
+    );
+  }
+
+  #[test]
+  fn playback_speed_change_keeps_cursor()
+   {
+    let now = Instant::now();
+    let started_at =
+      playback_started_at(
+        now, 4.0, 1.0
+      );
+    let later =
+      now + Duration::from_secs(2);
+    assert!(
+      (playback_elapsed(
+        started_at, later, 1.0
+      ) - 6.0)
+        .abs()
+        < 1e-3
+    );
+
+    let slowed = playback_started_at(
+      later,
+      playback_elapsed(
+        started_at, later, 1.0
+      ),
+      0.5
+    );
+    assert!(
+      (playback_elapsed(
+        slowed, later, 0.5
+      ) - 6.0)
+        .abs()
+        < 1e-3
+    );
+    assert!(
+      (playback_elapsed(
+        slowed,
+        later + Duration::from_secs(2),
+        0.5
+      ) - 7.0)
+        .abs()
+        < 1e-3
+    );
+  }
 
-  format!("{note_name}{octave}")
+  #[test]
+  fn beat_grid_covers_rests_and_held_notes()
+   {
+    assert_eq!(
+      beats_crossed(1.0, 5.0)
+        .collect::<Vec<_>>(),
+      vec![2.0, 3.0, 4.0, 5.0]
+    );
+    assert_eq!(
+      beats_crossed(-1.0, 0.0)
+        .collect::<Vec<_>>(),
+      vec![0.0]
+    );
+    assert_eq!(
+      beats_crossed(3.0, 3.0).count(),
+      0
+    );
+  }
 }
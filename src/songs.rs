@@ -1,7 +1,8 @@
 use std::collections::hash_map::DefaultHasher;
 use std::collections::{
   BTreeMap,
-  HashMap
+  HashMap,
+  HashSet
 };
 use std::fs;
 use std::hash::{
@@ -36,32 +37,184 @@ use tracing::{
   warn
 };
 
-use crate::config::SongLibraryConfig;
+use crate::config::{
+  ChordGrouping,
+  DuplicateSourcePreference,
+  MidiImportConfig,
+  SongLibraryConfig
+};
 
-const SONG_CACHE_VERSION: u16 = 2;
+const SONG_CACHE_VERSION: u16 = 6;
+const LIBRARY_INDEX_VERSION: u16 = 3;
+pub const OUTLINE_COLUMNS: usize = 48;
+pub const OUTLINE_ROWS: usize = 8;
+const CACHE_REBUILD_PROGRESS_STEP:
+  usize = 25;
+const SONG_PARSER_VERSION: &str =
+  env!("CARGO_PKG_VERSION");
+const MIN_TEMPO_BPM: f32 = 10.0;
+const MAX_TEMPO_BPM: f32 = 400.0;
+const EXPORT_TICKS_PER_BEAT: u16 = 480;
+const MIN_SONG_GAIN: f32 = 0.1;
+const MAX_SONG_GAIN: f32 = 2.0;
+const GM_PROGRAM_NAMES: [&str; 128] = [
+  "Acoustic Grand Piano",
+  "Bright Acoustic Piano",
+  "Electric Grand Piano",
+  "Honky-tonk Piano",
+  "Electric Piano 1",
+  "Electric Piano 2",
+  "Harpsichord",
+  "Clavinet",
+  "Celesta",
+  "Glockenspiel",
+  "Music Box",
+  "Vibraphone",
+  "Marimba",
+  "Xylophone",
+  "Tubular Bells",
+  "Dulcimer",
+  "Drawbar Organ",
+  "Percussive Organ",
+  "Rock Organ",
+  "Church Organ",
+  "Reed Organ",
+  "Accordion",
+  "Harmonica",
+  "Tango Accordion",
+  "Acoustic Guitar (nylon)",
+  "Acoustic Guitar (steel)",
+  "Electric Guitar (jazz)",
+  "Electric Guitar (clean)",
+  "Electric Guitar (muted)",
+  "Overdriven Guitar",
+  "Distortion Guitar",
+  "Guitar Harmonics",
+  "Acoustic Bass",
+  "Electric Bass (finger)",
+  "Electric Bass (pick)",
+  "Fretless Bass",
+  "Slap Bass 1",
+  "Slap Bass 2",
+  "Synth Bass 1",
+  "Synth Bass 2",
+  "Violin",
+  "Viola",
+  "Cello",
+  "Contrabass",
+  "Tremolo Strings",
+  "Pizzicato Strings",
+  "Orchestral Harp",
+  "Timpani",
+  "String Ensemble 1",
+  "String Ensemble 2",
+  "Synth Strings 1",
+  "Synth Strings 2",
+  "Choir Aahs",
+  "Voice Oohs",
+  "Synth Voice",
+  "Orchestra Hit",
+  "Trumpet",
+  "Trombone",
+  "Tuba",
+  "Muted Trumpet",
+  "French Horn",
+  "Brass Section",
+  "Synth Brass 1",
+  "Synth Brass 2",
+  "Soprano Sax",
+  "Alto Sax",
+  "Tenor Sax",
+  "Baritone Sax",
+  "Oboe",
+  "English Horn",
+  "Bassoon",
+  "Clarinet",
+  "Piccolo",
+  "Flute",
+  "Recorder",
+  "Pan Flute",
+  "Blown Bottle",
+  "Shakuhachi",
+  "Whistle",
+  "Ocarina",
+  "Lead 1 (square)",
+  "Lead 2 (sawtooth)",
+  "Lead 3 (calliope)",
+  "Lead 4 (chiff)",
+  "Lead 5 (charang)",
+  "Lead 6 (voice)",
+  "Lead 7 (fifths)",
+  "Lead 8 (bass + lead)",
+  "Pad 1 (new age)",
+  "Pad 2 (warm)",
+  "Pad 3 (polysynth)",
+  "Pad 4 (choir)",
+  "Pad 5 (bowed)",
+  "Pad 6 (metallic)",
+  "Pad 7 (halo)",
+  "Pad 8 (sweep)",
+  "FX 1 (rain)",
+  "FX 2 (soundtrack)",
+  "FX 3 (crystal)",
+  "FX 4 (atmosphere)",
+  "FX 5 (brightness)",
+  "FX 6 (goblins)",
+  "FX 7 (echoes)",
+  "FX 8 (sci-fi)",
+  "Sitar",
+  "Banjo",
+  "Shamisen",
+  "Koto",
+  "Kalimba",
+  "Bagpipe",
+  "Fiddle",
+  "Shanai",
+  "Tinkle Bell",
+  "Agogo",
+  "Steel Drums",
+  "Woodblock",
+  "Taiko Drum",
+  "Melodic Tom",
+  "Synth Drum",
+  "Reverse Cymbal",
+  "Guitar Fret Noise",
+  "Breath Noise",
+  "Seashore",
+  "Bird Tweet",
+  "Telephone Ring",
+  "Helicopter",
+  "Applause",
+  "Gunshot"
+];
 
 #[derive(
   Debug, Clone, Serialize, Deserialize,
 )]
 #[serde(default)]
 pub struct SongFile {
-  pub version:  u16,
-  pub schema:   String,
-  pub meta:     SongMetadata,
-  pub sections: Vec<SongSection>,
-  pub events:   Vec<SongEvent>
+  pub version:     u16,
+  pub schema:      String,
+  pub meta:        SongMetadata,
+  pub sections:    Vec<SongSection>,
+  pub events:      Vec<SongEvent>,
+  pub note_stream: Vec<NoteStreamEvent>,
+  pub tempo_map:   Vec<(f32, f32)>
 }
 
 impl Default for SongFile {
   fn default() -> Self {
     Self {
-      version:  1,
-      schema:   "res/songs/schema/\
-                 song.schema.json"
+      version:     1,
+      schema:      "res/songs/schema/\
+                    song.schema.json"
         .to_string(),
-      meta:     SongMetadata::default(),
-      sections: Vec::new(),
-      events:   Vec::new()
+      meta:
+        SongMetadata::default(),
+      sections:    Vec::new(),
+      events:      Vec::new(),
+      note_stream: Vec::new(),
+      tempo_map:   Vec::new()
     }
   }
 }
@@ -85,7 +238,43 @@ pub struct SongMetadata {
   pub tags:             Vec<String>,
   pub source_url:       String,
   pub sort_order:       i32,
-  pub default_velocity: u8
+  pub default_velocity: u8,
+  pub gm_program:       Option<u8>,
+  pub gain:             Option<f32>,
+  pub metronome: MetronomeOverride
+}
+
+impl SongFile {
+  pub fn beats_to_seconds(
+    &self,
+    beats: f32
+  ) -> f32 {
+    let mut bpm =
+      self.meta.tempo_bpm.max(1.0);
+    if self.tempo_map.is_empty() {
+      return beats * 60.0 / bpm;
+    }
+
+    let mut seconds = 0.0;
+    let mut cursor = 0.0;
+    for &(at_beats, tempo) in
+      &self.tempo_map
+    {
+      if at_beats >= beats {
+        break;
+      }
+      if at_beats > cursor {
+        seconds += (at_beats - cursor)
+          * 60.0
+          / bpm;
+        cursor = at_beats;
+      }
+      bpm = tempo.max(1.0);
+    }
+
+    seconds
+      + (beats - cursor) * 60.0 / bpm
+  }
 }
 
 impl Default for SongMetadata {
@@ -108,11 +297,48 @@ impl Default for SongMetadata {
       tags:             Vec::new(),
       source_url:       String::new(),
       sort_order:       0,
-      default_velocity: 96
+      default_velocity: 96,
+      gm_program:       None,
+      gain:             None,
+      metronome:
+        MetronomeOverride::default()
     }
   }
 }
 
+impl NoteStreamEvent {
+  fn is_note_on(&self) -> bool {
+    self.on
+      && !self.velocity.is_some_and(
+        is_note_off_velocity
+      )
+  }
+}
+
+pub fn is_note_off_velocity(
+  velocity: u8
+) -> bool {
+  velocity == 0
+}
+
+impl SongMetadata {
+  pub fn gain(&self) -> f32 {
+    self.gain.unwrap_or(1.0)
+  }
+
+  pub fn gm_program_name(
+    &self
+  ) -> Option<&'static str> {
+    self.gm_program.and_then(
+      |program| {
+        GM_PROGRAM_NAMES
+          .get(usize::from(program))
+          .copied()
+      }
+    )
+  }
+}
+
 #[derive(
   Debug, Clone, Serialize, Deserialize,
 )]
@@ -122,7 +348,9 @@ pub struct SongSection {
   pub label:       String,
   pub start_beats: f32,
   pub end_beats:   f32,
-  pub looped:      bool
+  pub looped:      bool,
+  pub tempo_bpm:   Option<f32>,
+  pub loop_count:  Option<u32>
 }
 
 impl Default for SongSection {
@@ -132,13 +360,54 @@ impl Default for SongSection {
       label:       String::new(),
       start_beats: 0.0,
       end_beats:   0.0,
-      looped:      false
+      looped:      false,
+      tempo_bpm:   None,
+      loop_count:  None
     }
   }
 }
 
 #[derive(
-  Debug, Clone, Serialize, Deserialize,
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MetronomeOverride {
+  #[default]
+  Default,
+  On,
+  Off
+}
+
+impl MetronomeOverride {
+  pub fn resolve(
+    self,
+    globally_enabled: bool
+  ) -> bool {
+    match self {
+      | Self::Default => {
+        globally_enabled
+      }
+      | Self::On => true,
+      | Self::Off => false
+    }
+  }
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
 )]
 #[serde(rename_all = "snake_case")]
 pub enum Hand {
@@ -157,6 +426,8 @@ pub struct SongEvent {
   pub notes:          Vec<u8>,
   pub velocity:       Option<u8>,
   pub hand:           Option<Hand>,
+  pub channel:        Option<u8>,
+  pub fingering:      Vec<u8>,
   pub lyric:          Option<String>,
   pub accent:         bool
 }
@@ -169,19 +440,62 @@ impl Default for SongEvent {
       notes:          Vec::new(),
       velocity:       None,
       hand:           None,
+      channel:        None,
+      fingering:      Vec::new(),
       lyric:          None,
       accent:         false
     }
   }
 }
 
+#[derive(
+  Debug, Clone, Serialize, Deserialize,
+)]
+#[serde(default)]
+pub struct NoteStreamEvent {
+  pub at_beats: f32,
+  pub note:     u8,
+  pub on:       bool,
+  pub velocity: Option<u8>,
+  pub hand:     Option<Hand>,
+  pub channel:  Option<u8>
+}
+
+impl Default for NoteStreamEvent {
+  fn default() -> Self {
+    Self {
+      at_beats: 0.0,
+      note:     60,
+      on:       true,
+      velocity: None,
+      hand:     None,
+      channel:  None
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct LoadedSong {
-  pub path: PathBuf,
-  pub song: SongFile
+  pub path:    PathBuf,
+  pub song:    SongFile,
+  pub parsed:  bool,
+  pub outline: Vec<u64>
 }
 
 impl LoadedSong {
+  fn parsed(
+    path: PathBuf,
+    song: SongFile
+  ) -> Self {
+    let outline = song_outline(&song);
+    Self {
+      path,
+      song,
+      parsed: true,
+      outline
+    }
+  }
+
   pub fn duration_beats(&self) -> f32 {
     self
       .song
@@ -193,6 +507,120 @@ impl LoadedSong {
       })
       .fold(0.0, f32::max)
   }
+
+  pub fn duration_bars(&self) -> f32 {
+    self.duration_beats()
+      / f32::from(
+        self
+          .song
+          .meta
+          .beats_per_bar
+          .max(1)
+      )
+  }
+}
+
+/// Coarse piano-roll of a song for
+/// list thumbnails: one bitmask per
+/// pitch band, highest band first, with
+/// bit `c` set when any note sounds in
+/// time column `c`. Empty for songs
+/// without notes.
+pub fn song_outline(
+  song: &SongFile
+) -> Vec<u64> {
+  let notes = song
+    .events
+    .iter()
+    .flat_map(|event| {
+      event.notes.iter().copied()
+    });
+  let (Some(lowest), Some(highest)) =
+    (notes.clone().min(), notes.max())
+  else {
+    return Vec::new();
+  };
+  let pitch_span =
+    usize::from(highest - lowest) + 1;
+  let total_beats = song
+    .events
+    .iter()
+    .map(|event| {
+      event.at_beats
+        + event.duration_beats
+    })
+    .fold(0.0, f32::max)
+    .max(f32::EPSILON);
+
+  let mut rows =
+    vec![0_u64; OUTLINE_ROWS];
+  for event in &song.events {
+    let start =
+      ((event.at_beats / total_beats)
+        * OUTLINE_COLUMNS as f32)
+        .floor()
+        .max(0.0) as usize;
+    let end = (((event.at_beats
+      + event.duration_beats)
+      / total_beats)
+      * OUTLINE_COLUMNS as f32)
+      .ceil() as usize;
+    let start =
+      start.min(OUTLINE_COLUMNS - 1);
+    let end = end.clamp(
+      start + 1,
+      OUTLINE_COLUMNS
+    );
+    let columns = (start..end)
+      .fold(0_u64, |mask, column| {
+        mask | 1 << column
+      });
+
+    for note in &event.notes {
+      let offset =
+        usize::from(highest - note);
+      rows[offset * OUTLINE_ROWS
+        / pitch_span] |= columns;
+    }
+  }
+
+  rows
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+)]
+pub struct LibraryLoadStats {
+  pub cache_hits:   usize,
+  pub cache_misses: usize,
+  pub indexed:      usize
+}
+
+impl LibraryLoadStats {
+  fn total(&self) -> usize {
+    self.cache_hits
+      + self.cache_misses
+      + self.indexed
+  }
+
+  pub fn rebuilt_cache(&self) -> bool {
+    self.cache_misses > 0
+      && self.cache_misses * 2
+        > self.total()
+  }
+}
+
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq,
+)]
+enum CacheLookup {
+  Hit,
+  Miss
 }
 
 #[derive(Debug, Clone)]
@@ -245,24 +673,64 @@ struct SourceFingerprint {
   Debug, Clone, Serialize, Deserialize,
 )]
 struct CachedSongFile {
-  cache_version: u16,
-  source_path:   String,
-  source_kind:   SourceKind,
-  fingerprint:   SourceFingerprint,
-  song:          SongFile
+  cache_version:      u16,
+  source_path:        String,
+  source_kind:        SourceKind,
+  fingerprint:        SourceFingerprint,
+  #[serde(default)]
+  parser_config_hash: u64,
+  song:               SongFile
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Default,
+  Serialize,
+  Deserialize,
+)]
+#[serde(default)]
+struct LibraryIndex {
+  index_version: u16,
+  entries:       Vec<LibraryIndexEntry>
+}
+
+#[derive(
+  Debug, Clone, Serialize, Deserialize,
+)]
+struct LibraryIndexEntry {
+  source_path:        String,
+  source_kind:        SourceKind,
+  fingerprint:        SourceFingerprint,
+  parser_config_hash: u64,
+  meta:               SongMetadata,
+  #[serde(default)]
+  schema:             String,
+  outline:            Vec<u64>
+}
+
+#[derive(Debug, Default)]
+struct MidiTextMeta {
+  track_name: Option<String>,
+  copyright:  Option<String>,
+  markers:    Vec<(u64, String)>
 }
 
 #[derive(Debug, Clone, Copy)]
 struct MidiNoteRange {
   start_tick: u64,
   end_tick:   u64,
+  channel:    u8,
   note:       u8,
   velocity:   u8
 }
 
 pub fn load_song_library(
   config: &SongLibraryConfig
-) -> Result<Vec<LoadedSong>> {
+) -> Result<(
+  Vec<LoadedSong>,
+  LibraryLoadStats
+)> {
   let songs_root =
     Path::new(&config.directory);
   let midi_root =
@@ -270,7 +738,10 @@ pub fn load_song_library(
   let cache_root =
     Path::new(&config.cache_directory);
 
-  ensure_cache_dirs(cache_root)?;
+  ensure_cache_dirs(
+    cache_root,
+    config.cache_enabled
+  )?;
 
   let mut sources = Vec::new();
   sources.extend(
@@ -287,15 +758,87 @@ pub fn load_song_library(
     left.path.cmp(&right.path)
   });
 
+  let index_path =
+    (!config.index_file.is_empty())
+      .then(|| {
+        Path::new(&config.index_file)
+      });
+  let index = index_path
+    .map(read_library_index)
+    .unwrap_or_default();
+  let mut next_index = LibraryIndex {
+    index_version:
+      LIBRARY_INDEX_VERSION,
+    entries:       Vec::new()
+  };
+
   let mut loaded = Vec::new();
   let mut midi_loaded = 0usize;
   let mut toml_loaded = 0usize;
+  let mut stats =
+    LibraryLoadStats::default();
+  let total_sources = sources.len();
 
   for source in sources {
+    if config.cache_enabled
+      && stats.rebuilt_cache()
+      && stats.total() > 0
+      && stats.total().is_multiple_of(
+        CACHE_REBUILD_PROGRESS_STEP
+      )
+    {
+      info!(
+        "rebuilding song cache: {}/{}",
+        stats.total(),
+        total_sources
+      );
+    }
+
+    if index_path.is_some()
+      && let Some(entry) =
+        fresh_index_entry(
+          &index, &source, config
+        )
+    {
+      stats.indexed += 1;
+      let song = SongFile {
+        meta: entry.meta.clone(),
+        schema: entry.schema.clone(),
+        ..SongFile::default()
+      };
+      warn_on_schema_issue(
+        config, &source, &song
+      );
+      loaded.push((
+        source.kind,
+        LoadedSong {
+          path: source.path.clone(),
+          song,
+          parsed: false,
+          outline: entry
+            .outline
+            .clone()
+        }
+      ));
+      next_index.entries.push(entry);
+      continue;
+    }
+
     match load_source_with_cache(
       &source, config, cache_root
     ) {
-      | Ok(song) => {
+      | Ok((song, lookup)) => {
+        match lookup {
+          | CacheLookup::Hit => {
+            stats.cache_hits += 1
+          }
+          | CacheLookup::Miss => {
+            stats.cache_misses += 1
+          }
+        }
+        warn_on_schema_issue(
+          config, &source, &song.song
+        );
         match source.kind {
           | SourceKind::Toml => {
             toml_loaded += 1
@@ -304,7 +847,41 @@ pub fn load_song_library(
             midi_loaded += 1
           }
         }
-        loaded.push(song);
+        if index_path.is_some()
+          && let Ok(fingerprint) =
+            source_fingerprint(
+              &source.path
+            )
+        {
+          next_index.entries.push(
+            LibraryIndexEntry {
+              source_path: source
+                .path
+                .to_string_lossy()
+                .to_string(),
+              source_kind: source.kind,
+              fingerprint,
+              parser_config_hash:
+                parser_config_hash(
+                  config,
+                  source.kind
+                ),
+              meta: song
+                .song
+                .meta
+                .clone(),
+              schema: song
+                .song
+                .schema
+                .clone(),
+              outline: song
+                .outline
+                .clone()
+            }
+          );
+        }
+        loaded
+          .push((source.kind, song));
       }
       | Err(error) => {
         warn!(path = %source.path.display(), source_kind = ?source.kind, error = %error, "skipping invalid song source")
@@ -312,6 +889,22 @@ pub fn load_song_library(
     }
   }
 
+  if let Some(index_path) = index_path
+    && let Err(error) =
+      write_library_index(
+        index_path,
+        &next_index
+      )
+  {
+    warn!(index_path = %index_path.display(), error = %error, "failed writing song library index");
+  }
+
+  let (mut loaded, duplicates_hidden) =
+    dedupe_mixed_sources(
+      loaded,
+      config.duplicate_sources
+    );
+
   loaded.sort_by(|left, right| {
     left
       .song
@@ -327,34 +920,301 @@ pub fn load_song_library(
       )
   });
 
-  info!(songs_loaded = loaded.len(), toml_loaded, midi_loaded, cache_root = %cache_root.display(), "song library loaded");
+  info!(songs_loaded = loaded.len(), toml_loaded, midi_loaded, cache_hits = stats.cache_hits, cache_misses = stats.cache_misses, indexed = stats.indexed, duplicates_hidden, cache_root = %cache_root.display(), cache_enabled = config.cache_enabled, "song library loaded");
+
+  Ok((loaded, stats))
+}
+
+pub fn ensure_song_parsed(
+  loaded: &mut LoadedSong,
+  config: &SongLibraryConfig
+) -> Result<()> {
+  if loaded.parsed {
+    return Ok(());
+  }
+
+  let is_midi = loaded
+    .path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .is_some_and(|ext| {
+      ext.eq_ignore_ascii_case("mid")
+        || ext
+          .eq_ignore_ascii_case("midi")
+    });
+  let source = SongSource {
+    kind: if is_midi {
+      SourceKind::Midi
+    } else {
+      SourceKind::Toml
+    },
+    path: loaded.path.clone()
+  };
+
+  (*loaded, _) =
+    load_source_with_cache(
+      &source,
+      config,
+      Path::new(
+        &config.cache_directory
+      )
+    )?;
+  debug!(path = %loaded.path.display(), "parsed indexed song on selection");
+
+  Ok(())
+}
+
+fn read_library_index(
+  index_path: &Path
+) -> LibraryIndex {
+  let Ok(raw) =
+    fs::read_to_string(index_path)
+  else {
+    return LibraryIndex::default();
+  };
+
+  match toml::from_str::<LibraryIndex>(
+    &raw
+  ) {
+    | Ok(index)
+      if index.index_version
+        == LIBRARY_INDEX_VERSION =>
+    {
+      index
+    }
+    | Ok(_) => {
+      debug!(index_path = %index_path.display(), "stale song library index version");
+      LibraryIndex::default()
+    }
+    | Err(error) => {
+      warn!(index_path = %index_path.display(), error = %error, "ignoring unreadable song library index");
+      LibraryIndex::default()
+    }
+  }
+}
+
+fn warn_on_schema_issue(
+  config: &SongLibraryConfig,
+  source: &SongSource,
+  song: &SongFile
+) {
+  if config.enforce_schema
+    && source.kind == SourceKind::Toml
+    && let Some(issue) =
+      song_schema_issue(
+        song,
+        &source.path,
+        &config.schema_path
+      )
+  {
+    warn!(path = %source.path.display(), %issue, "song schema check failed");
+  }
+}
+
+fn fresh_index_entry(
+  index: &LibraryIndex,
+  source: &SongSource,
+  config: &SongLibraryConfig
+) -> Option<LibraryIndexEntry> {
+  let source_path =
+    source.path.to_string_lossy();
+  let entry = index
+    .entries
+    .iter()
+    .find(|entry| {
+      entry.source_kind == source.kind
+        && entry.source_path
+          == source_path
+    })?;
+  let fingerprint =
+    source_fingerprint(&source.path)
+      .ok()?;
+
+  (entry.fingerprint == fingerprint
+    && entry.parser_config_hash
+      == parser_config_hash(
+        config,
+        source.kind
+      ))
+  .then(|| entry.clone())
+}
+
+fn write_library_index(
+  index_path: &Path,
+  index: &LibraryIndex
+) -> Result<()> {
+  if let Some(parent) =
+    index_path.parent()
+    && !parent.as_os_str().is_empty()
+  {
+    fs::create_dir_all(parent)
+      .with_context(|| {
+        format!(
+          "failed creating index \
+           directory {}",
+          parent.display()
+        )
+      })?;
+  }
+
+  let rendered =
+    toml::to_string_pretty(index)
+      .context(
+        "failed serializing song \
+         library index"
+      )?;
+
+  fs::write(index_path, rendered)
+    .with_context(|| {
+      format!(
+        "failed writing index {}",
+        index_path.display()
+      )
+    })?;
+
+  Ok(())
+}
+
+fn dedupe_mixed_sources(
+  loaded: Vec<(SourceKind, LoadedSong)>,
+  preference: DuplicateSourcePreference
+) -> (Vec<LoadedSong>, usize) {
+  let preferred_kind = match preference
+  {
+    | DuplicateSourcePreference::KeepBoth => {
+      return (
+        loaded
+          .into_iter()
+          .map(|(_, song)| song)
+          .collect(),
+        0
+      );
+    }
+    | DuplicateSourcePreference::PreferToml => {
+      SourceKind::Toml
+    }
+    | DuplicateSourcePreference::PreferMidi => {
+      SourceKind::Midi
+    }
+  };
+
+  let preferred_ids = loaded
+    .iter()
+    .filter(|(kind, _)| {
+      *kind == preferred_kind
+    })
+    .map(|(_, song)| {
+      sanitize_song_id(
+        &song.song.meta.id
+      )
+    })
+    .collect::<HashSet<_>>();
+
+  let mut kept = Vec::new();
+  let mut hidden = 0usize;
+  for (kind, song) in loaded {
+    if kind != preferred_kind
+      && preferred_ids.contains(
+        &sanitize_song_id(
+          &song.song.meta.id
+        )
+      )
+    {
+      info!(path = %song.path.display(), id = %song.song.meta.id, "hiding duplicate song source");
+      hidden += 1;
+      continue;
+    }
+    kept.push(song);
+  }
+
+  (kept, hidden)
+}
+
+fn parser_config_hash(
+  config: &SongLibraryConfig,
+  kind: SourceKind
+) -> u64 {
+  let import_settings = match kind {
+    | SourceKind::Toml => String::new(),
+    | SourceKind::Midi => {
+      format!(
+        "{};schema_path={};\
+         default_tags={};\
+         default_artist={}",
+        config.midi_import.signature(),
+        config.schema_path,
+        config
+          .midi_default_tags
+          .join(","),
+        config.midi_default_artist
+      )
+    }
+  };
+
+  fnv1a_hash(&[
+    SONG_PARSER_VERSION,
+    &config.cache_key_salt,
+    &import_settings
+  ])
+}
 
-  Ok(loaded)
+/// FNV-1a over length-prefixed parts.
+/// Unlike `DefaultHasher`, the output
+/// is fixed across Rust releases, so it
+/// is safe to persist in caches and the
+/// library index.
+fn fnv1a_hash(parts: &[&str]) -> u64 {
+  const OFFSET_BASIS: u64 =
+    0xcbf2_9ce4_8422_2325;
+  const PRIME: u64 = 0x0100_0000_01b3;
+
+  parts
+    .iter()
+    .flat_map(|part| {
+      (part.len() as u64)
+        .to_le_bytes()
+        .into_iter()
+        .chain(part.bytes())
+    })
+    .fold(OFFSET_BASIS, |hash, byte| {
+      (hash ^ u64::from(byte))
+        .wrapping_mul(PRIME)
+    })
 }
 
 fn load_source_with_cache(
   source: &SongSource,
   config: &SongLibraryConfig,
   cache_root: &Path
-) -> Result<LoadedSong> {
+) -> Result<(LoadedSong, CacheLookup)> {
   let fingerprint =
     source_fingerprint(&source.path)?;
   let cache_path =
     cache_path_for_source(
       cache_root, source
     );
+  let parser_config_hash =
+    parser_config_hash(
+      config,
+      source.kind
+    );
 
-  if let Some(song) =
-    load_cached_song_if_fresh(
-      &cache_path,
-      source,
-      &fingerprint
-    )?
+  if config.cache_enabled
+    && let Some(song) =
+      load_cached_song_if_fresh(
+        &cache_path,
+        source,
+        &fingerprint,
+        parser_config_hash
+      )?
   {
-    return Ok(LoadedSong {
-      path: source.path.clone(),
-      song
-    });
+    return Ok((
+      LoadedSong::parsed(
+        source.path.clone(),
+        song
+      ),
+      CacheLookup::Hit
+    ));
   }
 
   let mut song = match source.kind {
@@ -367,7 +1227,10 @@ fn load_source_with_cache(
         &config.schema_path,
         Path::new(
           &config.midi_directory
-        )
+        ),
+        &config.midi_import,
+        &config.midi_default_tags,
+        &config.midi_default_artist
       )?
     }
   };
@@ -377,22 +1240,37 @@ fn load_source_with_cache(
     &source.path
   )?;
 
-  write_cached_song(
-    &cache_path,
-    source,
-    &fingerprint,
-    &song
-  )?;
-
-  Ok(LoadedSong {
-    path: source.path.clone(),
-    song
-  })
-}
+  if config.cache_enabled {
+    write_cached_song(
+      &cache_path,
+      source,
+      &fingerprint,
+      parser_config_hash,
+      &song
+    )?;
+  }
+
+  Ok((
+    LoadedSong::parsed(
+      source.path.clone(),
+      song
+    ),
+    CacheLookup::Miss
+  ))
+}
 
 fn ensure_cache_dirs(
-  cache_root: &Path
+  cache_root: &Path,
+  enabled: bool
 ) -> Result<()> {
+  if !enabled {
+    debug!(
+      cache_root = %cache_root.display(),
+      "song cache disabled"
+    );
+    return Ok(());
+  }
+
   for kind in
     [SourceKind::Toml, SourceKind::Midi]
   {
@@ -622,7 +1500,8 @@ fn sanitize_for_cache(
 fn load_cached_song_if_fresh(
   cache_path: &Path,
   source: &SongSource,
-  fingerprint: &SourceFingerprint
+  fingerprint: &SourceFingerprint,
+  parser_config_hash: u64
 ) -> Result<Option<SongFile>> {
   if !cache_path.exists() {
     return Ok(None);
@@ -669,6 +1548,13 @@ fn load_cached_song_if_fresh(
     return Ok(None);
   }
 
+  if cached.parser_config_hash
+    != parser_config_hash
+  {
+    debug!(cache_path = %cache_path.display(), "stale parser settings");
+    return Ok(None);
+  }
+
   let mut song = cached.song;
   finalize_song(
     &mut song,
@@ -684,6 +1570,7 @@ fn write_cached_song(
   cache_path: &Path,
   source: &SongSource,
   fingerprint: &SourceFingerprint,
+  parser_config_hash: u64,
   song: &SongFile
 ) -> Result<()> {
   if let Some(parent) =
@@ -701,13 +1588,14 @@ fn write_cached_song(
 
   let payload = CachedSongFile {
     cache_version: SONG_CACHE_VERSION,
-    source_path:   source
+    source_path: source
       .path
       .to_string_lossy()
       .to_string(),
-    source_kind:   source.kind,
-    fingerprint:   fingerprint.clone(),
-    song:          song.clone()
+    source_kind: source.kind,
+    fingerprint: fingerprint.clone(),
+    parser_config_hash,
+    song: song.clone()
   };
 
   let rendered =
@@ -728,6 +1616,232 @@ fn write_cached_song(
   Ok(())
 }
 
+pub fn normalize_song_file(
+  path: &Path
+) -> Result<bool> {
+  let original =
+    fs::read_to_string(path)
+      .with_context(|| {
+        format!(
+          "failed reading {}",
+          path.display()
+        )
+      })?;
+
+  let mut song = parse_toml_song(path)?;
+  finalize_song(
+    &mut song.clone(),
+    path
+  )?;
+  tidy_song_events(&mut song, path);
+
+  let rendered =
+    toml::to_string_pretty(&song)
+      .with_context(|| {
+        format!(
+          "failed serializing {}",
+          path.display()
+        )
+      })?;
+  if rendered == original {
+    return Ok(false);
+  }
+
+  fs::write(path, rendered)
+    .with_context(|| {
+      format!(
+        "failed writing {}",
+        path.display()
+      )
+    })?;
+  info!(path = %path.display(), song_id = %song.meta.id, "song file normalized");
+
+  Ok(true)
+}
+
+pub fn export_midi(
+  song: &SongFile,
+  path: &Path
+) -> Result<()> {
+  use midly::num::{
+    u4,
+    u7,
+    u15,
+    u24,
+    u28
+  };
+  use midly::{
+    Format,
+    Header,
+    TrackEvent
+  };
+
+  let ticks_per_beat =
+    f32::from(EXPORT_TICKS_PER_BEAT);
+  let to_ticks = |beats: f32| {
+    (beats.max(0.0) * ticks_per_beat)
+      .round() as u64
+  };
+
+  let mut timed = Vec::new();
+  for event in &song.events {
+    let velocity = event
+      .velocity
+      .unwrap_or(
+        song.meta.default_velocity
+      )
+      .clamp(1, 127);
+    let channel = event.channel.map_or(
+      0,
+      |channel| {
+        channel
+          .saturating_sub(1)
+          .min(15)
+      }
+    );
+    let start =
+      to_ticks(event.at_beats);
+    let end = to_ticks(
+      event.at_beats
+        + event.duration_beats
+    )
+    .max(start + 1);
+    let channel = u4::new(channel);
+    for note in &event.notes {
+      let key =
+        u7::new((*note).min(127));
+      timed.push((
+        end,
+        0u8,
+        TrackEventKind::Midi {
+          channel,
+          message:
+            MidiMessage::NoteOff {
+              key,
+              vel: u7::new(0)
+            }
+        }
+      ));
+      timed.push((
+        start,
+        2u8,
+        TrackEventKind::Midi {
+          channel,
+          message:
+            MidiMessage::NoteOn {
+              key,
+              vel: u7::new(velocity)
+            }
+        }
+      ));
+    }
+  }
+
+  let tempo_meta = |bpm: f32| {
+    let micros_per_beat =
+      (60_000_000.0 / bpm.max(1.0))
+        .round()
+        .min(16_777_215.0) as u32;
+    TrackEventKind::Meta(
+      MetaMessage::Tempo(u24::new(
+        micros_per_beat
+      ))
+    )
+  };
+  for &(at_beats, bpm) in
+    &song.tempo_map
+  {
+    timed.push((
+      to_ticks(at_beats),
+      1u8,
+      tempo_meta(bpm)
+    ));
+  }
+  timed.sort_by_key(
+    |(tick, order, _)| (*tick, *order)
+  );
+
+  let mut track = vec![
+    TrackEvent {
+      delta: u28::new(0),
+      kind:  TrackEventKind::Meta(
+        MetaMessage::TrackName(
+          song.meta.title.as_bytes()
+        )
+      )
+    },
+    TrackEvent {
+      delta: u28::new(0),
+      kind:  tempo_meta(
+        song.meta.tempo_bpm
+      )
+    },
+    TrackEvent {
+      delta: u28::new(0),
+      kind:  TrackEventKind::Meta(
+        MetaMessage::TimeSignature(
+          song.meta.beats_per_bar,
+          song
+            .meta
+            .beat_unit
+            .max(1)
+            .ilog2() as u8,
+          24,
+          8
+        )
+      )
+    },
+  ];
+
+  let mut last_tick = 0u64;
+  for (tick, _, kind) in timed {
+    let delta =
+      u32::try_from(tick - last_tick)
+        .context(
+        "MIDI export gap too long"
+      )?;
+    last_tick = tick;
+    track.push(TrackEvent {
+      delta: u28::new(delta),
+      kind
+    });
+  }
+  track.push(TrackEvent {
+    delta: u28::new(0),
+    kind:  TrackEventKind::Meta(
+      MetaMessage::EndOfTrack
+    )
+  });
+
+  let mut smf = Smf::new(Header::new(
+    Format::SingleTrack,
+    Timing::Metrical(u15::new(
+      EXPORT_TICKS_PER_BEAT
+    ))
+  ));
+  smf.tracks.push(track);
+
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)
+      .with_context(|| {
+        format!(
+          "failed creating {}",
+          parent.display()
+        )
+      })?;
+  }
+  smf.save(path).with_context(
+    || {
+      format!(
+        "failed writing MIDI {}",
+        path.display()
+      )
+    }
+  )?;
+
+  Ok(())
+}
+
 fn parse_toml_song(
   path: &Path
 ) -> Result<SongFile> {
@@ -755,7 +1869,10 @@ fn parse_toml_song(
 fn parse_midi_song(
   path: &Path,
   schema_path: &str,
-  midi_root: &Path
+  midi_root: &Path,
+  options: &MidiImportConfig,
+  default_tags: &[String],
+  default_artist: &str
 ) -> Result<SongFile> {
   let bytes = fs::read(path)
     .with_context(|| {
@@ -773,12 +1890,6 @@ fn parse_midi_song(
       )
     })?;
 
-  let ticks_per_beat =
-    ticks_per_beat_from_timing(
-      smf.header.timing,
-      path
-    );
-
   let mut tempo_changes: Vec<(
     u64,
     u32
@@ -793,6 +1904,10 @@ fn parse_midi_song(
     Vec<(u64, u8)>
   > = HashMap::new();
   let mut note_ranges = Vec::new();
+  let mut text_meta =
+    MidiTextMeta::default();
+  let mut channel_programs =
+    BTreeMap::<u8, u8>::new();
 
   for track in &smf.tracks {
     let mut absolute_tick = 0_u64;
@@ -815,7 +1930,8 @@ fn parse_midi_song(
             channel,
             absolute_tick,
             &mut active_notes,
-            &mut note_ranges
+            &mut note_ranges,
+            &mut channel_programs
           );
         }
         | TrackEventKind::Meta(
@@ -825,7 +1941,8 @@ fn parse_midi_song(
             meta_message,
             absolute_tick,
             &mut tempo_changes,
-            &mut time_signature
+            &mut time_signature,
+            &mut text_meta
           );
         }
         | _ => {}
@@ -833,12 +1950,50 @@ fn parse_midi_song(
     }
   }
 
+  tempo_changes.sort_by(
+    |left, right| left.0.cmp(&right.0)
+  );
+
+  let tempo_micros = tempo_changes
+    .first()
+    .map(|(_, micros)| *micros)
+    .unwrap_or(500_000);
+  let tempo_bpm = (60_000_000.0
+    / tempo_micros as f32)
+    .clamp(
+      MIN_TEMPO_BPM,
+      MAX_TEMPO_BPM
+    );
+
+  let ticks_per_beat =
+    ticks_per_beat_from_timing(
+      smf.header.timing,
+      tempo_bpm,
+      options,
+      path
+    );
+
   close_unended_notes(
     &active_notes,
     ticks_per_beat,
     &mut note_ranges
   );
 
+  let tempo_map = if matches!(
+    smf.header.timing,
+    Timing::Metrical(_)
+  ) {
+    midi_tempo_map(
+      &tempo_changes,
+      ticks_per_beat
+    )
+  } else {
+    Vec::new()
+  };
+  if !tempo_map.is_empty() {
+    debug!(path = %path.display(), tempo_changes = tempo_map.len(), "MIDI file has tempo changes; storing tempo map");
+  }
+
   if note_ranges.is_empty() {
     bail!(
       "{} contains no playable MIDI \
@@ -847,28 +2002,31 @@ fn parse_midi_song(
     );
   }
 
-  tempo_changes.sort_by(
-    |left, right| left.0.cmp(&right.0)
-  );
+  let (beats_per_bar, beat_unit) =
+    time_signature.unwrap_or((4, 4));
 
-  if tempo_changes.len() > 1 {
-    warn!(path = %path.display(), tempo_changes = tempo_changes.len(), "MIDI file has tempo changes; using first tempo for current song format");
+  if options.merge_tied_notes {
+    let gap_ticks =
+      (options.tie_gap_beats
+        * ticks_per_beat as f32)
+        .round() as u64;
+    let before = note_ranges.len();
+    note_ranges =
+      merge_tied_note_ranges(
+        note_ranges,
+        gap_ticks
+      );
+    debug!(path = %path.display(), merged = before - note_ranges.len(), gap_ticks, "merged tied MIDI notes");
   }
 
-  let tempo_micros = tempo_changes
-    .first()
-    .map(|(_, micros)| *micros)
-    .unwrap_or(500_000);
-  let tempo_bpm = (60_000_000.0
-    / tempo_micros as f32)
-    .clamp(10.0, 400.0);
-
-  let (beats_per_bar, beat_unit) =
-    time_signature.unwrap_or((4, 4));
+  let gm_program = primary_gm_program(
+    &note_ranges,
+    &channel_programs
+  );
 
   let mut grouped = BTreeMap::<
-    (u64, u64, u8),
-    Vec<u8>
+    (u64, u64, u8, u8),
+    Vec<(u8, u64)>
   >::new();
   let mut velocity_sum = 0_u32;
 
@@ -882,14 +2040,28 @@ fn parse_midi_song(
     velocity_sum +=
       u32::from(range.velocity);
 
+    let end_key = match options
+      .chord_grouping
+    {
+      | ChordGrouping::ExactDuration => {
+        range.end_tick
+      }
+      | ChordGrouping::OnsetLongest
+      | ChordGrouping::OnsetShortest => 0
+    };
+
     grouped
       .entry((
         range.start_tick,
-        range.end_tick,
-        range.velocity
+        end_key,
+        range.velocity,
+        range.channel
       ))
       .or_default()
-      .push(range.note);
+      .push((
+        range.note,
+        range.end_tick
+      ));
   }
 
   if grouped.is_empty() {
@@ -912,10 +2084,29 @@ fn parse_midi_song(
 
   let mut events = Vec::new();
   for (
-    (start_tick, end_tick, velocity),
-    mut notes
+    (start_tick, _, velocity, channel),
+    members
   ) in grouped
   {
+    let ends = members
+      .iter()
+      .map(|(_, end_tick)| *end_tick);
+    let end_tick = match options
+      .chord_grouping
+    {
+      | ChordGrouping::OnsetShortest => {
+        ends.min()
+      }
+      | ChordGrouping::ExactDuration
+      | ChordGrouping::OnsetLongest => {
+        ends.max()
+      }
+    }
+    .unwrap_or(start_tick);
+    let mut notes = members
+      .into_iter()
+      .map(|(note, _)| note)
+      .collect::<Vec<_>>();
     notes.sort_unstable();
     notes.dedup();
 
@@ -936,24 +2127,59 @@ fn parse_midi_song(
       notes,
       velocity: Some(velocity),
       hand: None,
+      channel: Some(channel + 1),
+      fingering: Vec::new(),
       lyric: None,
       accent: false
     });
   }
 
+  let song_end_beats = events
+    .iter()
+    .map(|event| {
+      event.at_beats
+        + event.duration_beats
+    })
+    .fold(0.0_f32, f32::max);
+  let sections = marker_sections(
+    &text_meta.markers,
+    ticks_per_beat,
+    song_end_beats
+  );
+
   let file_stem = path
     .file_stem()
     .and_then(|stem| stem.to_str())
     .unwrap_or("untitled");
   let id = sanitize_song_id(file_stem);
-  let title =
-    humanize_song_title(file_stem);
+  let title = text_meta
+    .track_name
+    .clone()
+    .unwrap_or_else(|| {
+      humanize_song_title(file_stem)
+    });
+  let description =
+    match &text_meta.copyright {
+      | Some(copyright) => {
+        format!(
+          "Imported from MIDI file {} \
+           ({copyright})",
+          path.display()
+        )
+      }
+      | None => {
+        format!(
+          "Imported from MIDI file {}",
+          path.display()
+        )
+      }
+    };
   let mut path_tags =
     midi_folder_tags(path, midi_root);
-  let mut tags = vec![
-    "midi".to_string(),
-    "imported".to_string(),
-  ];
+  let mut tags = default_tags
+    .iter()
+    .map(|tag| tag.trim().to_string())
+    .collect::<Vec<_>>();
   tags.append(&mut path_tags);
   tags.sort();
   tags.dedup();
@@ -964,14 +2190,12 @@ fn parse_midi_song(
     meta: SongMetadata {
       id,
       title,
-      artist: "MIDI Import".to_string(),
+      artist: default_artist
+        .to_string(),
       composer: String::new(),
       arranger: "MIDI Loader"
         .to_string(),
-      description: format!(
-        "Imported from MIDI file {}",
-        path.display()
-      ),
+      description,
       difficulty: 2,
       tempo_bpm,
       beats_per_bar,
@@ -983,10 +2207,16 @@ fn parse_midi_song(
         .to_string_lossy()
         .to_string(),
       sort_order: 200,
-      default_velocity
+      default_velocity,
+      gm_program,
+      gain: None,
+      metronome:
+        MetronomeOverride::Default
     },
-    sections: Vec::new(),
-    events
+    sections,
+    events,
+    note_stream: Vec::new(),
+    tempo_map
   };
 
   finalize_song(&mut song, path)?;
@@ -1004,7 +2234,11 @@ fn handle_midi_message(
     (u8, u8),
     Vec<(u64, u8)>
   >,
-  note_ranges: &mut Vec<MidiNoteRange>
+  note_ranges: &mut Vec<MidiNoteRange>,
+  channel_programs: &mut BTreeMap<
+    u8,
+    u8
+  >
 ) {
   match message {
     | MidiMessage::NoteOn {
@@ -1014,7 +2248,8 @@ fn handle_midi_message(
       let note = key.as_int();
       let velocity = vel.as_int();
 
-      if velocity == 0 {
+      if is_note_off_velocity(velocity)
+      {
         finish_active_note(
           channel,
           note,
@@ -1044,10 +2279,50 @@ fn handle_midi_message(
         note_ranges
       );
     }
+    | MidiMessage::ProgramChange {
+      program
+    } => {
+      channel_programs
+        .entry(channel)
+        .or_insert(program.as_int());
+    }
     | _ => {}
   }
 }
 
+fn primary_gm_program(
+  note_ranges: &[MidiNoteRange],
+  channel_programs: &BTreeMap<u8, u8>
+) -> Option<u8> {
+  let mut note_counts =
+    BTreeMap::<u8, usize>::new();
+  for range in note_ranges {
+    *note_counts
+      .entry(range.channel)
+      .or_default() += 1;
+  }
+
+  channel_programs
+    .iter()
+    .filter(|(channel, _)| {
+      **channel != 9
+    })
+    .max_by(|left, right| {
+      let left_count = note_counts
+        .get(left.0)
+        .copied()
+        .unwrap_or(0);
+      let right_count = note_counts
+        .get(right.0)
+        .copied()
+        .unwrap_or(0);
+      left_count
+        .cmp(&right_count)
+        .then(right.0.cmp(left.0))
+    })
+    .map(|(_, program)| *program)
+}
+
 fn finish_active_note(
   channel: u8,
   note: u8,
@@ -1072,6 +2347,7 @@ fn finish_active_note(
       note_ranges.push(MidiNoteRange {
         start_tick,
         end_tick,
+        channel,
         note,
         velocity
       });
@@ -1079,13 +2355,80 @@ fn finish_active_note(
   }
 }
 
+fn merge_tied_note_ranges(
+  mut ranges: Vec<MidiNoteRange>,
+  gap_ticks: u64
+) -> Vec<MidiNoteRange> {
+  ranges.sort_by(|left, right| {
+    left
+      .channel
+      .cmp(&right.channel)
+      .then(left.note.cmp(&right.note))
+      .then(
+        left
+          .start_tick
+          .cmp(&right.start_tick)
+      )
+  });
+
+  let mut merged =
+    Vec::<MidiNoteRange>::with_capacity(
+      ranges.len()
+    );
+  for range in ranges {
+    if let Some(previous) =
+      merged.last_mut()
+      && previous.channel
+        == range.channel
+      && previous.note == range.note
+      && range.start_tick
+        >= previous.end_tick
+      && range.start_tick
+        - previous.end_tick
+        <= gap_ticks
+    {
+      previous.end_tick = previous
+        .end_tick
+        .max(range.end_tick);
+      continue;
+    }
+
+    merged.push(range);
+  }
+
+  merged
+}
+
 fn handle_meta_message(
   message: MetaMessage,
   absolute_tick: u64,
   tempo_changes: &mut Vec<(u64, u32)>,
-  time_signature: &mut Option<(u8, u8)>
+  time_signature: &mut Option<(u8, u8)>,
+  text_meta: &mut MidiTextMeta
 ) {
   match message {
+    | MetaMessage::TrackName(bytes) => {
+      if text_meta.track_name.is_none()
+      {
+        text_meta.track_name =
+          midi_meta_text(bytes);
+      }
+    }
+    | MetaMessage::Copyright(bytes) => {
+      if text_meta.copyright.is_none() {
+        text_meta.copyright =
+          midi_meta_text(bytes);
+      }
+    }
+    | MetaMessage::Marker(bytes) => {
+      if let Some(label) =
+        midi_meta_text(bytes)
+      {
+        text_meta
+          .markers
+          .push((absolute_tick, label));
+      }
+    }
     | MetaMessage::Tempo(
       micros_per_quarter
     ) => {
@@ -1123,6 +2466,70 @@ fn handle_meta_message(
   }
 }
 
+fn midi_meta_text(
+  bytes: &[u8]
+) -> Option<String> {
+  let text =
+    String::from_utf8_lossy(bytes)
+      .chars()
+      .filter(|ch| !ch.is_control())
+      .collect::<String>();
+  let trimmed = text.trim();
+
+  (!trimmed.is_empty())
+    .then(|| trimmed.to_string())
+}
+
+fn marker_sections(
+  markers: &[(u64, String)],
+  ticks_per_beat: u32,
+  song_end_beats: f32
+) -> Vec<SongSection> {
+  let mut markers = markers.to_vec();
+  markers
+    .sort_by_key(|(tick, _)| *tick);
+  markers
+    .dedup_by_key(|(tick, _)| *tick);
+
+  let starts = markers
+    .iter()
+    .map(|(tick, _)| {
+      ticks_to_beats(
+        *tick,
+        ticks_per_beat
+      )
+    })
+    .collect::<Vec<_>>();
+
+  markers
+    .iter()
+    .enumerate()
+    .filter(|(index, _)| {
+      starts[*index] < song_end_beats
+    })
+    .map(|(index, (_, label))| {
+      let start_beats = starts[index];
+      let end_beats = starts
+        .get(index + 1)
+        .copied()
+        .unwrap_or(song_end_beats)
+        .min(song_end_beats);
+
+      SongSection {
+        id: format!(
+          "{:02}_{}",
+          index + 1,
+          sanitize_song_id(label)
+        ),
+        label: label.clone(),
+        start_beats,
+        end_beats,
+        ..SongSection::default()
+      }
+    })
+    .collect()
+}
+
 fn close_unended_notes(
   active_notes: &HashMap<
     (u8, u8),
@@ -1135,7 +2542,7 @@ fn close_unended_notes(
     u64::from(ticks_per_beat.max(1))
       / 2;
 
-  for ((_, note), starts) in
+  for ((channel, note), starts) in
     active_notes
   {
     for (start_tick, velocity) in starts
@@ -1146,6 +2553,7 @@ fn close_unended_notes(
           .saturating_add(
             fallback_duration.max(1)
           ),
+        channel:    *channel,
         note:       *note,
         velocity:   *velocity
       });
@@ -1155,6 +2563,8 @@ fn close_unended_notes(
 
 fn ticks_per_beat_from_timing(
   timing: Timing,
+  tempo_bpm: f32,
+  options: &MidiImportConfig,
   path: &Path
 ) -> u32 {
   match timing {
@@ -1163,10 +2573,69 @@ fn ticks_per_beat_from_timing(
     ) => {
       u32::from(ticks_per_beat.as_int())
     }
+    | Timing::Timecode(
+      fps,
+      subframes
+    ) if options
+      .convert_smpte_timing =>
+    {
+      let ticks_per_second = fps
+        .as_f32()
+        * f32::from(subframes.max(1));
+      let ticks_per_beat =
+        (ticks_per_second * 60.0
+          / tempo_bpm)
+          .round()
+          .max(1.0) as u32;
+      debug!(path = %path.display(), fps = fps.as_f32(), subframes, ticks_per_beat, "converted SMPTE MIDI timing");
+      ticks_per_beat
+    }
     | Timing::Timecode(_, _) => {
-      warn!(path = %path.display(), "MIDI uses SMPTE timing; using fallback ticks_per_beat=480");
-      480
+      let fallback = options
+        .smpte_fallback_ticks_per_beat;
+      warn!(path = %path.display(), fallback, "MIDI uses SMPTE timing; using fallback ticks_per_beat");
+      fallback
+    }
+  }
+}
+
+fn midi_tempo_map(
+  tempo_changes: &[(u64, u32)],
+  ticks_per_beat: u32
+) -> Vec<(f32, f32)> {
+  let mut tempo_map: Vec<(f32, f32)> =
+    Vec::new();
+  for (tick, micros) in tempo_changes {
+    let at_beats = ticks_to_beats(
+      *tick,
+      ticks_per_beat
+    );
+    let bpm = (60_000_000.0
+      / (*micros).max(1) as f32)
+      .clamp(
+        MIN_TEMPO_BPM,
+        MAX_TEMPO_BPM
+      );
+    if let Some(last) =
+      tempo_map.last_mut()
+      && last.0 == at_beats
+    {
+      last.1 = bpm;
+      continue;
     }
+    if tempo_map
+      .last()
+      .is_some_and(|last| last.1 == bpm)
+    {
+      continue;
+    }
+    tempo_map.push((at_beats, bpm));
+  }
+
+  if tempo_map.len() > 1 {
+    tempo_map
+  } else {
+    Vec::new()
   }
 }
 
@@ -1273,12 +2742,17 @@ fn finalize_song(
   song: &mut SongFile,
   source_path: &Path
 ) -> Result<()> {
-  song.events.sort_by(|left, right| {
-    left
-      .at_beats
-      .total_cmp(&right.at_beats)
-  });
+  if !song.note_stream.is_empty() {
+    let stream_events =
+      note_stream_to_events(
+        &song.note_stream,
+        source_path
+      )?;
+    song.events.extend(stream_events);
+    song.note_stream.clear();
+  }
 
+  tidy_song_events(song, source_path);
   validate_song(song, source_path)?;
 
   debug!(
@@ -1291,9 +2765,192 @@ fn finalize_song(
   Ok(())
 }
 
-fn validate_song(
-  song: &SongFile,
-  path: &Path
+fn tidy_song_events(
+  song: &mut SongFile,
+  source_path: &Path
+) {
+  let before = song.events.len();
+  song.events.retain(|event| {
+    !event
+      .velocity
+      .is_some_and(is_note_off_velocity)
+  });
+  if song.events.len() < before {
+    debug!(path = %source_path.display(), rests = before - song.events.len(), "dropped velocity-0 events as rests");
+  }
+
+  song.events.sort_by(|left, right| {
+    left
+      .at_beats
+      .total_cmp(&right.at_beats)
+  });
+}
+
+fn note_stream_to_events(
+  stream: &[NoteStreamEvent],
+  path: &Path
+) -> Result<Vec<SongEvent>> {
+  let mut ordered: Vec<
+    &NoteStreamEvent
+  > = stream.iter().collect();
+  ordered.sort_by(|left, right| {
+    left
+      .at_beats
+      .total_cmp(&right.at_beats)
+      .then(
+        left
+          .is_note_on()
+          .cmp(&right.is_note_on())
+      )
+  });
+
+  let mut open: HashMap<
+    (u8, Option<u8>),
+    &NoteStreamEvent
+  > = HashMap::new();
+  let mut events = Vec::new();
+
+  for entry in ordered {
+    if !entry.at_beats.is_finite()
+      || entry.at_beats < 0.0
+    {
+      bail!(
+        "{} note_stream has invalid \
+         at_beats {}",
+        path.display(),
+        entry.at_beats
+      );
+    }
+
+    let key =
+      (entry.note, entry.channel);
+    if entry.is_note_on() {
+      if open.contains_key(&key) {
+        bail!(
+          "{} note_stream turns note \
+           {} on at beat {} while it \
+           is already sounding",
+          path.display(),
+          entry.note,
+          entry.at_beats
+        );
+      }
+      open.insert(key, entry);
+      continue;
+    }
+
+    let Some(start) = open.remove(&key)
+    else {
+      bail!(
+        "{} note_stream turns note {} \
+         off at beat {} without a \
+         matching on",
+        path.display(),
+        entry.note,
+        entry.at_beats
+      );
+    };
+
+    let duration_beats =
+      entry.at_beats - start.at_beats;
+    if duration_beats <= 0.0 {
+      bail!(
+        "{} note_stream note {} at \
+         beat {} has zero length",
+        path.display(),
+        start.note,
+        start.at_beats
+      );
+    }
+
+    events.push(SongEvent {
+      at_beats: start.at_beats,
+      duration_beats,
+      notes: vec![start.note],
+      velocity: start.velocity,
+      hand: start.hand,
+      channel: start.channel,
+      ..SongEvent::default()
+    });
+  }
+
+  if let Some(start) = open
+    .values()
+    .min_by(|left, right| {
+      left
+        .at_beats
+        .total_cmp(&right.at_beats)
+    })
+  {
+    bail!(
+      "{} note_stream note {} at beat \
+       {} is never turned off",
+      path.display(),
+      start.note,
+      start.at_beats
+    );
+  }
+
+  Ok(events)
+}
+
+fn song_schema_issue(
+  song: &SongFile,
+  path: &Path,
+  library_schema: &str
+) -> Option<String> {
+  let declared = song.schema.trim();
+  if declared.is_empty() {
+    return Some(
+      "schema field is empty"
+        .to_string()
+    );
+  }
+
+  let candidates = [
+    PathBuf::from(declared),
+    path
+      .parent()
+      .unwrap_or_else(|| Path::new("."))
+      .join(declared)
+  ];
+  let Some(resolved) =
+    candidates.iter().find(
+      |candidate| candidate.is_file()
+    )
+  else {
+    return Some(format!(
+      "schema {declared} does not \
+       exist"
+    ));
+  };
+
+  let matches_library =
+    fs::canonicalize(resolved)
+      .ok()
+      .zip(
+        fs::canonicalize(
+          library_schema
+        )
+        .ok()
+      )
+      .is_some_and(|(left, right)| {
+        left == right
+      });
+  if !matches_library {
+    return Some(format!(
+      "schema {declared} differs from \
+       song_library.schema_path \
+       {library_schema}"
+    ));
+  }
+
+  None
+}
+
+fn validate_song(
+  song: &SongFile,
+  path: &Path
 ) -> Result<()> {
   if song.version == 0 {
     bail!(
@@ -1323,6 +2980,57 @@ fn validate_song(
     );
   }
 
+  if !(MIN_TEMPO_BPM..=MAX_TEMPO_BPM)
+    .contains(&song.meta.tempo_bpm)
+  {
+    warn!(path = %path.display(), tempo_bpm = song.meta.tempo_bpm, "song tempo is outside {MIN_TEMPO_BPM}..={MAX_TEMPO_BPM} BPM; playback and metronome may be unusable");
+  }
+
+  let mut previous_tempo_beats = 0.0;
+  for (index, (at_beats, tempo)) in
+    song.tempo_map.iter().enumerate()
+  {
+    if !tempo.is_finite()
+      || *tempo <= 0.0
+    {
+      bail!(
+        "{} tempo_map[{index}] has \
+         non-positive or non-finite \
+         tempo",
+        path.display()
+      );
+    }
+    if !at_beats.is_finite()
+      || *at_beats
+        < previous_tempo_beats
+    {
+      bail!(
+        "{} tempo_map[{index}] must \
+         be finite, sorted by beat \
+         and non-negative",
+        path.display()
+      );
+    }
+    if !(MIN_TEMPO_BPM..=MAX_TEMPO_BPM)
+      .contains(tempo)
+    {
+      warn!(path = %path.display(), tempo_map = index, tempo_bpm = tempo, "tempo map entry is outside {MIN_TEMPO_BPM}..={MAX_TEMPO_BPM} BPM");
+    }
+    previous_tempo_beats = *at_beats;
+  }
+
+  if let Some(gain) = song.meta.gain
+    && !(MIN_SONG_GAIN..=MAX_SONG_GAIN)
+      .contains(&gain)
+  {
+    bail!(
+      "{} has meta.gain {gain} \
+       outside {MIN_SONG_GAIN}..\
+       ={MAX_SONG_GAIN}",
+      path.display()
+    );
+  }
+
   if song.meta.beats_per_bar == 0 {
     bail!(
       "{} has beats_per_bar = 0",
@@ -1409,6 +3117,43 @@ fn validate_song(
         );
       }
     }
+
+    if let Some(channel) = event.channel
+      && !(1..=16).contains(&channel)
+    {
+      bail!(
+        "{} event[{index}] has \
+         channel outside 1..=16",
+        path.display()
+      );
+    }
+
+    if !event.fingering.is_empty() {
+      if event.fingering.len()
+        != event.notes.len()
+      {
+        bail!(
+          "{} event[{index}] has {} \
+           fingering entries for {} \
+           notes",
+          path.display(),
+          event.fingering.len(),
+          event.notes.len()
+        );
+      }
+
+      if event.fingering.iter().any(
+        |finger| {
+          !(1..=5).contains(finger)
+        }
+      ) {
+        bail!(
+          "{} event[{index}] has \
+           fingering outside 1..=5",
+          path.display()
+        );
+      }
+    }
   }
 
   for (index, section) in
@@ -1431,7 +3176,1347 @@ fn validate_song(
         path.display()
       );
     }
+
+    if section.tempo_bpm.is_some_and(
+      |tempo| {
+        !tempo.is_finite()
+          || tempo <= 0.0
+      }
+    ) {
+      bail!(
+        "{} section[{index}] has \
+         non-positive or non-finite \
+         tempo_bpm",
+        path.display()
+      );
+    }
+
+    if let Some(tempo) =
+      section.tempo_bpm
+      && !(MIN_TEMPO_BPM
+        ..=MAX_TEMPO_BPM)
+        .contains(&tempo)
+    {
+      warn!(path = %path.display(), section = index, tempo_bpm = tempo, "section tempo is outside {MIN_TEMPO_BPM}..={MAX_TEMPO_BPM} BPM");
+    }
+
+    if section.loop_count.is_some_and(
+      |count| {
+        !(1..=64).contains(&count)
+      }
+    ) {
+      bail!(
+        "{} section[{index}] has \
+         loop_count outside 1..=64",
+        path.display()
+      );
+    }
   }
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn merges_back_to_back_identical_notes()
+   {
+    let ranges = vec![
+      MidiNoteRange {
+        start_tick: 0,
+        end_tick:   480,
+        channel:    0,
+        note:       60,
+        velocity:   90
+      },
+      MidiNoteRange {
+        start_tick: 480,
+        end_tick:   960,
+        channel:    0,
+        note:       60,
+        velocity:   80
+      },
+      MidiNoteRange {
+        start_tick: 480,
+        end_tick:   960,
+        channel:    0,
+        note:       64,
+        velocity:   80
+      },
+    ];
+
+    let merged =
+      merge_tied_note_ranges(ranges, 0);
+
+    assert_eq!(merged.len(), 2);
+    assert_eq!(merged[0].note, 60);
+    assert_eq!(merged[0].start_tick, 0);
+    assert_eq!(merged[0].end_tick, 960);
+    assert_eq!(merged[0].velocity, 90);
+    assert_eq!(merged[1].note, 64);
+  }
+
+  #[test]
+  fn keeps_rearticulated_notes_past_gap()
+   {
+    let ranges = vec![
+      MidiNoteRange {
+        start_tick: 0,
+        end_tick:   400,
+        channel:    0,
+        note:       60,
+        velocity:   90
+      },
+      MidiNoteRange {
+        start_tick: 480,
+        end_tick:   960,
+        channel:    0,
+        note:       60,
+        velocity:   90
+      },
+    ];
+
+    let merged = merge_tied_note_ranges(
+      ranges, 40
+    );
+
+    assert_eq!(merged.len(), 2);
+  }
+
+  #[test]
+  fn keeps_same_pitch_on_other_channels_apart()
+   {
+    let ranges = vec![
+      MidiNoteRange {
+        start_tick: 0,
+        end_tick:   480,
+        channel:    0,
+        note:       48,
+        velocity:   90
+      },
+      MidiNoteRange {
+        start_tick: 480,
+        end_tick:   960,
+        channel:    1,
+        note:       48,
+        velocity:   90
+      },
+    ];
+
+    let merged =
+      merge_tied_note_ranges(ranges, 0);
+
+    assert_eq!(merged.len(), 2);
+  }
+
+  #[test]
+  fn rejects_zero_section_loop_count() {
+    let mut song = SongFile::default();
+    song.events.push(SongEvent {
+      notes: vec![60],
+      ..SongEvent::default()
+    });
+    song.sections.push(SongSection {
+      id: "a".to_string(),
+      end_beats: 4.0,
+      loop_count: Some(0),
+      ..SongSection::default()
+    });
+
+    assert!(
+      validate_song(
+        &song,
+        Path::new("song.toml")
+      )
+      .is_err()
+    );
+
+    song.sections[0].loop_count =
+      Some(3);
+    assert!(
+      validate_song(
+        &song,
+        Path::new("song.toml")
+      )
+      .is_ok()
+    );
+
+    song.sections[0].tempo_bpm =
+      Some(f32::NAN);
+    assert!(
+      validate_song(
+        &song,
+        Path::new("song.toml")
+      )
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn rejects_channels_outside_midi_range()
+   {
+    let mut song = SongFile::default();
+    song.events.push(SongEvent {
+      notes: vec![60],
+      channel: Some(16),
+      ..SongEvent::default()
+    });
+    assert!(
+      validate_song(
+        &song,
+        Path::new("song.toml")
+      )
+      .is_ok()
+    );
+
+    for channel in [0, 17] {
+      song.events[0].channel =
+        Some(channel);
+      assert!(
+        validate_song(
+          &song,
+          Path::new("song.toml")
+        )
+        .is_err()
+      );
+    }
+  }
+
+  #[test]
+  fn rejects_non_finite_tempo_map_entries()
+   {
+    let mut song = SongFile::default();
+    song.events.push(SongEvent {
+      notes: vec![60],
+      ..SongEvent::default()
+    });
+    song.tempo_map =
+      vec![(0.0, 120.0), (4.0, 90.0)];
+    assert!(
+      validate_song(
+        &song,
+        Path::new("song.toml")
+      )
+      .is_ok()
+    );
+
+    song.tempo_map[1].1 = f32::NAN;
+    assert!(
+      validate_song(
+        &song,
+        Path::new("song.toml")
+      )
+      .is_err()
+    );
+
+    song.tempo_map[1] =
+      (f32::NAN, 90.0);
+    assert!(
+      validate_song(
+        &song,
+        Path::new("song.toml")
+      )
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn validates_fingering_against_notes()
+  {
+    let mut song = SongFile::default();
+    song.events.push(SongEvent {
+      notes: vec![60, 64],
+      fingering: vec![1],
+      ..SongEvent::default()
+    });
+
+    assert!(
+      validate_song(
+        &song,
+        Path::new("song.toml")
+      )
+      .is_err()
+    );
+
+    song.events[0].fingering =
+      vec![1, 6];
+    assert!(
+      validate_song(
+        &song,
+        Path::new("song.toml")
+      )
+      .is_err()
+    );
+
+    song.events[0].fingering =
+      vec![1, 3];
+    assert!(
+      validate_song(
+        &song,
+        Path::new("song.toml")
+      )
+      .is_ok()
+    );
+  }
+
+  #[test]
+  fn treats_velocity_zero_as_note_off()
+  {
+    let mut song = SongFile {
+      events: vec![
+        SongEvent {
+          notes: vec![60],
+          ..SongEvent::default()
+        },
+        SongEvent {
+          at_beats: 1.0,
+          notes: vec![62],
+          velocity: Some(0),
+          ..SongEvent::default()
+        },
+      ],
+      note_stream: vec![
+        NoteStreamEvent {
+          at_beats: 2.0,
+          note: 64,
+          ..NoteStreamEvent::default()
+        },
+        NoteStreamEvent {
+          at_beats: 3.0,
+          note: 64,
+          velocity: Some(0),
+          ..NoteStreamEvent::default()
+        },
+      ],
+      ..SongFile::default()
+    };
+
+    finalize_song(
+      &mut song,
+      Path::new("song.toml")
+    )
+    .unwrap();
+
+    let notes = song
+      .events
+      .iter()
+      .map(|event| {
+        (
+          event.notes[0],
+          event.duration_beats
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(notes, vec![
+      (60, 1.0),
+      (64, 1.0)
+    ]);
+  }
+
+  #[test]
+  fn converts_note_stream_to_events() {
+    let stream = [
+      (0.0, 48, true),
+      (0.0, 64, true),
+      (1.0, 64, false),
+      (1.0, 67, true),
+      (2.0, 67, false),
+      (2.0, 67, true),
+      (3.0, 67, false),
+      (4.0, 48, false)
+    ];
+    let mut song = SongFile {
+      note_stream: stream
+        .iter()
+        .map(|&(at_beats, note, on)| {
+          NoteStreamEvent {
+            at_beats,
+            note,
+            on,
+            ..NoteStreamEvent::default()
+          }
+        })
+        .collect(),
+      ..SongFile::default()
+    };
+
+    finalize_song(
+      &mut song,
+      Path::new("song.toml")
+    )
+    .unwrap();
+
+    assert!(
+      song.note_stream.is_empty()
+    );
+    let spans: Vec<(f32, f32, u8)> =
+      song
+        .events
+        .iter()
+        .map(|event| {
+          (
+            event.at_beats,
+            event.duration_beats,
+            event.notes[0]
+          )
+        })
+        .collect();
+    assert_eq!(spans.len(), 4);
+    assert!(
+      spans.contains(&(0.0, 4.0, 48))
+    );
+    assert!(
+      spans.contains(&(0.0, 1.0, 64))
+    );
+    assert!(
+      spans.contains(&(1.0, 1.0, 67))
+    );
+    assert!(
+      spans.contains(&(2.0, 1.0, 67))
+    );
+
+    let mut unterminated =
+      SongFile::default();
+    unterminated
+      .note_stream
+      .push(NoteStreamEvent::default());
+    assert!(
+      finalize_song(
+        &mut unterminated,
+        Path::new("song.toml")
+      )
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn normalizing_keeps_note_stream_form()
+   {
+    let song = SongFile {
+      note_stream: vec![
+        NoteStreamEvent {
+          at_beats: 0.0,
+          note: 60,
+          ..NoteStreamEvent::default()
+        },
+        NoteStreamEvent {
+          at_beats: 1.0,
+          note: 60,
+          on: false,
+          ..NoteStreamEvent::default()
+        },
+      ],
+      ..SongFile::default()
+    };
+    let dir = std::env::temp_dir()
+      .join(format!(
+        "symfose-normalize-{}",
+        std::process::id()
+      ));
+    fs::create_dir_all(&dir)
+      .expect("temp dir");
+    let path = dir.join("stream.toml");
+    fs::write(
+      &path,
+      toml::to_string(&song)
+        .expect("serialize song")
+    )
+    .expect("write song");
+
+    normalize_song_file(&path)
+      .expect("normalize");
+    let normalized =
+      parse_toml_song(&path)
+        .expect("parse normalized");
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(
+      normalized.events.is_empty()
+    );
+    let stream = normalized
+      .note_stream
+      .iter()
+      .map(|event| {
+        (
+          event.at_beats,
+          event.note,
+          event.on
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(stream, vec![
+      (0.0, 60, true),
+      (1.0, 60, false)
+    ]);
+  }
+
+  #[test]
+  fn converts_smpte_timing_to_beats() {
+    use midly::num::{
+      u4,
+      u7,
+      u24,
+      u28
+    };
+    use midly::{
+      Format,
+      Fps,
+      Header,
+      TrackEvent
+    };
+
+    let note =
+      |delta: u32,
+       key: u8,
+       on: bool| {
+        TrackEvent {
+          delta: u28::new(delta),
+          kind:  TrackEventKind::Midi {
+            channel: u4::new(0),
+            message: if on {
+              MidiMessage::NoteOn {
+                key: u7::new(key),
+                vel: u7::new(100)
+              }
+            } else {
+              MidiMessage::NoteOff {
+                key: u7::new(key),
+                vel: u7::new(0)
+              }
+            }
+          }
+        }
+      };
+
+    // 25 fps * 40 subframes = 1000
+    // ticks per second.
+    let mut smf =
+      Smf::new(Header::new(
+        Format::SingleTrack,
+        Timing::Timecode(
+          Fps::Fps25,
+          40
+        )
+      ));
+    smf.tracks.push(vec![
+      TrackEvent {
+        delta: u28::new(0),
+        kind:  TrackEventKind::Meta(
+          MetaMessage::Tempo(u24::new(
+            500_000
+          ))
+        )
+      },
+      note(0, 60, true),
+      note(1_000, 60, false),
+      note(1_000, 64, true),
+      note(500, 64, false),
+      TrackEvent {
+        delta: u28::new(0),
+        kind:  TrackEventKind::Meta(
+          MetaMessage::EndOfTrack
+        )
+      },
+    ]);
+
+    let dir = std::env::temp_dir()
+      .join(format!(
+        "symfose-smpte-{}",
+        std::process::id()
+      ));
+    fs::create_dir_all(&dir)
+      .expect("temp dir");
+    let path = dir.join("smpte.mid");
+    smf
+      .save(&path)
+      .expect("write midi");
+
+    let song = parse_midi_song(
+      &path,
+      "schema.json",
+      &dir,
+      &MidiImportConfig::default(),
+      &["midi".to_string()],
+      "MIDI Import"
+    )
+    .expect("parse smpte midi");
+    let _ = fs::remove_dir_all(&dir);
+
+    let seconds_per_beat =
+      60.0 / song.meta.tempo_bpm;
+    let onsets = song
+      .events
+      .iter()
+      .map(|event| {
+        event.at_beats
+          * seconds_per_beat
+      })
+      .collect::<Vec<_>>();
+
+    assert_eq!(onsets.len(), 2);
+    assert!(onsets[0].abs() < 1e-3);
+    assert!(
+      (onsets[1] - 2.0).abs() < 1e-3
+    );
+    assert!(
+      (song.events[1].duration_beats
+        * seconds_per_beat
+        - 0.5)
+        .abs()
+        < 1e-3
+    );
+  }
+
+  #[test]
+  fn groups_staggered_chord_releases_by_onset()
+   {
+    use midly::num::{
+      u4,
+      u7,
+      u15,
+      u28
+    };
+    use midly::{
+      Format,
+      Header,
+      TrackEvent
+    };
+
+    let note =
+      |delta: u32,
+       key: u8,
+       on: bool| {
+        TrackEvent {
+          delta: u28::new(delta),
+          kind:  TrackEventKind::Midi {
+            channel: u4::new(0),
+            message: if on {
+              MidiMessage::NoteOn {
+                key: u7::new(key),
+                vel: u7::new(100)
+              }
+            } else {
+              MidiMessage::NoteOff {
+                key: u7::new(key),
+                vel: u7::new(0)
+              }
+            }
+          }
+        }
+      };
+
+    let mut smf =
+      Smf::new(Header::new(
+        Format::SingleTrack,
+        Timing::Metrical(u15::new(480))
+      ));
+    smf.tracks.push(vec![
+      note(0, 60, true),
+      note(0, 64, true),
+      note(0, 67, true),
+      note(240, 67, false),
+      note(240, 64, false),
+      note(480, 60, false),
+      TrackEvent {
+        delta: u28::new(0),
+        kind:  TrackEventKind::Meta(
+          MetaMessage::EndOfTrack
+        )
+      },
+    ]);
+
+    let dir = std::env::temp_dir()
+      .join(format!(
+        "symfose-chord-{}",
+        std::process::id()
+      ));
+    fs::create_dir_all(&dir)
+      .expect("temp dir");
+    let path = dir.join("chord.mid");
+    smf
+      .save(&path)
+      .expect("write midi");
+
+    let parse = |grouping| {
+      let options = MidiImportConfig {
+        chord_grouping: grouping,
+        ..MidiImportConfig::default()
+      };
+      parse_midi_song(
+        &path,
+        "schema.json",
+        &dir,
+        &options,
+        &["midi".to_string()],
+        "MIDI Import"
+      )
+      .expect("parse chord midi")
+    };
+
+    let exact = parse(
+      ChordGrouping::ExactDuration
+    );
+    let longest = parse(
+      ChordGrouping::OnsetLongest
+    );
+    let shortest = parse(
+      ChordGrouping::OnsetShortest
+    );
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(exact.events.len(), 3);
+
+    assert_eq!(longest.events.len(), 1);
+    assert_eq!(
+      longest.events[0].notes,
+      vec![60, 64, 67]
+    );
+    assert!(
+      (longest.events[0]
+        .duration_beats
+        - 2.0)
+        .abs()
+        < 1e-3
+    );
+
+    assert_eq!(
+      shortest.events.len(),
+      1
+    );
+    assert!(
+      (shortest.events[0]
+        .duration_beats
+        - 0.5)
+        .abs()
+        < 1e-3
+    );
+  }
+
+  #[test]
+  fn picks_program_of_busiest_melodic_channel()
+   {
+    let range = |channel| {
+      MidiNoteRange {
+        start_tick: 0,
+        end_tick: 480,
+        channel,
+        note: 60,
+        velocity: 90
+      }
+    };
+    let ranges = vec![
+      range(0),
+      range(1),
+      range(1),
+      range(9),
+      range(9),
+      range(9),
+    ];
+    let programs = BTreeMap::from([
+      (0, 0),
+      (1, 40),
+      (9, 0)
+    ]);
+
+    let program = primary_gm_program(
+      &ranges, &programs
+    );
+    assert_eq!(program, Some(40));
+
+    let meta = SongMetadata {
+      gm_program: program,
+      ..SongMetadata::default()
+    };
+    assert_eq!(
+      meta.gm_program_name(),
+      Some("Violin")
+    );
+  }
+
+  #[test]
+  fn builds_sections_from_midi_markers()
+  {
+    let markers = vec![
+      (960, "Chorus".to_string()),
+      (0, "Verse 1".to_string()),
+      (960, "Duplicate".to_string()),
+      (4_800, "Past end".to_string()),
+    ];
+
+    let sections = marker_sections(
+      &markers, 480, 6.0
+    );
+
+    assert_eq!(sections.len(), 2);
+    assert_eq!(
+      sections[0].id,
+      "01_verse_1"
+    );
+    assert_eq!(
+      sections[0].end_beats,
+      2.0
+    );
+    assert_eq!(
+      sections[1].label,
+      "Chorus"
+    );
+    assert_eq!(
+      sections[1].end_beats,
+      6.0
+    );
+    assert_eq!(
+      midi_meta_text(b"  My Song\0 "),
+      Some("My Song".to_string())
+    );
+  }
+
+  #[test]
+  fn dedupes_songs_present_as_toml_and_midi()
+   {
+    let song =
+      |id: &str, path: &str| {
+        let mut file =
+          SongFile::default();
+        file.meta.id = id.to_string();
+        LoadedSong::parsed(
+          PathBuf::from(path),
+          file
+        )
+      };
+    let loaded = || {
+      vec![
+        (
+          SourceKind::Toml,
+          song("ode", "songs/ode.toml")
+        ),
+        (
+          SourceKind::Midi,
+          song("ode", "midi/ode.mid")
+        ),
+        (
+          SourceKind::Midi,
+          song(
+            "waltz",
+            "midi/waltz.mid"
+          )
+        ),
+      ]
+    };
+
+    let (kept, hidden) =
+      dedupe_mixed_sources(
+        loaded(),
+        DuplicateSourcePreference::KeepBoth
+      );
+    assert_eq!(
+      (kept.len(), hidden),
+      (3, 0)
+    );
+
+    let (kept, hidden) =
+      dedupe_mixed_sources(
+        loaded(),
+        DuplicateSourcePreference::PreferToml
+      );
+    assert_eq!(hidden, 1);
+    assert_eq!(
+      kept
+        .iter()
+        .map(|song| song.path.clone())
+        .collect::<Vec<_>>(),
+      vec![
+        PathBuf::from("songs/ode.toml"),
+        PathBuf::from("midi/waltz.mid"),
+      ]
+    );
+
+    let (kept, hidden) =
+      dedupe_mixed_sources(
+        loaded(),
+        DuplicateSourcePreference::PreferMidi
+      );
+    assert_eq!(hidden, 1);
+    assert!(kept.iter().all(|song| {
+      song.path.starts_with("midi")
+    }));
+  }
+
+  #[test]
+  fn song_metronome_override_beats_global_setting()
+   {
+    let song: SongFile =
+      toml::from_str(
+        "version = 1\n[meta]\nid = \
+         \"intro\"\ntitle = \
+         \"Intro\"\nmetronome = \
+         \"off\"\n"
+      )
+      .expect("parse song");
+    assert_eq!(
+      song.meta.metronome,
+      MetronomeOverride::Off
+    );
+    assert!(
+      !song
+        .meta
+        .metronome
+        .resolve(true)
+    );
+    assert!(
+      MetronomeOverride::On
+        .resolve(false)
+    );
+    assert!(
+      MetronomeOverride::Default
+        .resolve(true)
+    );
+
+    let loud = concat!(
+      "version = 1\n",
+      "[meta]\n",
+      "metronome = \"loud\"\n"
+    );
+    assert!(
+      toml::from_str::<SongFile>(loud)
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn parser_config_hash_tracks_relevant_settings()
+   {
+    let base =
+      SongLibraryConfig::default();
+    let mut tie_gap = base.clone();
+    tie_gap.midi_import.tie_gap_beats =
+      0.1;
+    let mut salted = base.clone();
+    salted.cache_key_salt =
+      "quantize-fix".to_string();
+
+    let hash =
+      |config: &SongLibraryConfig,
+       kind| {
+        parser_config_hash(config, kind)
+      };
+
+    assert_eq!(
+      hash(&base, SourceKind::Midi),
+      hash(
+        &base.clone(),
+        SourceKind::Midi
+      )
+    );
+    assert_ne!(
+      hash(&base, SourceKind::Midi),
+      hash(&tie_gap, SourceKind::Midi)
+    );
+    assert_eq!(
+      hash(&base, SourceKind::Toml),
+      hash(&tie_gap, SourceKind::Toml)
+    );
+    assert_ne!(
+      hash(&base, SourceKind::Toml),
+      hash(&salted, SourceKind::Toml)
+    );
+    assert_ne!(
+      hash(&base, SourceKind::Midi),
+      hash(&salted, SourceKind::Midi)
+    );
+  }
+
+  #[test]
+  fn parser_config_hash_is_stable_across_builds()
+   {
+    assert_eq!(
+      fnv1a_hash(&[]),
+      0xcbf2_9ce4_8422_2325
+    );
+    assert_eq!(
+      fnv1a_hash(&["a"]),
+      0x529a_4ddc_8ff5_6bbf
+    );
+    assert_ne!(
+      fnv1a_hash(&["ab", ""]),
+      fnv1a_hash(&["a", "b"])
+    );
+  }
+
+  #[test]
+  fn checks_song_schema_reference() {
+    let dir = std::env::temp_dir()
+      .join(format!(
+        "symfose-schema-{}",
+        std::process::id()
+      ));
+    fs::create_dir_all(&dir)
+      .expect("temp dir");
+    let schema =
+      dir.join("song.schema.json");
+    let other = dir.join("other.json");
+    fs::write(&schema, "{}")
+      .expect("write schema");
+    fs::write(&other, "{}")
+      .expect("write other");
+    let song_path =
+      dir.join("song.toml");
+    let library_schema =
+      schema.display().to_string();
+
+    let issue = |schema: &str| {
+      song_schema_issue(
+        &SongFile {
+          schema: schema.to_string(),
+          ..SongFile::default()
+        },
+        &song_path,
+        &library_schema
+      )
+    };
+
+    let relative =
+      issue("song.schema.json");
+    let typo =
+      issue("song.shcema.json");
+    let different = issue("other.json");
+    let empty = issue(" ");
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(relative, None);
+    assert!(typo.is_some_and(
+      |issue| {
+        issue.contains("does not exist")
+      }
+    ));
+    assert!(different.is_some_and(
+      |issue| {
+        issue.contains("differs")
+      }
+    ));
+    assert!(empty.is_some());
+  }
+
+  #[test]
+  fn lists_unchanged_songs_from_index_and_parses_on_demand()
+   {
+    let dir = std::env::temp_dir()
+      .join(format!(
+        "symfose-index-{}",
+        std::process::id()
+      ));
+    let songs_dir = dir.join("songs");
+    fs::create_dir_all(&songs_dir)
+      .expect("temp dir");
+    fs::copy(
+      "res/songs/twinkle.toml",
+      songs_dir.join("twinkle.toml")
+    )
+    .expect("copy song");
+
+    let config = SongLibraryConfig {
+      directory: songs_dir
+        .to_string_lossy()
+        .to_string(),
+      midi_directory: dir
+        .join("midi")
+        .to_string_lossy()
+        .to_string(),
+      cache_enabled: false,
+      index_file: dir
+        .join("library.index.toml")
+        .to_string_lossy()
+        .to_string(),
+      ..SongLibraryConfig::default()
+    };
+
+    let (first, first_stats) =
+      load_song_library(&config)
+        .expect("first load");
+    let (mut second, second_stats) =
+      load_song_library(&config)
+        .expect("indexed load");
+    assert_eq!(
+      first_stats.cache_misses,
+      1
+    );
+    assert_eq!(second_stats.indexed, 1);
+    assert!(first[0].parsed);
+    assert!(!second[0].parsed);
+    assert!(
+      second[0].song.events.is_empty()
+    );
+    assert_eq!(
+      second[0].song.meta.title,
+      first[0].song.meta.title
+    );
+    assert!(
+      !first[0].outline.is_empty()
+    );
+    assert_eq!(
+      second[0].outline,
+      first[0].outline
+    );
+
+    ensure_song_parsed(
+      &mut second[0],
+      &config
+    )
+    .expect("parse on demand");
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(second[0].parsed);
+    assert_eq!(
+      second[0].song.events.len(),
+      first[0].song.events.len()
+    );
+  }
+
+  #[test]
+  fn rejects_song_gain_outside_sane_range()
+   {
+    let mut song =
+      parse_toml_song(Path::new(
+        "res/songs/twinkle.toml"
+      ))
+      .expect("parse twinkle");
+    assert_eq!(song.meta.gain(), 1.0);
+
+    song.meta.gain = Some(1.5);
+    assert!(
+      validate_song(
+        &song,
+        Path::new("gain.toml")
+      )
+      .is_ok()
+    );
+
+    song.meta.gain = Some(4.0);
+    assert!(
+      validate_song(
+        &song,
+        Path::new("gain.toml")
+      )
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn reports_cache_rebuild_when_most_sources_miss()
+   {
+    let rebuild = LibraryLoadStats {
+      cache_hits:   10,
+      cache_misses: 290,
+      indexed:      0
+    };
+    assert!(rebuild.rebuilt_cache());
+
+    let warm = LibraryLoadStats {
+      cache_hits:   2,
+      cache_misses: 1,
+      indexed:      297
+    };
+    assert!(!warm.rebuilt_cache());
+    assert!(
+      !LibraryLoadStats::default()
+        .rebuilt_cache()
+    );
+  }
+
+  #[test]
+  fn exported_midi_round_trips_notes_and_timing()
+   {
+    let mut song = SongFile::default();
+    song.meta.tempo_bpm = 90.0;
+    song.meta.beats_per_bar = 3;
+    song.meta.default_velocity = 80;
+    song.tempo_map =
+      vec![(0.0, 90.0), (2.0, 60.0)];
+    song.events = vec![
+      SongEvent {
+        at_beats: 0.0,
+        duration_beats: 1.0,
+        notes: vec![60, 64],
+        ..SongEvent::default()
+      },
+      SongEvent {
+        at_beats: 1.5,
+        duration_beats: 0.5,
+        notes: vec![67],
+        velocity: Some(110),
+        channel: Some(2),
+        ..SongEvent::default()
+      },
+    ];
+
+    let dir = std::env::temp_dir()
+      .join(format!(
+        "symfose-export-{}",
+        std::process::id()
+      ));
+    let path = dir.join("export.mid");
+    export_midi(&song, &path)
+      .expect("export midi");
+    let imported = parse_midi_song(
+      &path,
+      "schema.json",
+      &dir,
+      &MidiImportConfig::default(),
+      &["midi".to_string()],
+      "MIDI Import"
+    )
+    .expect("parse exported midi");
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(
+      imported.meta.tempo_bpm.round(),
+      90.0
+    );
+    assert_eq!(
+      imported.meta.beats_per_bar,
+      3
+    );
+    assert_eq!(
+      imported
+        .tempo_map
+        .iter()
+        .map(|(at_beats, tempo)| {
+          (*at_beats, tempo.round())
+        })
+        .collect::<Vec<_>>(),
+      vec![(0.0, 90.0), (2.0, 60.0)]
+    );
+    let onsets = imported
+      .events
+      .iter()
+      .map(|event| {
+        (
+          event.at_beats,
+          event.duration_beats,
+          event.notes.clone(),
+          event.velocity
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(onsets, vec![
+      (
+        0.0,
+        1.0,
+        vec![60, 64],
+        Some(80)
+      ),
+      (1.5, 0.5, vec![67], Some(110)),
+    ]);
+  }
+
+  #[test]
+  fn imports_midi_tempo_changes_as_tempo_map()
+   {
+    use midly::num::{
+      u4,
+      u7,
+      u15,
+      u24,
+      u28
+    };
+    use midly::{
+      Format,
+      Header,
+      TrackEvent
+    };
+
+    let tempo =
+      |delta: u32, micros: u32| {
+        TrackEvent {
+          delta: u28::new(delta),
+          kind:  TrackEventKind::Meta(
+            MetaMessage::Tempo(
+              u24::new(micros)
+            )
+          )
+        }
+      };
+    let note =
+      |delta: u32,
+       key: u8,
+       on: bool| {
+        TrackEvent {
+          delta: u28::new(delta),
+          kind:  TrackEventKind::Midi {
+            channel: u4::new(0),
+            message: if on {
+              MidiMessage::NoteOn {
+                key: u7::new(key),
+                vel: u7::new(100)
+              }
+            } else {
+              MidiMessage::NoteOff {
+                key: u7::new(key),
+                vel: u7::new(0)
+              }
+            }
+          }
+        }
+      };
+
+    let mut smf =
+      Smf::new(Header::new(
+        Format::SingleTrack,
+        Timing::Metrical(u15::new(480))
+      ));
+    smf.tracks.push(vec![
+      tempo(0, 500_000),
+      note(0, 60, true),
+      note(480, 60, false),
+      tempo(480, 1_000_000),
+      note(480, 62, true),
+      note(480, 62, false),
+      TrackEvent {
+        delta: u28::new(0),
+        kind:  TrackEventKind::Meta(
+          MetaMessage::EndOfTrack
+        )
+      },
+    ]);
+
+    let dir = std::env::temp_dir()
+      .join(format!(
+        "symfose-tempo-map-{}",
+        std::process::id()
+      ));
+    fs::create_dir_all(&dir)
+      .expect("temp dir");
+    let path = dir.join("tempo.mid");
+    smf
+      .save(&path)
+      .expect("write midi");
+    let song = parse_midi_song(
+      &path,
+      "schema.json",
+      &dir,
+      &MidiImportConfig::default(),
+      &["midi".to_string()],
+      "MIDI Import"
+    )
+    .expect("parse tempo midi");
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(
+      song.meta.tempo_bpm,
+      120.0
+    );
+    assert_eq!(song.tempo_map, vec![
+      (0.0, 120.0),
+      (2.0, 60.0)
+    ]);
+    assert_eq!(
+      song.beats_to_seconds(3.0),
+      2.0
+    );
+    assert_eq!(
+      song.beats_to_seconds(1.0),
+      0.5
+    );
+  }
+}